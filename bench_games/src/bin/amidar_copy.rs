@@ -0,0 +1,43 @@
+//! How fast can Amidar states be copied, as a tree search does before every rollout?
+//!
+//! Usage: amidar_copy [COPIES]
+
+extern crate amidar;
+extern crate toybox_core;
+
+use amidar::{Amidar, State};
+use std::time::Instant;
+use toybox_core::{AleAction, State as _};
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let n = args
+        .get(1)
+        .map(|n| n.parse::<usize>().expect("COPIES must be a number"))
+        .unwrap_or(100_000);
+
+    let mut state = State::try_new(&Amidar::default()).unwrap();
+    for _ in 0..300 {
+        state.update_mut(AleAction::LEFT.to_input());
+    }
+
+    let start = Instant::now();
+    let mut lives = 0;
+    for _ in 0..n {
+        let copy = state.copy();
+        lives += copy.lives();
+    }
+    let copy_time = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..n {
+        let mut copy = state.copy();
+        copy.update_mut(AleAction::UP.to_input());
+        lives += copy.lives();
+    }
+    let step_time = start.elapsed();
+
+    println!("{} copies (checksum {}):", n, lives);
+    println!("  copy:          {:?}", copy_time);
+    println!("  copy and step: {:?}", step_time);
+}
@@ -0,0 +1,50 @@
+//! Compare JSON and binary savestates for Amidar: how long does it take to save and restore a state?
+//!
+//! Usage: amidar_savestate [ROUND_TRIPS]
+
+extern crate amidar;
+extern crate toybox_core;
+
+use amidar::{Amidar, State};
+use std::time::Instant;
+use toybox_core::{AleAction, Simulation, State as _};
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let n = args
+        .get(1)
+        .map(|n| n.parse::<usize>().expect("ROUND_TRIPS must be a number"))
+        .unwrap_or(10000);
+
+    let config = Amidar::default();
+    let mut state = State::try_new(&config).unwrap();
+    for _ in 0..300 {
+        state.update_mut(AleAction::LEFT.to_input());
+    }
+
+    let start = Instant::now();
+    let mut json_bytes = 0;
+    for _ in 0..n {
+        let json = state.to_json();
+        json_bytes = json.len();
+        config.new_state_from_json(&json).unwrap();
+    }
+    let json_time = start.elapsed();
+
+    let start = Instant::now();
+    let mut binary_bytes = 0;
+    for _ in 0..n {
+        let bytes = state.to_bytes();
+        binary_bytes = bytes.len();
+        State::from_bytes(&config, &bytes).unwrap();
+    }
+    let binary_time = start.elapsed();
+
+    println!("{} round trips:", n);
+    println!("  json:   {:?} ({} bytes)", json_time, json_bytes);
+    println!("  binary: {:?} ({} bytes)", binary_time, binary_bytes);
+    println!(
+        "  speedup: {:.1}x",
+        json_time.as_secs_f64() / binary_time.as_secs_f64()
+    );
+}
@@ -29,6 +29,37 @@ impl Direction {
             None
         }
     }
+    /// All four directions, in the order ``from_input`` checks them.
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+    /// The input that holds just this direction's button.
+    pub fn to_input(self) -> Input {
+        let mut input = Input::default();
+        match self {
+            Direction::Up => input.up = true,
+            Direction::Down => input.down = true,
+            Direction::Left => input.left = true,
+            Direction::Right => input.right = true,
+        };
+        input
+    }
+    /// A quarter turn counter-clockwise, as seen on screen.
+    pub fn turn_left(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Left,
+            Direction::Left => Direction::Down,
+            Direction::Down => Direction::Right,
+            Direction::Right => Direction::Up,
+        }
+    }
+    /// A quarter turn clockwise, as seen on screen.
+    pub fn turn_right(self) -> Direction {
+        self.turn_left().opposite()
+    }
     pub fn opposite(self) -> Direction {
         match self {
             Direction::Up => Direction::Down,
@@ -42,6 +42,16 @@ impl Color {
         let gray = self.grayscale_byte();
         Color::rgb(gray, gray, gray)
     }
+    /// Halfway between this color and ``other``, e.g., to dim something toward the background. Keeps this color's visibility.
+    pub fn halfway_to(self, other: Color) -> Color {
+        let mid = |a: u8, b: u8| ((u16::from(a) + u16::from(b)) / 2) as u8;
+        Color::rgba(
+            mid(self.r, other.r),
+            mid(self.g, other.g),
+            mid(self.b, other.b),
+            self.a,
+        )
+    }
 
     fn float_red(&self) -> f64 {
         self.r as f64 / 255.0
@@ -145,6 +155,26 @@ impl FixedSpriteData {
         FixedSpriteData::new(output)
     }
 
+    /// The same sprite with every visible pixel halfway to ``bg``, e.g. to draw something inactive.
+    pub fn make_faded_version(&self, bg: Color) -> FixedSpriteData {
+        let data = self
+            .data
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&pixel| {
+                        if pixel.is_visible() {
+                            pixel.halfway_to(bg)
+                        } else {
+                            pixel
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        FixedSpriteData::new(data)
+    }
+
     /// Given an include_bytes! png, convert it to a FixedSpriteData.
     pub fn load_png(data: &[u8]) -> FixedSpriteData {
         let decoder = png::Decoder::new(data);
@@ -259,6 +289,86 @@ impl Drawable {
     }
 }
 
+/// A 64-bit FNV-1a hash of draw commands, fed them as little-endian, fixed-width fields, so the same commands hash the same on every Rust release and target.
+struct FnvDigest(u64);
+
+impl FnvDigest {
+    fn bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x100_0000_01b3);
+        }
+    }
+    fn int(&mut self, n: i32) {
+        self.bytes(&n.to_le_bytes());
+    }
+    fn len(&mut self, n: usize) {
+        self.bytes(&(n as u64).to_le_bytes());
+    }
+    fn color(&mut self, color: Color) {
+        self.bytes(&[color.r, color.g, color.b, color.a]);
+    }
+    fn colors(&mut self, rows: &[Vec<Color>]) {
+        self.len(rows.len());
+        for row in rows {
+            self.len(row.len());
+            for &color in row {
+                self.color(color);
+            }
+        }
+    }
+    fn drawable(&mut self, drawable: &Drawable) {
+        match drawable {
+            Drawable::Clear(color) => {
+                self.bytes(&[0]);
+                self.color(*color);
+            }
+            Drawable::Rectangle { color, x, y, w, h } => {
+                self.bytes(&[1]);
+                self.color(*color);
+                for &n in &[*x, *y, *w, *h] {
+                    self.int(n);
+                }
+            }
+            Drawable::ColoredBitmap { x, y, color, data } => {
+                self.bytes(&[2]);
+                self.int(*x);
+                self.int(*y);
+                self.color(*color);
+                self.len(data.data.len());
+                for row in data.data.iter() {
+                    self.len(row.len());
+                    for &set in row {
+                        self.bytes(&[set as u8]);
+                    }
+                }
+            }
+            Drawable::DestructibleSprite(sprite) => {
+                self.bytes(&[3]);
+                self.int(sprite.x);
+                self.int(sprite.y);
+                self.colors(&sprite.data);
+            }
+            Drawable::StaticSprite { x, y, data } => {
+                self.bytes(&[4]);
+                self.int(*x);
+                self.int(*y);
+                self.colors(&data.data);
+            }
+        }
+    }
+}
+
+/// Hash a list of draw commands, so frames can be compared (or checked against a known value in tests) without rendering them.
+pub fn digest(commands: &[Drawable]) -> u64 {
+    let mut digest = FnvDigest(0xcbf2_9ce4_8422_2325);
+    digest.len(commands.len());
+    for command in commands {
+        digest.drawable(command);
+    }
+    digest.0
+}
+
 pub struct GrayscaleBuffer {
     pub width: i32,
     pub height: i32,
@@ -482,6 +592,38 @@ pub fn render_to_buffer(target: &mut ImageBuffer, commands: &[Drawable]) {
     target.render(commands);
 }
 
+/// Rasterize ``commands`` into a caller's pixel buffer, which must hold exactly ``width * height`` bytes of grayscale or ``width * height * 4`` bytes of RGBA.
+pub fn render_into(
+    commands: &[Drawable],
+    width: i32,
+    height: i32,
+    grayscale: bool,
+    out: &mut [u8],
+) -> Result<(), String> {
+    let channels = if grayscale { 1 } else { 4 };
+    let expected = (width * height * channels) as usize;
+    if out.len() != expected {
+        return Err(format!(
+            "expected a {}x{}x{} pixel buffer ({} bytes) but got {} bytes",
+            width,
+            height,
+            channels,
+            expected,
+            out.len()
+        ));
+    }
+    if grayscale {
+        let mut img = GrayscaleBuffer::alloc(width, height);
+        img.render(commands);
+        out.copy_from_slice(&img.data);
+    } else {
+        let mut img = ImageBuffer::alloc(width, height);
+        img.render(commands);
+        out.copy_from_slice(&img.data);
+    }
+    Ok(())
+}
+
 /// Parse a number from number_sprites.txt into a SpriteData.
 fn load_sprite(data: &[&str], on_color: Color, set: char, ignore: char) -> FixedSpriteData {
     let off_color = Color::invisible();
@@ -549,3 +691,24 @@ pub fn load_bitmap_sprites(data: &str, set: char) -> Vec<BitmapSpriteData> {
 
     sprites.into_iter().rev().collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digest_known_value() {
+        let commands = vec![
+            Drawable::Clear(Color::black()),
+            Drawable::rect(Color::rgb(1, 2, 3), 4, 5, 6, 7),
+            Drawable::ColoredBitmap {
+                x: -1,
+                y: 2,
+                color: Color::white(),
+                data: BitmapSpriteData::new(vec![vec![true, false]]),
+            },
+        ];
+        assert_eq!(digest(&commands), 2_626_868_326_123_587_863);
+        assert_ne!(digest(&commands), digest(&commands[1..]));
+    }
+}
@@ -1,5 +1,5 @@
 /// Think NES-style controls: directions, and two buttons.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[repr(C)]
 pub struct Input {
     pub left: bool,
@@ -29,6 +29,10 @@ impl Input {
     pub fn new_from_ale(x: i32) -> Option<Input> {
         AleAction::from_int(x).map(|a| a.to_input())
     }
+    /// The ALE action that presses exactly these buttons, if there is one; ALE has no ``button2``, or opposite directions held together.
+    pub fn to_ale(self) -> Option<AleAction> {
+        AleAction::from_input(self)
+    }
     pub fn is_empty(self) -> bool {
         !self.left && !self.right && !self.up && !self.down && !self.button1 && !self.button2
     }
@@ -102,6 +106,12 @@ impl AleAction {
             _ => None,
         }
     }
+    /// The inverse of ``to_input``.
+    pub fn from_input(input: Input) -> Option<AleAction> {
+        (0..=17)
+            .filter_map(AleAction::from_int)
+            .find(|action| action.to_input() == input)
+    }
     pub fn to_input(self) -> Input {
         let mut input = Input::default();
         match self {
@@ -178,9 +188,16 @@ impl AleAction {
     }
 }
 
+impl From<AleAction> for Input {
+    fn from(action: AleAction) -> Input {
+        action.to_input()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Direction;
 
     #[test]
     pub fn test_ale_to_from_int() {
@@ -190,4 +207,32 @@ mod tests {
             assert_eq!(Some(i), action_int);
         }
     }
+
+    #[test]
+    pub fn test_ale_to_from_input() {
+        for i in 0..=17 {
+            let action = AleAction::from_int(i).unwrap();
+            assert_eq!(Input::from(action).to_ale(), Some(action));
+        }
+        let mut both_ways = Input::new();
+        both_ways.left = true;
+        both_ways.right = true;
+        assert_eq!(both_ways.to_ale(), None);
+        let mut button2 = Input::new();
+        button2.button2 = true;
+        assert_eq!(button2.to_ale(), None);
+    }
+
+    #[test]
+    pub fn test_direction_turns() {
+        for dir in Direction::ALL.iter() {
+            assert_eq!(dir.turn_left().turn_right(), *dir);
+            assert_eq!(dir.turn_left().turn_left(), dir.opposite());
+            assert_eq!(Direction::from_input(dir.to_input()), Some(*dir));
+            let (dx, dy) = dir.delta();
+            let (rx, ry) = dir.turn_right().delta();
+            // Clockwise on screen, where y grows downward.
+            assert_eq!((rx, ry), (-dy, dx));
+        }
+    }
 }
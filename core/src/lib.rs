@@ -10,6 +10,8 @@ pub mod body2d;
 pub mod collision;
 pub mod graphics;
 pub mod random;
+pub mod script;
+pub mod trajectory;
 pub mod vec2d;
 
 mod input;
@@ -46,6 +48,32 @@ impl fmt::Display for QueryError {
 
 impl Error for QueryError {}
 
+/// This enum defines failure conditions for starting a game or writing one out.
+#[derive(Debug)]
+pub enum SimulationError {
+    /// The config can't start a game; the message says why.
+    InvalidConfig(String),
+    /// A config or state couldn't be written as JSON.
+    Serialization(serde_json::Error),
+}
+
+impl From<serde_json::Error> for SimulationError {
+    fn from(e: serde_json::Error) -> SimulationError {
+        SimulationError::Serialization(e)
+    }
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::InvalidConfig(msg) => write!(f, "Invalid config: {}", msg),
+            SimulationError::Serialization(e) => write!(f, "Serialization failed: {}", e),
+        }
+    }
+}
+
+impl Error for SimulationError {}
+
 /// This trait models a single frame state for a Simulation.
 pub trait State {
     /// When < 0, this state should be replaced with a call to new_game() on the simulation.
@@ -54,16 +82,42 @@ pub trait State {
     fn score(&self) -> i32;
     /// Get the level from the game.
     fn level(&self) -> i32;
+    /// Has the game ended? Once it has, ``update_mut`` should change nothing; games that don't track it end when ``lives`` drops below zero.
+    fn game_over(&self) -> bool {
+        self.lives() < 0
+    }
     /// To update internally to the next state, we pass buttons to internal logic.
     fn update_mut(&mut self, buttons: Input);
     /// Any state can create a vector of drawable objects to present itself.
     fn draw(&self) -> Vec<graphics::Drawable>;
     /// Any state can serialize to JSON String.
     fn to_json(&self) -> String;
+    /// Like ``to_json``, but reports a failure instead of panicking; games that can't fail keep this default.
+    fn try_to_json(&self) -> Result<String, SimulationError> {
+        Ok(self.to_json())
+    }
     /// Copy this state to save it for later.
     fn copy(&self) -> Box<dyn State>;
     /// Submit a query to this state object, returning a JSON String or error message.
     fn query_json(&self, query: &str, args: &serde_json::Value) -> Result<String, QueryError>;
+    /// Change this state in place, the writable counterpart of ``query_json``; games that support no changes keep this default.
+    fn set_query_json(
+        &mut self,
+        _query: &str,
+        _args: &serde_json::Value,
+    ) -> Result<(), QueryError> {
+        Err(QueryError::NoSuchQuery)
+    }
+    /// Draw this state straight into a caller's pixel buffer, e.g. a numpy array; see ``graphics::render_into`` for the layout.
+    fn render_into(
+        &self,
+        width: i32,
+        height: i32,
+        grayscale: bool,
+        out: &mut [u8],
+    ) -> Result<(), String> {
+        graphics::render_into(&self.draw(), width, height, grayscale, out)
+    }
 }
 
 /// This trait models a simulation or game. It knows how to start a new game, and to declare its size before any gameplay starts.
@@ -73,6 +127,10 @@ pub trait Simulation {
 
     /// Generate a new State. This is in a Box<State> because it may be 1 of many unknown types as far as calling code is concerned.
     fn new_game(&mut self) -> Box<dyn State>;
+    /// Like ``new_game``, but reports a config that can't start a game instead of panicking.
+    fn try_new_game(&mut self) -> Result<Box<dyn State>, SimulationError> {
+        Ok(self.new_game())
+    }
     /// Generate a new State from JSON String (usually modified from a dump of State::to_json).
     fn new_state_from_json(&self, json: &str) -> Result<Box<dyn State>, serde_json::Error>;
 
@@ -81,6 +139,10 @@ pub trait Simulation {
 
     /// This serializes the "config" for a game to json.
     fn to_json(&self) -> String;
+    /// Like ``to_json``, but reports a failure instead of panicking.
+    fn try_to_json(&self) -> Result<String, SimulationError> {
+        Ok(self.to_json())
+    }
     /// This deserializes the "config" for a game from json.
     /// Generate new state and new config from JSON String.
     fn from_json(&self, json: &str) -> Result<Box<dyn Simulation>, serde_json::Error>;
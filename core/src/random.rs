@@ -1,7 +1,7 @@
 use rand_core::{impls, Error, RngCore};
 
 /// This implementation is a xoroshiro128+ that is serde serializable.
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub struct Gen {
     state: [u64; 2],
 }
@@ -42,6 +42,10 @@ impl Gen {
     pub fn reset_seed(&mut self, seed: u32) {
         self.state = [0x193a6754a8a7d469 ^ (seed as u64), 0x97830e05113ba7bb]
     }
+    /// A short fingerprint of the whole state: two generators with different digests will draw different numbers from here on.
+    pub fn digest(&self) -> u64 {
+        self.state[0].wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ self.state[1]
+    }
 }
 
 impl RngCore for Gen {
@@ -73,4 +77,16 @@ mod test {
 
         assert!(rng1.next_u64() != rng2.next_u64());
     }
+
+    #[test]
+    fn test_rng_digest() {
+        let mut rng1 = Gen::new_from_seed(1234);
+        let mut rng2 = Gen::new_from_seed(1234);
+        assert_eq!(rng1.digest(), rng2.digest());
+        rng1.next_u64();
+        assert_ne!(rng1.digest(), rng2.digest());
+        rng2.next_u64();
+        assert_eq!(rng1.digest(), rng2.digest());
+        assert_ne!(rng1.digest(), Gen::new_from_seed(4321).digest());
+    }
 }
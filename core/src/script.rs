@@ -0,0 +1,74 @@
+//! A tiny language for writing down input sequences, e.g. `"R×30, U×12, NOOP×5"`.
+//!
+//! A script is a comma-separated list of steps. Each step is an action and an optional repeat count, separated by `×`, `x` or `*`.
+//! Actions are ALE action names (`NOOP`, `UPFIRE`, `DOWNLEFT`, ...) or the single letters `U`, `D`, `L`, `R` and `F`; case doesn't matter.
+//! Whitespace and empty steps are ignored, so scripts can be split over several lines.
+
+use super::{AleAction, Input};
+
+/// Parse a whole script into one input per frame.
+pub fn parse_script(script: &str) -> Result<Vec<Input>, String> {
+    let mut inputs = Vec::new();
+    for step in script.split(',') {
+        let step = step.trim();
+        if step.is_empty() {
+            continue;
+        }
+        let (action, count) = parse_step(step)?;
+        for _ in 0..count {
+            inputs.push(action.to_input());
+        }
+    }
+    Ok(inputs)
+}
+
+/// Parse a single ``ACTION×COUNT`` step.
+fn parse_step(step: &str) -> Result<(AleAction, usize), String> {
+    let mut parts = step.splitn(2, &['×', 'x', 'X', '*'][..]);
+    let name = parts.next().unwrap_or("").trim();
+    let count = match parts.next() {
+        Some(count) => count
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("Bad repeat count in script step {:?}.", step))?,
+        None => 1,
+    };
+    let action = parse_action(name)
+        .ok_or_else(|| format!("Unknown action {:?} in script step {:?}.", name, step))?;
+    Ok((action, count))
+}
+
+fn parse_action(name: &str) -> Option<AleAction> {
+    let name = name.to_uppercase();
+    let name = match name.as_str() {
+        "U" => "UP",
+        "D" => "DOWN",
+        "L" => "LEFT",
+        "R" => "RIGHT",
+        "F" => "FIRE",
+        other => other,
+    };
+    (0..=17)
+        .filter_map(AleAction::from_int)
+        .find(|action| format!("{:?}", action) == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let inputs = parse_script("R×30, U x 12,\n noop*5, downleftfire").unwrap();
+        assert_eq!(inputs.len(), 48);
+        assert_eq!(inputs[0], AleAction::RIGHT.to_input());
+        assert_eq!(inputs[29], AleAction::RIGHT.to_input());
+        assert_eq!(inputs[30], AleAction::UP.to_input());
+        assert_eq!(inputs[42], Input::default());
+        assert_eq!(inputs[47], AleAction::DOWNLEFTFIRE.to_input());
+
+        assert_eq!(parse_script(""), Ok(Vec::new()));
+        assert!(parse_script("SIDEWAYS×3").is_err());
+        assert!(parse_script("U×many").is_err());
+    }
+}
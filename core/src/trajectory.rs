@@ -0,0 +1,214 @@
+//! Logging a game as it is played: one compact JSON object per step, one step per line.
+//!
+//! This needs nothing beyond ``serde_json``, and the files read back line by line with any JSON library, or with ``read_trajectory`` here.
+
+use super::{AleAction, State};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// One line of a trajectory file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrajectoryStep {
+    /// How many steps were recorded before this one, across every file of the trajectory?
+    pub frame: u64,
+    /// What was pressed.
+    pub action: AleAction,
+    /// The reward for the step, as the caller reported it.
+    pub reward: f32,
+    /// The score after the step.
+    pub score: i32,
+    /// Lives left after the step.
+    pub lives: i32,
+    /// The answer to each of the writer's queries after the step, by query.
+    pub queries: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Appends a ``TrajectoryStep`` per step to a buffered file, optionally starting a new file every so many steps.
+pub struct TrajectoryWriter {
+    /// The first file; later ones add ``.1``, ``.2``, ... to it.
+    path: PathBuf,
+    /// What to ask the state after every step (with no arguments).
+    queries: Vec<String>,
+    /// How many lines go in each file before the next is started? None keeps to one file.
+    lines_per_file: Option<u64>,
+    out: BufWriter<File>,
+    frame: u64,
+    lines_in_file: u64,
+    file_index: u32,
+}
+
+impl TrajectoryWriter {
+    /// Start writing to ``path``, replacing anything already there; every line answers ``queries`` after its step.
+    pub fn create<P: AsRef<Path>>(path: P, queries: &[&str]) -> io::Result<TrajectoryWriter> {
+        let path = path.as_ref().to_path_buf();
+        Ok(TrajectoryWriter {
+            out: BufWriter::new(File::create(&path)?),
+            path,
+            queries: queries.iter().map(|q| (*q).to_owned()).collect(),
+            lines_per_file: None,
+            frame: 0,
+            lines_in_file: 0,
+            file_index: 0,
+        })
+    }
+
+    /// Start a new file every ``lines`` steps: ``path``, then ``path.1``, ``path.2``, and so on.
+    pub fn rotate_every(mut self, lines: u64) -> TrajectoryWriter {
+        self.lines_per_file = Some(lines.max(1));
+        self
+    }
+
+    /// The file for the ``index``-th block of lines.
+    fn file_path(&self, index: u32) -> PathBuf {
+        if index == 0 {
+            return self.path.clone();
+        }
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    /// Every file written to so far, in order.
+    pub fn files(&self) -> Vec<PathBuf> {
+        (0..=self.file_index).map(|i| self.file_path(i)).collect()
+    }
+
+    /// Log a step that pressed ``action`` for ``reward`` and left the game in ``state``.
+    pub fn record(&mut self, action: AleAction, reward: f32, state: &dyn State) -> io::Result<()> {
+        let mut queries = serde_json::Map::new();
+        for query in &self.queries {
+            let answer = state
+                .query_json(query, &serde_json::Value::Null)
+                .map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Query {:?} failed: {}", query, e),
+                    )
+                })?;
+            let value = serde_json::from_str(&answer).unwrap_or(serde_json::Value::String(answer));
+            queries.insert(query.clone(), value);
+        }
+        let step = TrajectoryStep {
+            frame: self.frame,
+            action,
+            reward,
+            score: state.score(),
+            lives: state.lives(),
+            queries,
+        };
+
+        if self.lines_per_file == Some(self.lines_in_file) {
+            self.out.flush()?;
+            self.file_index += 1;
+            self.out = BufWriter::new(File::create(self.file_path(self.file_index))?);
+            self.lines_in_file = 0;
+        }
+        serde_json::to_writer(&mut self.out, &step)?;
+        self.out.write_all(b"\n")?;
+        self.frame += 1;
+        self.lines_in_file += 1;
+        Ok(())
+    }
+
+    /// Push everything buffered so far out to disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Read back one file written by a ``TrajectoryWriter``.
+pub fn read_trajectory<P: AsRef<Path>>(path: P) -> io::Result<Vec<TrajectoryStep>> {
+    let mut steps = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if !line.is_empty() {
+            steps.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Drawable;
+    use crate::{Input, QueryError};
+
+    /// Just enough of a game to log: the score counts the frames played.
+    #[derive(Clone)]
+    struct Counter {
+        frames: i32,
+    }
+
+    impl State for Counter {
+        fn lives(&self) -> i32 {
+            3
+        }
+        fn score(&self) -> i32 {
+            self.frames
+        }
+        fn level(&self) -> i32 {
+            1
+        }
+        fn update_mut(&mut self, _buttons: Input) {
+            self.frames += 1;
+        }
+        fn draw(&self) -> Vec<Drawable> {
+            Vec::new()
+        }
+        fn to_json(&self) -> String {
+            format!("{{\"frames\":{}}}", self.frames)
+        }
+        fn copy(&self) -> Box<dyn State> {
+            Box::new(self.clone())
+        }
+        fn query_json(&self, query: &str, _args: &serde_json::Value) -> Result<String, QueryError> {
+            match query {
+                "odd" => Ok(format!("{}", self.frames % 2 == 1)),
+                _ => Err(QueryError::NoSuchQuery),
+            }
+        }
+    }
+
+    #[test]
+    fn test_trajectory_writer() {
+        let dir = std::env::temp_dir().join(format!("toybox-trajectory-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("run.jsonl");
+
+        let mut game = Counter { frames: 0 };
+        let mut writer = TrajectoryWriter::create(&path, &["odd"])
+            .unwrap()
+            .rotate_every(2);
+        for _ in 0..5 {
+            game.update_mut(AleAction::UP.to_input());
+            writer.record(AleAction::UP, 1.0, &game).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let files = writer.files();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[2], dir.join("run.jsonl.2"));
+        let steps: Vec<TrajectoryStep> = files
+            .iter()
+            .flat_map(|f| read_trajectory(f).unwrap())
+            .collect();
+        assert_eq!(steps.len(), 5);
+        assert_eq!(read_trajectory(&files[2]).unwrap().len(), 1);
+        for (i, step) in steps.iter().enumerate() {
+            assert_eq!(step.frame, i as u64);
+            assert_eq!(step.action, AleAction::UP);
+            assert_eq!(step.score, i as i32 + 1);
+            assert_eq!(step.queries["odd"], serde_json::Value::Bool(i % 2 == 0));
+        }
+
+        assert!(
+            TrajectoryWriter::create(dir.join("bad.jsonl"), &["missing"])
+                .unwrap()
+                .record(AleAction::NOOP, 0.0, &game)
+                .is_err()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -142,7 +142,7 @@ class Simulator(object):
 
     def to_json(self) -> Dict[str, Any]:
         """Get the configuration of this simulator/config as JSON"""
-        json_str = rust_str(lib.simulator_to_json(self.get_simulator()))
+        json_str = rust_str(_handle_ffi_result(lib.simulator_to_json(self.get_simulator())))
         return json.loads(str(json_str))
 
     def from_json(self, config_js: Union[Dict[str, Any], str]):
@@ -182,14 +182,15 @@ class State(object):
             sim: The simulator responsible for this state.
             state: Optional pointer to a state to use (otherwise it will create one). 
         """
+        self.deleted = True
+        """An internal field used to prevent freeing the ``__state`` multiple times; nothing to free until it is allocated."""
         self.sim = sim
         """A reference to the simulator that created this state."""
-        self.__state = state or lib.state_alloc(sim.get_simulator())
+        self.__state = state or _handle_ffi_result(lib.state_alloc(sim.get_simulator()))
         """The raw pointer to the state itself."""
         self.game_name = sim.game_name
         """The name of the game that created this state."""
         self.deleted = False
-        """An internal field used to prevent freeing the ``__state`` multiple times."""
 
     def __enter__(self):
         return self
@@ -229,7 +230,7 @@ class State(object):
 
         >>> assert self.lives() < 0 == self.game_over()
         """
-        return self.lives() < 0
+        return lib.state_game_over(self.__state)
 
     def query_json(
         self, query: str, args: Union[Dict[str, Any], str] = "null"
@@ -263,6 +264,32 @@ class State(object):
         )
         return json.loads(txt)
 
+    def set_query_json(
+        self, query: str, args: Union[Dict[str, Any], str] = "null"
+    ) -> None:
+        """
+        Change the Rust state in place; the writable counterpart of ``query_json``.
+
+        Parameters:
+            query: the change to make to the rust state.
+            args: the arguments to send to the rust state, defaults to "null".
+
+        Raises:
+            ValueError: if the game doesn't support the change, or the arguments are wrong
+
+        ```python
+        with Toybox("amidar") as tb:
+          tb.set_query_state_json("set_lives", "1")
+        ```
+        """
+        _handle_ffi_result(
+            lib.state_set_query_json(
+                self.__state,
+                json_str(query).encode("utf-8"),
+                json_str(args).encode("utf-8"),
+            )
+        )
+
     def render_frame(self, sim: Simulator, grayscale: bool = True) -> np.array:
         """Generate an image from the current frame state object.
 
@@ -321,7 +348,7 @@ class State(object):
 
     def to_json(self) -> Dict[str, Any]:
         """Get a JSON representation of the state."""
-        json_str = rust_str(lib.state_to_json(self.__state))
+        json_str = rust_str(_handle_ffi_result(lib.state_to_json(self.__state)))
         return json.loads(str(json_str))
 
 
@@ -562,6 +589,17 @@ class Toybox(object):
         """
         return self.rstate.query_json(query, args)
 
+    def set_query_state_json(
+        self, query: str, args: Union[Dict[str, Any], str] = "null"
+    ):
+        """Change the game's state in place without a round trip through the whole JSON, e.g. to move the player mid-episode.
+
+        Parameters:
+            query: the change to make, e.g. "set_lives".
+            args: a JSON argument to attach to the change.
+        """
+        self.rstate.set_query_json(query, args)
+
     def __del__(self):
         if not self.deleted:
             self.deleted = True
@@ -8,6 +8,7 @@ from pygame.locals import *
 import pygame.surfarray
 import json
 import sys
+import time
 
 if __name__ == "__main__":
     parser = argparse.ArgumentParser(description="human_play for toybox")
@@ -20,6 +21,17 @@ if __name__ == "__main__":
     parser.add_argument("--query_args", type=str, default="null")
     parser.add_argument("--seed", type=int, default=-1)
     parser.add_argument("--record", action='store_const', const=True)
+    parser.add_argument(
+        "--latch",
+        action="store_true",
+        help="amidar: keep moving in the last direction pressed, like an arcade joystick",
+    )
+    parser.add_argument(
+        "--record_timing",
+        type=str,
+        default=None,
+        help="write timestamped key transitions and per-frame inputs to this file, as JSON lines",
+    )
 
     args = parser.parse_args()
     print("Starting up: " + args.game)
@@ -33,6 +45,11 @@ if __name__ == "__main__":
             tb.set_seed(args.seed)
             tb.new_game()
 
+        if args.latch:
+            latched_config = tb.config_to_json()
+            latched_config["latch_directions"] = True
+            tb.write_config_json(latched_config)
+
         config_json = tb.config_to_json()
         state_json = tb.to_state_json()
 
@@ -42,6 +59,16 @@ if __name__ == "__main__":
         clock = pygame.time.Clock()
         FPS = args.fps
 
+        # For reaction-time studies: every line has seconds since start, from a monotonic clock.
+        timing_log = open(args.record_timing, "w") if args.record_timing else None
+        start_time = time.perf_counter()
+        frame = 0
+
+        def log_timing(record):
+            record["time"] = time.perf_counter() - start_time
+            record["frame"] = frame
+            timing_log.write(json.dumps(record) + "\n")
+
         quit = False
         while not quit:
             # close human_play on game over
@@ -54,6 +81,13 @@ if __name__ == "__main__":
                 if event.type == KEYDOWN and event.key == K_ESCAPE:
                     quit = True
                     break
+                if timing_log and event.type in (KEYDOWN, KEYUP):
+                    log_timing(
+                        {
+                            "key": pygame.key.name(event.key),
+                            "down": event.type == KEYDOWN,
+                        }
+                    )
             key_state = pygame.key.get_pressed()
             player_input = Input()
 
@@ -69,6 +103,9 @@ if __name__ == "__main__":
             )
 
             tb.apply_action(player_input)
+            if timing_log:
+                log_timing({"input": player_input.__dict__})
+            frame += 1
             if args.record:
                 if player_input.left: print('left')
                 elif player_input.right: print('right')
@@ -86,5 +123,7 @@ if __name__ == "__main__":
                 clock.tick(FPS * 4)
             else:
                 clock.tick(FPS)
+        if timing_log:
+            timing_log.close()
     pygame.quit()
     sys.exit()
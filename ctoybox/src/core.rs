@@ -6,9 +6,8 @@ use serde_json;
 use std::boxed::Box;
 use std::error::Error;
 use std::ffi::CString;
-use std::mem;
+use std::slice;
 use toybox;
-use toybox_core::graphics::{GrayscaleBuffer, ImageBuffer};
 use toybox_core::{AleAction, Input};
 
 fn get_simulator(ptr: *mut WrapSimulator) -> &'static mut dyn toybox::Simulation {
@@ -58,9 +57,11 @@ pub extern "C" fn simulator_seed(ptr: *mut WrapSimulator, seed: u32) {
 }
 
 #[no_mangle]
-pub extern "C" fn simulator_to_json(ptr: *mut WrapSimulator) -> *const c_void {
-    let json = get_simulator(ptr).to_json();
-    return_string(&json)
+pub extern "C" fn simulator_to_json(ptr: *mut WrapSimulator) -> *const FFIResult {
+    let json = get_simulator(ptr)
+        .try_to_json()
+        .map_err(|e| Box::new(e) as Box<dyn Error>);
+    str_result_to_ffi(json)
 }
 
 #[no_mangle]
@@ -96,10 +97,12 @@ pub extern "C" fn simulator_schema_for_config(ptr: *mut WrapSimulator) -> *const
 
 // STATE ALLOC + FREE
 #[no_mangle]
-pub extern "C" fn state_alloc(ptr: *mut WrapSimulator) -> *mut WrapState {
-    let state = get_simulator(ptr).new_game();
-    let boxed_wrapped_state = Box::new(WrapState { state });
-    Box::into_raw(boxed_wrapped_state)
+pub extern "C" fn state_alloc(ptr: *mut WrapSimulator) -> *const FFIResult {
+    let state = get_simulator(ptr)
+        .try_new_game()
+        .map(|state| WrapState { state })
+        .map_err(|e| Box::new(e) as Box<dyn Error>);
+    result_to_ffi(state)
 }
 
 #[no_mangle]
@@ -137,6 +140,23 @@ pub extern "C" fn state_query_json(
     str_result_to_ffi(response)
 }
 
+/// The writable counterpart of ``state_query_json``: change the state in place.
+#[no_mangle]
+pub extern "C" fn state_set_query_json(
+    ptr: *mut WrapState,
+    query_str: *const c_void,
+    args_json_str: *const c_void,
+) -> *const FFIResult {
+    let rc: Result<(), Box<dyn Error>> = (|| {
+        let query_str = accept_str("query_str", query_str)?;
+        let args_str = accept_str("args_json_str", args_json_str)?;
+        let args: serde_json::Value = serde_json::from_str(args_str)?;
+        Ok(get_state(ptr).set_query_json(query_str, &args)?)
+    })();
+
+    empty_result_to_ffi(rc)
+}
+
 // Need this information to initialize the numpy array in python
 #[no_mangle]
 pub extern "C" fn simulator_frame_width(ptr: *mut WrapSimulator) -> i32 {
@@ -165,31 +185,8 @@ pub extern "C" fn render_current_frame(
         let (w, h) = get_simulator(sim_ptr).game_size();
         let state = get_state(state_ptr);
 
-        let imgdata = if grayscale {
-            let mut img = GrayscaleBuffer::alloc(w, h);
-            img.render(&state.draw());
-            img.data
-        } else {
-            let mut img = ImageBuffer::alloc(w, h);
-            img.render(&state.draw());
-            img.data
-        };
-
-        if numpy_pixels_len != imgdata.len() {
-            return Err(format!(
-                "expected numpy_pixels_len={} to match rendered image: {}",
-                numpy_pixels_len,
-                imgdata.len()
-            )
-            .into());
-        }
-
-        let mut dat: Vec<u8> = unsafe { Vec::from_raw_parts(numpy_pixels, 0, numpy_pixels_len) };
-        assert_eq!(dat.len(), 0);
-        // Copy pixels at once (let LLVM/Rust optimize it as a linear copy).
-        dat.extend(&imgdata);
-        assert_eq!(dat.len(), imgdata.len());
-        mem::forget(dat);
+        let dat: &mut [u8] = unsafe { slice::from_raw_parts_mut(numpy_pixels, numpy_pixels_len) };
+        state.render_into(w, h, grayscale, dat)?;
         Ok(())
     })();
 
@@ -227,6 +224,11 @@ pub extern "C" fn state_lives(state_ptr: *mut WrapState) -> i32 {
     get_state(state_ptr).lives()
 }
 
+#[no_mangle]
+pub extern "C" fn state_game_over(state_ptr: *mut WrapState) -> bool {
+    get_state(state_ptr).game_over()
+}
+
 #[no_mangle]
 pub extern "C" fn state_level(state_ptr: *mut WrapState) -> i32 {
     get_state(state_ptr).level()
@@ -238,8 +240,11 @@ pub extern "C" fn state_score(state_ptr: *mut WrapState) -> i32 {
 }
 
 #[no_mangle]
-pub extern "C" fn state_to_json(state_ptr: *mut WrapState) -> *const c_void {
-    return_string(&get_state(state_ptr).to_json())
+pub extern "C" fn state_to_json(state_ptr: *mut WrapState) -> *const FFIResult {
+    let json = get_state(state_ptr)
+        .try_to_json()
+        .map_err(|e| Box::new(e) as Box<dyn Error>);
+    str_result_to_ffi(json)
 }
 
 #[no_mangle]
@@ -0,0 +1,30 @@
+import unittest
+from ctoybox import Toybox
+
+
+class TestFFIErrors(unittest.TestCase):
+    def test_new_game_from_bad_config(self):
+        with Toybox("amidar") as tb:
+            config = tb.config_to_json()
+            # This parses, but no game can be started from it.
+            config["frame_skip"] = 0
+            with self.assertRaises(ValueError):
+                tb.write_config_json(config)
+            # The last good state is still there, and a good config starts games again.
+            self.assertEqual(tb.get_lives(), 3)
+            config["frame_skip"] = 1
+            tb.write_config_json(config)
+            self.assertIn("frame_skip", tb.config_to_json())
+            self.assertIn("score", tb.state_to_json())
+
+    def test_bad_enemy_index(self):
+        with Toybox("amidar") as tb:
+            self.assertEqual(len(tb.query_state_json("enemy_tile", "0")), 2)
+            with self.assertRaises(ValueError):
+                tb.query_state_json("enemy_tile", "99")
+            with self.assertRaises(ValueError):
+                tb.query_state_json("enemy_caught", "99")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -1,14 +1,19 @@
-use super::digit_sprites::{draw_score, DIGIT_HEIGHT};
+use super::digit_sprites::{draw_score, DIGIT_HEIGHT, DIGIT_WIDTH};
 use crate::types::*;
 use access_json::JSONQuery;
 use serde_json;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
 use toybox_core;
-use toybox_core::graphics::{Color, Drawable, FixedSpriteData};
+#[cfg(feature = "graphics")]
+use toybox_core::graphics::FixedSpriteData;
+use toybox_core::graphics::{self, Color, Drawable, ImageBuffer};
 use toybox_core::random;
-use toybox_core::{AleAction, Direction, Input, QueryError};
+use toybox_core::{AleAction, Direction, Input, QueryError, SimulationError};
 
 use rand::seq::SliceRandom;
+use rand::Rng;
 
 // Window constants:
 pub mod screen {
@@ -24,7 +29,10 @@ pub mod screen {
 
     pub const SCORE_Y_POS: i32 = 198;
     pub const SCORE_X_POS: i32 = LIVES_X_POS - LIVES_X_STEP * 3 - 8;
+    /// Room under the board for the score and lives; the default board ends 58 pixels above the bottom of ``GAME_SIZE``.
+    pub const HUD_HEIGHT: i32 = 58;
 }
+#[cfg(feature = "graphics")]
 pub mod raw_images {
     pub const PLAYER_L1: &[u8] = include_bytes!("resources/amidar/player_l1.png");
     pub const PLAYER_L2: &[u8] = include_bytes!("resources/amidar/player_l2.png");
@@ -43,6 +51,7 @@ pub mod raw_images {
     pub const BLOCK_TILE_UNPAINTED_L2: &[u8] =
         include_bytes!("resources/amidar/block_tile_unpainted_l2.png");
 }
+#[cfg(feature = "graphics")]
 pub mod images {
     use super::*;
     lazy_static! {
@@ -54,6 +63,7 @@ pub mod images {
         pub static ref ENEMY_CHASE_L1: FixedSpriteData =
             FixedSpriteData::load_png(raw_images::ENEMY_CHASE_L1);
         pub static ref ENEMY_CAUGHT_L1: FixedSpriteData = ENEMY_CHASE_L1.make_black_version();
+        pub static ref ENEMY_HELD_L1: FixedSpriteData = ENEMY_L1.make_faded_version(Color::black());
         pub static ref BLOCK_TILE_PAINTED_L1: FixedSpriteData =
             FixedSpriteData::load_png(raw_images::BLOCK_TILE_PAINTED_L1);
         pub static ref BLOCK_TILE_UNPAINTED_L1: FixedSpriteData =
@@ -68,6 +78,7 @@ pub mod images {
         pub static ref ENEMY_CHASE_L2: FixedSpriteData =
             FixedSpriteData::load_png(raw_images::ENEMY_CHASE_L2);
         pub static ref ENEMY_CAUGHT_L2: FixedSpriteData = ENEMY_CHASE_L2.make_black_version();
+        pub static ref ENEMY_HELD_L2: FixedSpriteData = ENEMY_L2.make_faded_version(Color::black());
         pub static ref BLOCK_TILE_PAINTED_L2: FixedSpriteData =
             FixedSpriteData::load_png(raw_images::BLOCK_TILE_PAINTED_L2);
         pub static ref BLOCK_TILE_UNPAINTED_L2: FixedSpriteData =
@@ -80,10 +91,12 @@ pub mod images {
     }
 }
 
-mod world {
+pub mod world {
     use super::screen;
     pub const SCALE: i32 = 16;
     pub const TILE_SIZE: (i32, i32) = (screen::TILE_SIZE.0 * SCALE, screen::TILE_SIZE.1 * SCALE);
+    /// The most rows or columns a board may have; keeps every world coordinate and screen pixel far inside i32.
+    pub const MAX_BOARD_TILES: u32 = 4096;
 }
 pub const AMIDAR_BOARD: &str = include_str!("resources/amidar_default_board");
 pub const AMIDAR_ENEMY_POSITIONS_DATA: &str = include_str!("resources/amidar_enemy_positions");
@@ -93,7 +106,112 @@ mod inits {
     pub const PLAYER_SPEED: i32 = 8;
 }
 
+/// The first bytes of every ``State::to_bytes`` savestate.
+const SAVESTATE_MAGIC: &[u8; 4] = b"TBAM";
+/// Which layout of ``StateCore`` do ``State::to_bytes`` savestates hold? Bump it whenever ``StateCore`` changes, so stale savestates are refused instead of misread.
+pub const SAVESTATE_VERSION: u16 = 21;
+
+/// The fixed channels of ``State::board_tensor``; per-enemy channels follow.
+pub const BOARD_TENSOR_CHANNELS: [&str; 8] = [
+    "walkable",
+    "unpainted",
+    "painted",
+    "chase_marker",
+    "penalty",
+    "player",
+    "enemies",
+    "catchable_enemies",
+];
+
+/// The config fields that ``Theme`` covers, in the order ``Amidar::colors`` returns them.
+const THEME_COLOR_FIELDS: [&str; 8] = [
+    "bg_color",
+    "enemy_color",
+    "inner_painted_color",
+    "painted_color",
+    "player_color",
+    "unpainted_color",
+    "penalty_color",
+    "gate_color",
+];
+
+impl Theme {
+    /// Every theme is a default config with its colors swapped out.
+    fn config(self) -> Amidar {
+        let mut config = Amidar::default();
+        config.apply_theme(self);
+        config
+    }
+}
+
 impl Amidar {
+    /// Overwrite every color field with ``theme``'s palette.
+    pub fn apply_theme(&mut self, theme: Theme) {
+        let (bg, enemy, inner_painted, painted, player, unpainted, penalty, gate) = match theme {
+            Theme::Classic => (
+                Color::black(),
+                Color::rgb(255, 50, 100),
+                Color::rgb(255, 255, 0),
+                Color::rgb(255, 255, 30),
+                Color::rgb(255, 255, 153),
+                Color::rgb(148, 0, 211),
+                Color::rgb(0, 160, 160),
+                Color::rgb(80, 200, 255),
+            ),
+            Theme::Mono => (
+                Color::black(),
+                Color::rgb(150, 150, 150),
+                Color::rgb(110, 110, 110),
+                Color::rgb(200, 200, 200),
+                Color::white(),
+                Color::rgb(70, 70, 70),
+                Color::rgb(40, 40, 40),
+                Color::rgb(230, 230, 230),
+            ),
+            Theme::Neon => (
+                Color::rgb(10, 0, 30),
+                Color::rgb(255, 40, 40),
+                Color::rgb(0, 90, 255),
+                Color::rgb(57, 255, 20),
+                Color::rgb(0, 255, 255),
+                Color::rgb(255, 0, 255),
+                Color::rgb(255, 140, 0),
+                Color::rgb(255, 255, 80),
+            ),
+            Theme::Paper => (
+                Color::rgb(245, 240, 225),
+                Color::rgb(200, 40, 40),
+                Color::rgb(200, 215, 240),
+                Color::rgb(60, 90, 160),
+                Color::rgb(30, 30, 30),
+                Color::rgb(170, 160, 140),
+                Color::rgb(110, 150, 110),
+                Color::rgb(230, 160, 40),
+            ),
+        };
+        self.theme = Some(theme);
+        self.bg_color = bg;
+        self.enemy_color = enemy;
+        self.inner_painted_color = inner_painted;
+        self.painted_color = painted;
+        self.player_color = player;
+        self.unpainted_color = unpainted;
+        self.penalty_color = penalty;
+        self.gate_color = gate;
+    }
+    /// Score like ALE's MsPacman where Amidar has a counterpart: enemies caught in one chase mode are worth 200, 400, 800, then 1600 each.
+    /// Lives work like it too: 3 in all, counting the one in play as ALE reports them, one extra at 10,000 points, and none back for clearing a level.
+    /// Pellets (10 and 50) and fruit have no Amidar counterpart, so painting, boxes and level clears keep their own values.
+    pub fn apply_ale_mspacman_scoring(&mut self) {
+        self.chase_score_bonus = 200;
+        self.chase_bonus_factor = 2;
+        self.chase_bonus_max = Some(1600);
+        self.start_lives = 2;
+        self.count_life_in_play = true;
+        self.extra_life_score = Some(10_000);
+        self.refill_lives_on_level_clear = false;
+    }
+
     pub fn colors(&self) -> Vec<&Color> {
         vec![
             &self.bg_color,
@@ -102,8 +220,336 @@ impl Amidar {
             &self.painted_color,
             &self.player_color,
             &self.unpainted_color,
+            &self.penalty_color,
+            &self.gate_color,
         ]
     }
+    /// How many enemies (see ``enemies_per_level``) are in play on ``level``?
+    pub fn enemies_for_level(&self, level: i32) -> usize {
+        let scheduled = match self.enemies_per_level.len() {
+            0 => self.enemies.len(),
+            n => self.enemies_per_level[((level - 1).max(0) as usize).min(n - 1)],
+        };
+        scheduled.min(self.enemies.len())
+    }
+    /// The ``difficulty_table`` entry for ``level``, if there is one.
+    pub fn level_params(&self, level: i32) -> Option<&LevelParams> {
+        let last = self.difficulty_table.len().checked_sub(1)?;
+        self.difficulty_table
+            .get(((level - 1).max(0) as usize).min(last))
+    }
+    /// The layout (see ``board_generator``, ``boards`` and ``level_board_order``) played on ``level``.
+    pub fn board_for_level(&self, level: i32) -> Vec<String> {
+        if let Some(generator) = &self.board_generator {
+            return generator.generate(level);
+        }
+        if self.level_board_order.is_empty() {
+            return self.board.clone();
+        }
+        let n = self.level_board_order.len() as i32;
+        let layout = self.level_board_order[(level - 1).rem_euclid(n) as usize];
+        if layout == 0 {
+            self.board.clone()
+        } else {
+            self.boards.get(layout - 1).unwrap_or(&self.board).clone()
+        }
+    }
+    /// Check that every spawn marker on ``board`` belongs to an enemy that can start anywhere.
+    fn check_spawn_markers(&self, board: &Board) -> Result<(), String> {
+        for (i, spawn) in board.enemy_spawns.iter().enumerate() {
+            if spawn.is_none() {
+                continue;
+            }
+            match self.enemies.get(i) {
+                None => {
+                    return Err(format!(
+                        "The board has a spawn marker for enemy {}, but there are only {} enemies.",
+                        i + 1,
+                        self.enemies.len()
+                    ))
+                }
+                Some(ai) if !ai.clone().set_start(&TilePoint::new(0, 0)) => {
+                    return Err(format!(
+                        "Enemy {} follows a fixed route, so it can't start at a spawn marker.",
+                        i + 1
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+    /// Check that every layout this config can play parses and is playable from ``player_start`` (or its ``P`` marker), describing any problems.
+    pub fn validate(&self) -> Result<(), String> {
+        let layouts: Vec<(String, Vec<String>, TilePoint)> = match &self.board_generator {
+            Some(generator) => vec![(
+                "generated board".to_string(),
+                generator.generate(1),
+                generator.player_start(),
+            )],
+            None => std::iter::once(&self.board)
+                .chain(self.boards.iter())
+                .enumerate()
+                .map(|(i, b)| {
+                    (
+                        format!("layout {}", i),
+                        b.clone(),
+                        self.player_start.clone(),
+                    )
+                })
+                .collect(),
+        };
+        let mut problems = Vec::new();
+        if self.frames_per_second.is_nan() || self.frames_per_second <= 0.0 {
+            problems.push(format!(
+                "frames_per_second must be more than 0, not {}.",
+                self.frames_per_second
+            ));
+        }
+        for (name, lines, start) in layouts {
+            match Board::try_new(&lines) {
+                Ok(board) => {
+                    let start = board.player_spawn.clone().unwrap_or(start);
+                    if let Err(errors) = board.validate(&start) {
+                        for e in errors {
+                            problems.push(format!("{}: {}", name, e));
+                        }
+                    }
+                    if let Err(e) = self.check_spawn_markers(&board) {
+                        problems.push(format!("{}: {}", name, e));
+                    }
+                }
+                Err(e) => problems.push(format!("{}: {}", name, e)),
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join(" "))
+        }
+    }
+    /// How big is the screen for these boards? The default board fills exactly ``screen::GAME_SIZE``; larger boards grow the screen to fit the largest, and never shrink it.
+    pub fn screen_size(&self) -> (i32, i32) {
+        let layouts = || std::iter::once(&self.board).chain(self.boards.iter());
+        let (rows, cols) = match &self.board_generator {
+            Some(generator) => (
+                generator.height.max(5) as i32,
+                generator.width.max(5) as i32,
+            ),
+            None => (
+                layouts().map(|b| b.len()).max().unwrap_or(0) as i32,
+                layouts()
+                    .flat_map(|b| b.iter())
+                    .map(|row| row.chars().count())
+                    .max()
+                    .unwrap_or(0) as i32,
+            ),
+        };
+        let (offset_x, offset_y) = screen::BOARD_OFFSET;
+        let (tile_w, tile_h) = screen::TILE_SIZE;
+        let (min_w, min_h) = screen::GAME_SIZE;
+        (
+            (2 * offset_x + cols * tile_w).max(min_w),
+            (offset_y + rows * tile_h + screen::HUD_HEIGHT).max(min_h),
+        )
+    }
+    /// How far the score and lives move from their ``screen`` positions, so they stay under the bottom-right of a larger board.
+    fn hud_shift(&self) -> (i32, i32) {
+        let (w, h) = self.screen_size();
+        (w - screen::GAME_SIZE.0, h - screen::GAME_SIZE.1)
+    }
+    /// Turn a raw score change into a per-frame reward, according to ``reward_scale`` and ``reward_clip``.
+    pub fn shape_reward(&self, score_change: i32) -> f32 {
+        let reward = score_change as f32 * self.reward_scale;
+        if self.reward_clip {
+            reward.clamp(-1.0, 1.0)
+        } else {
+            reward
+        }
+    }
+    /// Fill in what a state saved by an older version of the game left out, so it plays under this config.
+    pub(crate) fn upgrade_state(&self, state: &mut StateCore) {
+        // States saved before the per-enemy and per-gate counters existed have them empty.
+        state.kills_by_enemy.resize(self.enemies.len(), 0);
+        state.catches_by_enemy.resize(self.enemies.len(), 0);
+        state.gates_reached.resize(self.bonus_gates.len(), false);
+        if state.level_tiles_to_paint == 0 {
+            // Nor did they note how much of the level there was to paint; its layout still says.
+            state.level_tiles_to_paint = Board::try_new(&self.board_for_level(state.level))
+                .map_or(state.board.tiles_to_paint(), |board| board.tiles_to_paint());
+        }
+    }
+}
+
+impl BoardReport {
+    /// Everything that makes the board unplayable; empty if it's fine.
+    pub fn errors(&self) -> Vec<BoardProblem> {
+        let mut problems = Vec::new();
+        if !self.ragged_rows.is_empty() {
+            problems.push(BoardProblem::RaggedRows {
+                rows: self.ragged_rows.clone(),
+                width: self.width,
+            });
+        }
+        if !self.start_walkable {
+            problems.push(BoardProblem::StartNotWalkable {
+                start: self.start.clone(),
+            });
+        }
+        if self.regions > 1 {
+            problems.push(BoardProblem::SplitRegions {
+                regions: self.regions,
+            });
+        }
+        if !self.unreachable.is_empty() {
+            problems.push(BoardProblem::Unreachable {
+                tiles: self.unreachable.clone(),
+            });
+        }
+        if self.paint_tiles == 0 {
+            problems.push(BoardProblem::NothingToPaint);
+        }
+        problems
+    }
+    /// Human-readable descriptions of ``errors``.
+    pub fn problems(&self) -> Vec<String> {
+        self.errors().iter().map(|p| p.to_string()).collect()
+    }
+}
+
+impl fmt::Display for BoardProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardProblem::RaggedRows { rows, width } => {
+                write!(f, "Rows {:?} are not {} tiles wide.", rows, width)
+            }
+            BoardProblem::StartNotWalkable { start } => write!(
+                f,
+                "The player starts on an empty tile ({}, {}).",
+                start.tx, start.ty
+            ),
+            BoardProblem::SplitRegions { regions } => {
+                write!(f, "The board is split into {} separate regions.", regions)
+            }
+            BoardProblem::Unreachable { tiles } => write!(
+                f,
+                "{} tiles that need painting can't be reached from the start.",
+                tiles.len()
+            ),
+            BoardProblem::NothingToPaint => write!(f, "There is nothing to paint."),
+        }
+    }
+}
+
+impl BoardGenerator {
+    /// Where the player starts on every generated board: halfway down the right edge, which is always track.
+    pub fn player_start(&self) -> TilePoint {
+        TilePoint::new(self.width.max(5) as i32 - 1, self.height.max(5) as i32 / 2)
+    }
+    /// Build the board for ``level``, as rows of board characters.
+    pub fn generate(&self, level: i32) -> Vec<String> {
+        let w = self.width.max(5) as usize;
+        let h = self.height.max(5) as usize;
+        let mut rng =
+            random::Gen::new_from_seed(self.seed ^ (level as u32).wrapping_mul(0x9E37_79B9));
+        let mut grid = vec![vec![' '; w]; h];
+
+        // Full-width lines: the top, the bottom, and more every three to six rows between.
+        let mut rows = vec![0];
+        loop {
+            let next = rows[rows.len() - 1] + rng.gen_range(3, 7);
+            if next + 3 > h - 1 {
+                break;
+            }
+            rows.push(next);
+        }
+        rows.push(h - 1);
+        for &y in &rows {
+            for tile in grid[y].iter_mut() {
+                *tile = '=';
+            }
+        }
+
+        // Each band between two lines gets the edges plus a few columns, never closer than three apart.
+        for band in rows.windows(2) {
+            let mut columns = vec![0, w - 1];
+            let limit = match self.symmetry {
+                Symmetry::None => w - 2,
+                Symmetry::Vertical => (w - 1) / 2 + 1,
+            };
+            for _ in 0..w / 3 {
+                let x = rng.gen_range(2, limit.max(3));
+                let mut xs = vec![x];
+                if self.symmetry == Symmetry::Vertical {
+                    xs.push(w - 1 - x);
+                }
+                let fits = |c: &usize| columns.iter().all(|&o| (o as i32 - *c as i32).abs() >= 3);
+                let mirrored_apart =
+                    xs.len() < 2 || xs[0] == xs[1] || (xs[0] as i32 - xs[1] as i32).abs() >= 3;
+                if mirrored_apart && xs.iter().all(fits) && xs.iter().all(|&c| c < w - 2) {
+                    columns.extend(xs);
+                    columns.dedup();
+                }
+            }
+            for &x in &columns {
+                for row in grid.iter_mut().take(band[1] + 1).skip(band[0]) {
+                    row[x] = '=';
+                }
+            }
+        }
+
+        for &(x, y) in &[(0, 0), (w - 1, 0), (0, h - 1), (w - 1, h - 1)] {
+            grid[y][x] = 'c';
+        }
+        grid.into_iter()
+            .map(|row| row.into_iter().collect())
+            .collect()
+    }
+}
+
+impl BonusGate {
+    /// What is this gate worth ``frames`` into the level?
+    pub fn bonus_at(&self, frames: i32) -> i32 {
+        if self.decay_frames <= 0 {
+            return if frames <= 0 { self.max_bonus } else { 0 };
+        }
+        let left = (self.decay_frames - frames).max(0) as i64;
+        (self.max_bonus as i64 * left / self.decay_frames as i64) as i32
+    }
+}
+
+impl GameTime {
+    /// How many frames is this, at ``fps`` frames per second? Partial frames round to the nearest.
+    pub fn to_frames(self, fps: f32) -> i32 {
+        match self {
+            GameTime::Frames(frames) => frames,
+            GameTime::Seconds(seconds) => (seconds * fps).round() as i32,
+        }
+    }
+    /// How many seconds is this, at ``fps`` frames per second?
+    pub fn to_seconds(self, fps: f32) -> f32 {
+        match self {
+            GameTime::Frames(frames) => frames as f32 / fps,
+            GameTime::Seconds(seconds) => seconds,
+        }
+    }
+}
+
+impl ExplorationBonus {
+    /// How much intrinsic reward is the first visit to ``tile`` worth?
+    fn first_visit_reward(self, board: &Board, tile: &TilePoint) -> f32 {
+        match self {
+            ExplorationBonus::Off => 0.0,
+            ExplorationBonus::Tiles(bonus) => bonus,
+            ExplorationBonus::Junctions(bonus) => {
+                if board.is_junction(tile) {
+                    bonus
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
 }
 
 impl Default for Amidar {
@@ -111,20 +557,39 @@ impl Default for Amidar {
         Amidar {
             rand: random::Gen::new_from_seed(13),
             board: AMIDAR_BOARD.lines().map(|s| s.to_owned()).collect(),
+            boards: Vec::new(),
+            level_board_order: Vec::new(),
+            board_generator: None,
             player_start: TilePoint::new(31, 15),
+            theme: None,
             bg_color: Color::black(),
             player_color: Color::rgb(255, 255, 153),
             unpainted_color: Color::rgb(148, 0, 211),
             painted_color: Color::rgb(255, 255, 30),
             enemy_color: Color::rgb(255, 50, 100),
             inner_painted_color: Color::rgb(255, 255, 0),
+            penalty_color: Color::rgb(0, 160, 160),
             start_lives: 3,
+            refill_lives_on_level_clear: true,
+            extra_life_score: None,
+            count_life_in_play: false,
             start_jumps: 4,
-            chase_time: 10 * 30, // 10 seconds
+            chase_time: GameTime::Seconds(10.0),
             chase_score_bonus: 100,
-            jump_time: 2 * 30 + 15, // 2.5 seconds
-            render_images: true,
+            chase_bonus_factor: 1,
+            chase_bonus_max: None,
+            immobilized_time: 0,
+            respawn_point: None,
+            enemy_respawn_points: Vec::new(),
+            eyes_speed: None,
+            start_immobilized_base: 0,
+            vulnerable_warning_frames: 0,
+            flash_period_frames: 15,
+            jump_time: GameTime::Seconds(2.5),
+            render_images: cfg!(feature = "graphics"),
             box_bonus: 50,
+            penalty_tile_cost: 10,
+            penalty_slow_frames: 0,
             default_board_bugs: true,
             // limit number of junctions remembered to something greater than two.
             history_limit: 12,
@@ -134,8 +599,57 @@ impl Default for Amidar {
                     default_route_index: idx as u32,
                 })
                 .collect(),
+            enemy_releases: Vec::new(),
+            enemies_per_level: Vec::new(),
+            difficulty_table: Vec::new(),
+            adaptive_difficulty: None,
             enemy_starting_speed: inits::ENEMY_STARTING_SPEED,
+            chase_enemy_speed: inits::ENEMY_STARTING_SPEED,
+            enemy_speed_offsets: Vec::new(),
+            elroy_part1_tiles: None,
+            elroy_part1_speed: 2,
+            elroy_part2_tiles: None,
+            elroy_part2_speed: 4,
+            frightened_policy: FrightenedPolicy::Unchanged,
             player_speed: inits::PLAYER_SPEED,
+            debug_ai: false,
+            reward_scale: 1.0,
+            reward_clip: false,
+            level_clear_bonus: 0,
+            rollback_score_on_death: false,
+            collision_model: CollisionModel::Recheck,
+            collision_radius_world_units: None,
+            update_order: UpdateOrder::PlayerFirst,
+            stall_limit: None,
+            stall_ends_game: false,
+            track_visits: false,
+            exploration_bonus: ExplorationBonus::Off,
+            event_log_limit: 64,
+            score_display: ScoreDisplay::Shown,
+            frames_per_second: 30.0,
+            catch_freeze_frames: 0,
+            score_popup_frames: 0,
+            audit_rng: false,
+            episodic_life: false,
+            respawn_grace_frames: 0,
+            death_pause_frames: 0,
+            respawn_pause_frames: 0,
+            ready_frames: 0,
+            level_complete_frames: 0,
+            blink_during_grace: true,
+            game_over_screen: false,
+            frame_skip: 1,
+            action_repeat_probability: 0.0,
+            latch_directions: false,
+            reverse_mid_step: false,
+            buffer_turns: false,
+            cornering_tolerance: None,
+            observation_stack: 0,
+            mode_schedule: Vec::new(),
+            reverse_on_mode_change: false,
+            bonus_gates: Vec::new(),
+            gate_color: Color::rgb(80, 200, 255),
+            unpainter: None,
         }
     }
 }
@@ -170,6 +684,11 @@ impl WorldPoint {
     pub fn translate(&self, dx: i32, dy: i32) -> WorldPoint {
         WorldPoint::new(self.x + dx, self.y + dy)
     }
+    pub fn distance_squared(&self, other: &WorldPoint) -> i64 {
+        let dx = i64::from(self.x - other.x);
+        let dy = i64::from(self.y - other.y);
+        dx * dx + dy * dy
+    }
 }
 
 impl TilePoint {
@@ -179,6 +698,11 @@ impl TilePoint {
     pub fn manhattan_dist(&self, other: &TilePoint) -> i32 {
         (self.tx - other.tx).abs() + (self.ty - other.ty).abs()
     }
+    pub fn distance_squared(&self, other: &TilePoint) -> i32 {
+        let dx = self.tx - other.tx;
+        let dy = self.ty - other.ty;
+        dx * dx + dy * dy
+    }
     pub fn to_world(&self) -> WorldPoint {
         WorldPoint::new(self.tx * world::TILE_SIZE.0, self.ty * world::TILE_SIZE.1)
     }
@@ -243,19 +767,32 @@ impl Tile {
             '=' => Ok(Tile::Unpainted),
             'p' => Ok(Tile::Painted),
             'c' => Ok(Tile::ChaseMarker),
+            'x' => Ok(Tile::Penalty),
             ' ' => Ok(Tile::Empty),
+            // Spawn markers are plain track; ``Board::try_new`` notes where they are.
+            '1'..='9' | 'P' => Ok(Tile::Unpainted),
             _ => Err(format!("Cannot construct AmidarTile from '{}'", c)),
         }
     }
+    /// The board-file character for this tile; the inverse of parsing.
+    pub fn to_char(self) -> char {
+        match self {
+            Tile::Unpainted => '=',
+            Tile::Painted => 'p',
+            Tile::ChaseMarker => 'c',
+            Tile::Penalty => 'x',
+            Tile::Empty => ' ',
+        }
+    }
     pub fn walkable(self) -> bool {
         match self {
             Tile::Empty => false,
-            Tile::ChaseMarker | Tile::Painted | Tile::Unpainted => true,
+            Tile::ChaseMarker | Tile::Painted | Tile::Unpainted | Tile::Penalty => true,
         }
     }
     pub fn needs_paint(self) -> bool {
         match self {
-            Tile::Painted | Tile::Empty => false,
+            Tile::Painted | Tile::Penalty | Tile::Empty => false,
             Tile::ChaseMarker | Tile::Unpainted => true,
         }
     }
@@ -284,6 +821,26 @@ impl MovementAI {
                 ref mut dir,
                 start_dir,
                 ..
+            }
+            | MovementAI::EnemyChase {
+                ref mut dir,
+                start_dir,
+                ..
+            }
+            | MovementAI::EnemyAmbush {
+                ref mut dir,
+                start_dir,
+                ..
+            }
+            | MovementAI::EnemyFlank {
+                ref mut dir,
+                start_dir,
+                ..
+            }
+            | MovementAI::EnemyShy {
+                ref mut dir,
+                start_dir,
+                ..
             } => {
                 *dir = *start_dir;
             }
@@ -296,6 +853,189 @@ impl MovementAI {
                 *dir = *start_dir;
                 *player_seen = None;
             }
+            MovementAI::EnemyScheduled {
+                ref mut schedule,
+                ref mut cursor,
+                ref mut frames,
+            } => {
+                *cursor = 0;
+                *frames = 0;
+                for (ai, _) in schedule.iter_mut() {
+                    ai.reset();
+                }
+            }
+        }
+    }
+    /// Flip the direction this AI is heading in; false for AIs that follow a fixed route and so can't turn around.
+    fn reverse(&mut self) -> bool {
+        match self {
+            MovementAI::Player
+            | MovementAI::EnemyLookupAI { .. }
+            | MovementAI::EnemyPerimeterAI { .. } => false,
+            MovementAI::EnemyAmidarMvmt {
+                ref mut vert,
+                ref mut horiz,
+                ..
+            } => {
+                *vert = vert.opposite();
+                *horiz = horiz.opposite();
+                true
+            }
+            MovementAI::EnemyRandomMvmt { ref mut dir, .. }
+            | MovementAI::EnemyTargetPlayer { ref mut dir, .. }
+            | MovementAI::EnemyChase { ref mut dir, .. }
+            | MovementAI::EnemyAmbush { ref mut dir, .. }
+            | MovementAI::EnemyFlank { ref mut dir, .. }
+            | MovementAI::EnemyShy { ref mut dir, .. } => {
+                *dir = dir.opposite();
+                true
+            }
+            MovementAI::EnemyScheduled {
+                ref mut schedule,
+                cursor,
+                ..
+            } => match schedule.get_mut(*cursor) {
+                Some((ai, _)) => ai.reverse(),
+                None => false,
+            },
+        }
+    }
+    /// Start (and restart) from ``tile`` instead; false for AIs that follow a fixed route and can't.
+    fn set_start(&mut self, tile: &TilePoint) -> bool {
+        match self {
+            MovementAI::Player
+            | MovementAI::EnemyLookupAI { .. }
+            | MovementAI::EnemyPerimeterAI { .. } => false,
+            MovementAI::EnemyAmidarMvmt { start, .. }
+            | MovementAI::EnemyRandomMvmt { start, .. }
+            | MovementAI::EnemyTargetPlayer { start, .. }
+            | MovementAI::EnemyChase { start, .. }
+            | MovementAI::EnemyAmbush { start, .. }
+            | MovementAI::EnemyFlank { start, .. }
+            | MovementAI::EnemyShy { start, .. } => {
+                *start = tile.clone();
+                true
+            }
+            MovementAI::EnemyScheduled { schedule, .. } => match schedule.first_mut() {
+                Some((ai, _)) => ai.set_start(tile),
+                None => false,
+            },
+        }
+    }
+    /// Where does a mob with this AI start (and restart after a death)?
+    fn start_position(&self, player_start: &TilePoint, board: &Board) -> TilePoint {
+        match self {
+            MovementAI::Player => player_start.clone(),
+            MovementAI::EnemyLookupAI {
+                default_route_index,
+                ..
+            } => board.lookup_position(DEFAULT_ENEMY_ROUTES[*default_route_index as usize][0]),
+            MovementAI::EnemyPerimeterAI { .. } => TilePoint::new(0, 0),
+            MovementAI::EnemyAmidarMvmt { ref start, .. } => start.clone(),
+            MovementAI::EnemyRandomMvmt { ref start, .. } => start.clone(),
+            MovementAI::EnemyTargetPlayer { ref start, .. } => start.clone(),
+            MovementAI::EnemyChase { ref start, .. }
+            | MovementAI::EnemyAmbush { ref start, .. }
+            | MovementAI::EnemyFlank { ref start, .. }
+            | MovementAI::EnemyShy { ref start, .. } => start.clone(),
+            MovementAI::EnemyScheduled { ref schedule, .. } => schedule
+                .first()
+                .map(|(ai, _)| ai.start_position(player_start, board))
+                .unwrap_or_else(|| TilePoint::new(0, 0)),
+        }
+    }
+    /// Advance any frame-based AI bookkeeping; called once per frame.
+    fn tick(&mut self) {
+        if let MovementAI::EnemyScheduled {
+            ref schedule,
+            ref mut cursor,
+            ref mut frames,
+        } = self
+        {
+            if schedule.is_empty() {
+                return;
+            }
+            *frames += 1;
+            if *frames >= schedule[*cursor].1 {
+                *frames = 0;
+                *cursor = (*cursor + 1) % schedule.len();
+            }
+        }
+    }
+    /// Keep going until a junction or a wall, then take the exit closest (straight-line) to ``target``.
+    /// Like the arcade ghosts, never turn around unless there's no other way out.
+    fn steer_toward(
+        board: &Board,
+        position: &TilePoint,
+        dir: &mut Direction,
+        target: &TilePoint,
+        why: &mut Deliberation,
+    ) -> Option<TilePoint> {
+        let tp_default = board.can_move(position, *dir);
+        if tp_default.is_some() && !board.is_junction(position) {
+            return tp_default;
+        }
+        why.reason = AiReason::TargetSeeking;
+        // The arcade's tie-breaking order.
+        let directions = &[
+            Direction::Up,
+            Direction::Left,
+            Direction::Down,
+            Direction::Right,
+        ];
+        let eligible: Vec<(Direction, TilePoint)> = directions
+            .iter()
+            .filter_map(|d| board.can_move(position, *d).map(|tp| (*d, tp)))
+            .collect();
+        let forward: Vec<(Direction, TilePoint)> = eligible
+            .iter()
+            .filter(|(d, _)| *d != dir.opposite())
+            .cloned()
+            .collect();
+        let options = if forward.is_empty() {
+            eligible
+        } else {
+            forward
+        };
+        why.considered = options.iter().map(|(d, _)| *d).collect();
+        let best = options
+            .into_iter()
+            .min_by_key(|(_, tp)| tp.distance_squared(target));
+        best.map(|(d, tp)| {
+            *dir = d;
+            tp
+        })
+    }
+    /// Which way is a mob (usually the player) heading? None if it's standing still.
+    fn facing(mob: &Mob) -> Option<Direction> {
+        let here = mob.position.to_tile();
+        let next = mob.step.as_ref()?;
+        let (dx, dy) = (next.tx - here.tx, next.ty - here.ty);
+        if dx == 0 && dy == 0 {
+            None
+        } else if dx.abs() > dy.abs() {
+            Some(if dx < 0 {
+                Direction::Left
+            } else {
+                Direction::Right
+            })
+        } else {
+            Some(if dy < 0 {
+                Direction::Up
+            } else {
+                Direction::Down
+            })
+        }
+    }
+    /// The tile ``distance`` tiles ahead of the player (off the board is fine; it's only a target).
+    fn ahead_of_player(player: &Mob, distance: i32) -> TilePoint {
+        let tile = player.position.to_tile();
+        match MovementAI::facing(player) {
+            Some(facing) => {
+                let (dx, dy) = facing.delta();
+                TilePoint::new(tile.tx + dx * distance, tile.ty + dy * distance)
+            }
+            None => tile,
         }
     }
     fn choose_next_tile(
@@ -303,9 +1043,20 @@ impl MovementAI {
         position: &TilePoint,
         buttons: Input,
         board: &Board,
-        player: Option<Mob>,
+        surroundings: Surroundings,
         rng: &mut random::Gen,
+        why: &mut Deliberation,
     ) -> Option<TilePoint> {
+        let Surroundings {
+            player,
+            enemies,
+            mode,
+            catchable,
+            frightened_policy,
+            elroy,
+        } = surroundings;
+        why.reason = AiReason::Continue;
+        why.considered.clear();
         match self {
             &mut MovementAI::Player => {
                 let mut input: Option<Direction> = None;
@@ -334,9 +1085,11 @@ impl MovementAI {
             } => {
                 let path = &DEFAULT_ENEMY_ROUTES[default_route_index as usize];
                 *next = (*next + 1) % (path.len() as u32);
+                why.reason = AiReason::Scripted;
                 Some(board.lookup_position(path[*next as usize]))
             }
             &mut MovementAI::EnemyPerimeterAI { .. } => {
+                why.reason = AiReason::Scripted;
                 let perimeter = board.get_perimeter(position);
                 let mut tilepoint = None;
                 for dir in perimeter {
@@ -347,6 +1100,9 @@ impl MovementAI {
                         Direction::Left => Direction::Up,
                     };
                     let tp = board.can_move(position, go);
+                    if tp.is_some() {
+                        why.considered.push(go);
+                    }
                     tilepoint = tilepoint.or(tp)
                 }
                 tilepoint
@@ -359,8 +1115,15 @@ impl MovementAI {
                 let maybe_vert: Option<TilePoint> = board.can_move(position, *vert);
                 let perimeter: Vec<Direction> = board.get_perimeter(position);
                 let maybe_horiz = board.can_move(position, *horiz);
+                if maybe_vert.is_some() {
+                    why.considered.push(*vert);
+                }
+                if maybe_horiz.is_some() {
+                    why.considered.push(*horiz);
+                }
                 if perimeter.contains(vert) {
                     *vert = vert.opposite();
+                    why.reason = AiReason::Blocked;
                 }
                 if maybe_vert.is_some() {
                     // Check to see if we are on the left or right sides
@@ -377,6 +1140,8 @@ impl MovementAI {
                 } else {
                     // Flip horiz
                     *horiz = horiz.opposite();
+                    why.reason = AiReason::Blocked;
+                    why.considered.push(*horiz);
                     board.can_move(position, *horiz)
                 }
             }
@@ -389,12 +1154,19 @@ impl MovementAI {
                 ];
                 let tp_default = board.can_move(position, *dir);
                 if board.is_junction(position) || tp_default.is_none() {
+                    why.reason = if tp_default.is_none() {
+                        AiReason::Blocked
+                    } else {
+                        AiReason::Junction
+                    };
                     let eligible: Vec<(&Direction, Option<TilePoint>)> = directions
                         .iter()
                         .map(|d| (d, board.can_move(position, *d)))
                         .filter(|(_, tp)| tp.is_some())
                         .collect();
-                    let (d, tp) = eligible.choose(rng).cloned().unwrap();
+                    why.considered = eligible.iter().map(|(d, _)| **d).collect();
+                    // Boxed in on every side: stay put.
+                    let (d, tp) = eligible.choose(rng).cloned()?;
                     // Move to the randomly selected tile point, in its dir.
                     *dir = *d;
                     return tp;
@@ -405,6 +1177,7 @@ impl MovementAI {
                 ref mut player_seen,
                 ref mut dir,
                 vision_distance,
+                line_of_sight,
                 ..
             } => {
                 let player = player.unwrap();
@@ -412,11 +1185,33 @@ impl MovementAI {
                 let player_tile = player.position.to_tile();
                 let px = player_tile.tx;
                 let py = player_tile.ty;
-                if board.is_line_of_sight(position, &player_tile)
-                    && position.manhattan_dist(&player_tile) <= vision_distance
-                {
+                let sees_player = position.manhattan_dist(&player_tile) <= vision_distance
+                    && (!line_of_sight || board.is_line_of_sight(position, &player_tile));
+                if catchable && sees_player {
+                    // Run: turn tail at once rather than waiting for a junction, then keep picking the exit that leads furthest away.
+                    *player_seen = None;
+                    let away = TilePoint::new(2 * position.tx - px, 2 * position.ty - py);
+                    let here = position.distance_squared(&player_tile);
+                    if board
+                        .can_move(position, dir.opposite())
+                        .filter(|tp| tp.distance_squared(&player_tile) > here)
+                        .is_some()
+                    {
+                        *dir = dir.opposite();
+                    }
+                    let next = MovementAI::steer_toward(board, position, dir, &away, why);
+                    why.reason = AiReason::Fleeing;
+                    next
+                } else if sees_player && !line_of_sight {
+                    // The player may be around a corner, so find the way there.
+                    *player_seen = Some(player_tile.clone());
+                    let next = MovementAI::steer_toward(board, position, dir, &player_tile, why);
+                    why.reason = AiReason::TargetSeeking;
+                    next
+                } else if sees_player {
                     // The player is currently within view
                     *player_seen = Some(player_tile);
+                    why.reason = AiReason::TargetSeeking;
                     *dir = if px == position.tx {
                         if py < position.ty {
                             Direction::Up
@@ -435,13 +1230,28 @@ impl MovementAI {
                         // the trail is stale. Reset.
                         *player_seen = None;
                     }
-                    if player_seen.is_some() {
+                    if catchable {
+                        // Don't go looking for a player that can catch us.
+                        *player_seen = None;
+                    }
+                    if let (Some(seen), false) = (player_seen.clone(), line_of_sight) {
+                        // Head for where the player was, the same way we'd head for the player.
+                        let next = MovementAI::steer_toward(board, position, dir, &seen, why);
+                        why.reason = AiReason::TargetSeeking;
+                        next
+                    } else if player_seen.is_some() {
                         // We are still tracking the player
+                        why.reason = AiReason::TargetSeeking;
                         board.can_move(position, *dir)
                     } else {
                         // Explore
                         let tp_default = board.can_move(position, *dir);
                         if board.is_junction(position) || tp_default.is_none() {
+                            why.reason = if tp_default.is_none() {
+                                AiReason::Blocked
+                            } else {
+                                AiReason::Junction
+                            };
                             let directions = &[
                                 Direction::Up,
                                 Direction::Down,
@@ -453,7 +1263,8 @@ impl MovementAI {
                                 .map(|d| (d, board.can_move(position, *d)))
                                 .filter(|(_, tp)| tp.is_some())
                                 .collect();
-                            let (d, tp) = eligible.choose(rng).cloned().unwrap();
+                            why.considered = eligible.iter().map(|(d, _)| **d).collect();
+                            let (d, tp) = eligible.choose(rng).cloned()?;
                             *dir = *d;
                             tp
                         } else {
@@ -462,6 +1273,134 @@ impl MovementAI {
                     }
                 }
             }
+            &mut MovementAI::EnemyChase {
+                ref start,
+                ref mut dir,
+                ..
+            }
+            | &mut MovementAI::EnemyAmbush {
+                ref start,
+                ref mut dir,
+                ..
+            }
+            | &mut MovementAI::EnemyFlank {
+                ref start,
+                ref mut dir,
+                ..
+            }
+            | &mut MovementAI::EnemyShy {
+                ref start,
+                ref mut dir,
+                ..
+            } if mode == EnemyMode::Scatter => {
+                MovementAI::steer_toward(board, position, dir, start, why)
+            }
+            &mut MovementAI::EnemyChase { ref mut dir, .. } => {
+                let target = player.unwrap().position.to_tile();
+                MovementAI::steer_toward(board, position, dir, &target, why)
+            }
+            &mut MovementAI::EnemyAmbush {
+                ref mut dir,
+                lookahead,
+                ..
+            } => {
+                let target = MovementAI::ahead_of_player(&player.unwrap(), lookahead);
+                MovementAI::steer_toward(board, position, dir, &target, why)
+            }
+            &mut MovementAI::EnemyFlank {
+                ref mut dir,
+                lookahead,
+                partner,
+                ..
+            } => {
+                let player = player.unwrap();
+                let pivot = MovementAI::ahead_of_player(&player, lookahead);
+                let target = match enemies.get(partner) {
+                    Some(partner) => {
+                        TilePoint::new(2 * pivot.tx - partner.tx, 2 * pivot.ty - partner.ty)
+                    }
+                    None => player.position.to_tile(),
+                };
+                MovementAI::steer_toward(board, position, dir, &target, why)
+            }
+            &mut MovementAI::EnemyShy {
+                ref mut dir,
+                shy_distance,
+                ref retreat,
+                ..
+            } => {
+                let player_tile = player.unwrap().position.to_tile();
+                let target =
+                    if position.distance_squared(&player_tile) > shy_distance * shy_distance {
+                        player_tile
+                    } else {
+                        retreat.clone()
+                    };
+                MovementAI::steer_toward(board, position, dir, &target, why)
+            }
+            &mut MovementAI::EnemyScheduled {
+                ref mut schedule,
+                cursor,
+                ..
+            } => match schedule.get_mut(cursor) {
+                Some((ai, _)) => {
+                    let surroundings = Surroundings {
+                        player,
+                        enemies,
+                        mode,
+                        catchable,
+                        frightened_policy,
+                        elroy,
+                    };
+                    ai.choose_next_tile(position, buttons, board, surroundings, rng, why)
+                }
+                None => None,
+            },
+        }
+    }
+}
+
+/// What an AI tells us about a move while making it: the rule it used, and the directions it weighed up.
+struct Deliberation {
+    reason: AiReason,
+    considered: Vec<Direction>,
+}
+
+impl Deliberation {
+    fn new() -> Deliberation {
+        Deliberation {
+            reason: AiReason::Continue,
+            considered: Vec::new(),
+        }
+    }
+}
+
+impl AiDecision {
+    fn new(position: TilePoint, target: Option<&TilePoint>, why: Deliberation) -> AiDecision {
+        let Deliberation { reason, considered } = why;
+        let directions = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ];
+        let chosen = target.and_then(|target| {
+            directions
+                .iter()
+                .cloned()
+                .find(|d| position.step(*d) == *target)
+        });
+        // Going straight on, or following a script, weighs up nothing but the way taken.
+        let considered = if considered.is_empty() {
+            chosen.into_iter().collect()
+        } else {
+            considered
+        };
+        AiDecision {
+            position,
+            considered,
+            chosen,
+            reason,
         }
     }
 }
@@ -473,8 +1412,15 @@ impl Mob {
             position,
             step: None,
             caught: false,
+            caught_this_frame: false,
+            chase_immune: false,
+            immobilized_timer: 0,
+            eaten: false,
             speed,
             history: VecDeque::new(),
+            released: true,
+            heading: None,
+            queued_direction: None,
         }
     }
     pub fn new_player(position: WorldPoint, speed: i32) -> Mob {
@@ -483,43 +1429,154 @@ impl Mob {
             position,
             step: None,
             caught: false,
+            caught_this_frame: false,
+            chase_immune: false,
+            immobilized_timer: 0,
+            eaten: false,
             speed,
             history: VecDeque::new(),
+            released: true,
+            heading: None,
+            queued_direction: None,
         }
     }
     fn is_player(&self) -> bool {
         self.ai == MovementAI::Player
     }
+    /// Can the player catch me, given whether chase mode is on?
+    fn catchable(&self, chasing: bool) -> bool {
+        chasing && !self.caught && !self.chase_immune && !self.eaten
+    }
     fn change_speed(&mut self, new_speed: i32) {
         self.speed = new_speed;
     }
     fn reset(&mut self, player_start: &TilePoint, board: &Board) {
         self.step = None;
+        self.heading = None;
+        self.queued_direction = None;
+        self.immobilized_timer = 0;
+        self.eaten = false;
         self.ai.reset();
-        self.position = match self.ai {
-            MovementAI::Player => player_start.to_world(),
-            MovementAI::EnemyLookupAI {
-                default_route_index,
-                ..
-            } => board
-                .lookup_position(DEFAULT_ENEMY_ROUTES[default_route_index as usize][0])
-                .to_world(),
-            MovementAI::EnemyPerimeterAI { .. } => TilePoint::new(0, 0).to_world(),
-            MovementAI::EnemyAmidarMvmt { ref start, .. } => start.clone().to_world(),
-            MovementAI::EnemyRandomMvmt { ref start, .. } => start.clone().to_world(),
-            MovementAI::EnemyTargetPlayer { ref start, .. } => start.clone().to_world(),
-        };
+        self.position = self.ai.start_position(player_start, board).to_world();
         self.history.clear();
     }
 
-    pub fn update(
-        &mut self,
-        buttons: Input,
-        board: &mut Board,
-        player: Option<Mob>,
-        history_limit: u32,
-        rng: &mut random::Gen,
+    /// If ``buttons`` press the way back while I'm between tiles, head back to the tile I came from.
+    fn turn_around(&mut self, buttons: Input) {
+        let back = match self.heading {
+            Some(heading) => heading.opposite(),
+            None => return,
+        };
+        let pressed = match back {
+            Direction::Up => buttons.up,
+            Direction::Down => buttons.down,
+            Direction::Left => buttons.left,
+            Direction::Right => buttons.right,
+        };
+        if pressed {
+            self.step_back();
+        }
+    }
+    /// Head for ``home`` as eyes at ``speed``, the shortest way along the track; true once there, or if there's no way there.
+    fn return_home(&mut self, home: &TilePoint, speed: i32, board: &Board) -> bool {
+        if self.step.is_none() {
+            let here = self.position.to_tile();
+            if here == *home {
+                return true;
+            }
+            self.step = board
+                .shortest_path(&here, home)
+                .and_then(|path| path.get(1).cloned());
+        }
+        let target = match &self.step {
+            Some(target) => target.to_world(),
+            None => return true,
+        };
+        let dx = target.x - self.position.x;
+        let dy = target.y - self.position.y;
+        self.position.x += dx.signum() * dx.abs().min(speed);
+        self.position.y += dy.signum() * dy.abs().min(speed);
+        if self.position.x == target.x && self.position.y == target.y {
+            self.step = None;
+        }
+        false
+    }
+    /// Turn an enemy around on the spot, AI and all; see ``reverse_on_mode_change``.
+    fn reverse(&mut self) {
+        if self.ai.reverse() {
+            self.step_back();
+        }
+    }
+    /// If I'm between tiles, head back to the tile I came from instead.
+    fn step_back(&mut self) {
+        if let (Some(target), Some(heading)) = (&self.step, self.heading) {
+            let (dx, dy) = heading.delta();
+            self.step = Some(TilePoint::new(target.tx - dx, target.ty - dy));
+            self.heading = Some(heading.opposite());
+        }
+    }
+    /// Where a catchable enemy goes under ``policy``: straight on between junctions, and at one, either a random way out or the one furthest from ``player``.
+    /// Like the arcade ghosts, never turn around unless there's no other way out.
+    fn frightened_step(
+        &self,
+        position: &TilePoint,
+        policy: FrightenedPolicy,
+        player: Option<TilePoint>,
+        board: &Board,
+        rng: &mut random::Gen,
+        why: &mut Deliberation,
+    ) -> Option<TilePoint> {
+        if let Some(heading) = self.heading {
+            let ahead = board.can_move(position, heading);
+            if ahead.is_some() && !board.is_junction(position) {
+                return ahead;
+            }
+        }
+        let directions = &[
+            Direction::Up,
+            Direction::Left,
+            Direction::Down,
+            Direction::Right,
+        ];
+        let eligible: Vec<(Direction, TilePoint)> = directions
+            .iter()
+            .filter(|d| self.heading != Some(d.opposite()))
+            .filter_map(|d| board.can_move(position, *d).map(|tp| (*d, tp)))
+            .collect();
+        let options = if eligible.is_empty() {
+            directions
+                .iter()
+                .filter_map(|d| board.can_move(position, *d).map(|tp| (*d, tp)))
+                .collect()
+        } else {
+            eligible
+        };
+        why.considered = options.iter().map(|(d, _)| *d).collect();
+        match (policy, player) {
+            (FrightenedPolicy::Flee, Some(player)) => {
+                why.reason = AiReason::Fleeing;
+                options
+                    .into_iter()
+                    .map(|(_, tp)| tp)
+                    .max_by_key(|tp| tp.distance_squared(&player))
+            }
+            _ => {
+                why.reason = AiReason::Junction;
+                options.choose(rng).map(|(_, tp)| tp.clone())
+            }
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        buttons: Input,
+        board: &mut Board,
+        surroundings: Surroundings,
+        history_limit: u32,
+        rng: &mut random::Gen,
+        decision: Option<&mut Option<AiDecision>>,
     ) -> Option<BoardUpdate> {
+        self.ai.tick();
         if self.history.is_empty() {
             if let Some(pt) = board.get_junction_id(&self.position.to_tile()) {
                 self.history.push_front(pt);
@@ -561,9 +1618,42 @@ impl Mob {
 
         // Not an else if -- if a player or enemy reaches a tile they can immediately choose a new target.
         if self.step.is_none() {
-            self.step =
-                self.ai
-                    .choose_next_tile(&self.position.to_tile(), buttons, board, player, rng)
+            let position = self.position.to_tile();
+            let mut why = Deliberation::new();
+            // A lookup table jumps to the next tile on its route, wherever the enemy is, so it can't be steered.
+            let steerable = !matches!(self.ai, MovementAI::EnemyLookupAI { .. });
+            let policy = if steerable && surroundings.catchable {
+                surroundings.frightened_policy
+            } else {
+                FrightenedPolicy::Unchanged
+            };
+            let elroy_target = match &surroundings.player {
+                Some(player) if steerable && surroundings.elroy && !surroundings.catchable => {
+                    Some(player.position.to_tile())
+                }
+                _ => None,
+            };
+            self.step = match policy {
+                FrightenedPolicy::Unchanged if elroy_target.is_some() => {
+                    let mut dir = self.heading.unwrap_or(Direction::Up);
+                    let target = elroy_target.unwrap();
+                    MovementAI::steer_toward(board, &position, &mut dir, &target, &mut why)
+                }
+                FrightenedPolicy::Unchanged => {
+                    self.ai
+                        .choose_next_tile(&position, buttons, board, surroundings, rng, &mut why)
+                }
+                FrightenedPolicy::Random | FrightenedPolicy::Flee => {
+                    let player = surroundings.player.map(|p| p.position.to_tile());
+                    self.frightened_step(&position, policy, player, board, rng, &mut why)
+                }
+            };
+            if let Some(heading) = MovementAI::facing(self) {
+                self.heading = Some(heading);
+            }
+            if let Some(decision) = decision {
+                *decision = Some(AiDecision::new(position, self.step.as_ref(), why));
+            }
         }
 
         // Manage history:
@@ -611,6 +1701,7 @@ impl BoardUpdate {
             horizontal: 0,
             num_boxes: 0,
             triggers_chase: false,
+            penalties: 0,
         }
     }
     fn happened(&self) -> bool {
@@ -619,6 +1710,7 @@ impl BoardUpdate {
             || self.horizontal != 0
             || self.num_boxes != 0
             || self.triggers_chase
+            || self.penalties != 0
     }
     fn into_option(self) -> Option<Self> {
         if self.happened() {
@@ -633,32 +1725,76 @@ impl Board {
     pub fn fast_new() -> Board {
         DEFAULT_BOARD.clone()
     }
-    fn try_new(lines: &[String]) -> Result<Board, String> {
-        let mut tiles = Vec::new();
+    /// Parse a board from lines of tile characters (see ``Amidar::board``).
+    pub fn try_new(lines: &[String]) -> Result<Board, String> {
+        let mut rows = Vec::new();
         for line in lines {
             // Rust will aggregate errors in collect for us if we give it a type-hint.
             let row: Result<Vec<_>, _> = line.chars().map(Tile::new_from_char).collect();
             // Exit function if row is errorful.
-            tiles.push(row?);
+            rows.push(row?);
+        }
+        if rows.is_empty() {
+            return Err("A board needs at least one row.".to_string());
+        }
+        let width = rows[0].len() as u32;
+        let height = rows.len() as u32;
+        if width > world::MAX_BOARD_TILES || height > world::MAX_BOARD_TILES {
+            return Err(format!(
+                "A board can be at most {0}x{0} tiles, not {1}x{2}.",
+                world::MAX_BOARD_TILES,
+                width,
+                height
+            ));
+        }
+
+        let mut enemy_spawns: Vec<Option<TilePoint>> = Vec::new();
+        let mut player_spawn = None;
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == 'P' {
+                    if player_spawn.is_some() {
+                        return Err("Spawn marker 'P' appears more than once.".to_string());
+                    }
+                    player_spawn = Some(TilePoint::new(x as i32, y as i32));
+                    continue;
+                }
+                let index = match c.to_digit(10) {
+                    Some(n) if n > 0 => (n - 1) as usize,
+                    _ => continue,
+                };
+                if enemy_spawns.len() <= index {
+                    enemy_spawns.resize(index + 1, None);
+                }
+                if enemy_spawns[index].is_some() {
+                    return Err(format!("Spawn marker '{}' appears more than once.", c));
+                }
+                enemy_spawns[index] = Some(TilePoint::new(x as i32, y as i32));
+            }
         }
-        let width = tiles[0].len() as u32;
-        let height = tiles.len() as u32;
 
+        let (tiles, ragged_rows) = flatten_rows(rows, width, height);
         let mut board = Board {
-            tiles,
+            tiles_to_paint: Board::count_tiles_to_paint(&tiles),
+            tiles: Arc::new(tiles),
             width,
             height,
-            junctions: HashSet::new(),
-            chase_junctions: HashSet::new(),
-            boxes: Vec::new(),
+            junctions: Arc::new(BTreeSet::new()),
+            chase_junctions: Arc::new(BTreeSet::new()),
+            boxes: Arc::new(Vec::new()),
+            ragged_rows,
+            enemy_spawns,
+            player_spawn,
         };
         board.init_junctions();
         debug_assert!(board.boxes.is_empty());
-        board.boxes = board
-            .junctions
-            .iter()
-            .flat_map(|pt| board.junction_corners(*pt))
-            .collect();
+        board.boxes = Arc::new(
+            board
+                .junctions
+                .iter()
+                .flat_map(|pt| board.junction_corners(*pt))
+                .collect(),
+        );
         Ok(board)
     }
 
@@ -759,7 +1895,9 @@ impl Board {
         // Only run this function once.
         debug_assert!(self.junctions.is_empty());
 
-        for (y, row) in self.tiles.iter().enumerate() {
+        let mut junctions = BTreeSet::new();
+        let mut chase_junctions = BTreeSet::new();
+        for (y, row) in self.rows().enumerate() {
             let y = y as i32;
             for (x, cell) in row.iter().enumerate() {
                 let x = x as i32;
@@ -772,14 +1910,16 @@ impl Board {
                     if walkable_neighbors > 2 || self.is_corner(x, y) {
                         let y = y as u32;
                         let x = x as u32;
-                        let _ = self.junctions.insert(y * self.width + x);
+                        let _ = junctions.insert(y * self.width + x);
                         if cell == &Tile::ChaseMarker {
-                            self.chase_junctions.insert(y * self.width + x);
+                            chase_junctions.insert(y * self.width + x);
                         }
                     }
                 }
             }
         }
+        self.junctions = Arc::new(junctions);
+        self.chase_junctions = Arc::new(chase_junctions);
     }
 
     fn is_painted(&self, xy: &TilePoint) -> bool {
@@ -869,10 +2009,13 @@ impl Board {
 
         let updated = indices.len() as i32;
         let mut chase_change = false;
-        for i in indices {
-            self.boxes[i].painted = true;
-            if self.boxes[i].triggers_chase {
-                chase_change = true;
+        if !indices.is_empty() {
+            let boxes = Arc::make_mut(&mut self.boxes);
+            for i in indices {
+                boxes[i].painted = true;
+                if boxes[i].triggers_chase {
+                    chase_change = true;
+                }
             }
         }
 
@@ -900,11 +2043,16 @@ impl Board {
                 debug_assert!(dx.abs() + dy.abs() == 1);
 
                 let mut newly_painted = false;
-                newly_painted |= self.paint(&t1);
                 let mut t = t1.clone();
-                while t != t2 {
-                    t = t.translate(dx, dy);
+                loop {
+                    if self.get_tile(&t) == Tile::Penalty {
+                        score_change.penalties += 1;
+                    }
                     newly_painted |= self.paint(&t);
+                    if t == t2 {
+                        break;
+                    }
+                    t = t.translate(dx, dy);
                 }
 
                 // vertical segments give you 1, horizontal give you length
@@ -932,15 +2080,58 @@ impl Board {
         score_change
     }
 
+    /// Change any of the tiles, one row after another, in ``edit``; a board sharing them with its copies gets its own first.
+    pub fn edit_tiles(&mut self, edit: impl FnOnce(&mut [Tile])) {
+        edit(Arc::make_mut(&mut self.tiles).as_mut_slice());
+        self.tiles_to_paint = Board::count_tiles_to_paint(&self.tiles);
+    }
+    /// How many tiles still need painting before the level is complete?
+    pub fn tiles_to_paint(&self) -> u32 {
+        self.tiles_to_paint
+    }
+    pub(crate) fn count_tiles_to_paint(tiles: &[Tile]) -> u32 {
+        tiles.iter().filter(|tile| tile.needs_paint()).count() as u32
+    }
+    /// The tiles, a row at a time.
+    pub fn rows(&self) -> std::slice::Chunks<'_, Tile> {
+        self.tiles.chunks(self.width.max(1) as usize)
+    }
+    /// Change one tile; tiles off the board are ignored.
+    pub fn set_tile(&mut self, tile: &TilePoint, value: Tile) {
+        if let Some(id) = self.tile_id(tile) {
+            let old = std::mem::replace(&mut Arc::make_mut(&mut self.tiles)[id as usize], value);
+            if old.needs_paint() {
+                self.tiles_to_paint -= 1;
+            }
+            if value.needs_paint() {
+                self.tiles_to_paint += 1;
+            }
+        }
+    }
     pub fn paint(&mut self, tile: &TilePoint) -> bool {
-        let val = &mut self.tiles[tile.ty as usize][tile.tx as usize];
-        if *val == Tile::Painted {
+        if self.get_tile(tile) == Tile::Painted {
             false
         } else {
-            *val = Tile::Painted;
+            self.set_tile(tile, Tile::Painted);
             true
         }
     }
+    /// Undo ``paint``: the tile, and every box it borders, need painting again. Returns false if it wasn't painted.
+    pub fn unpaint(&mut self, tile: &TilePoint) -> bool {
+        if self.get_tile(tile) != Tile::Painted {
+            return false;
+        }
+        self.set_tile(tile, Tile::Unpainted);
+        if self.boxes.iter().any(|b| b.painted && b.matches(tile)) {
+            for b in Arc::make_mut(&mut self.boxes)
+                .iter_mut()
+                .filter(|b| b.matches(tile))
+            {
+                b.painted = false;
+            }
+        }
+        true
+    }
     pub fn make_enemy(&self, ai: MovementAI, speed: i32) -> Mob {
         let fake = TilePoint::new(0, 0);
         let mut m = Mob::new(ai, fake.to_world(), speed);
@@ -952,42 +2143,265 @@ impl Board {
         let y = position / self.width;
         TilePoint::new(x as i32, y as i32)
     }
-    fn get_tile(&self, tile: &TilePoint) -> Tile {
-        if let Some(row) = self.tiles.get(tile.ty as usize) {
-            if let Some(t) = row.get(tile.tx as usize) {
-                return *t;
+    /// Check a board for problems that would make it unplayable or surprising, with the player starting at ``start``.
+    pub fn lint(&self, start: &TilePoint) -> BoardReport {
+        let directions = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ];
+        let width = self.width as i32;
+        let all_tiles: Vec<TilePoint> = (0..self.height as i32)
+            .flat_map(|ty| (0..width).map(move |tx| TilePoint::new(tx, ty)))
+            .collect();
+        let walkable: Vec<&TilePoint> = all_tiles
+            .iter()
+            .filter(|tp| self.get_tile(tp).walkable())
+            .collect();
+
+        // Flood-fill each walkable region; remember which one the start is in.
+        let mut region = vec![None; (self.width * self.height) as usize];
+        let mut regions = 0;
+        for seed in &walkable {
+            let seed_id = match self.tile_id(seed) {
+                Some(id) => id as usize,
+                None => continue,
+            };
+            if region[seed_id].is_some() {
+                continue;
+            }
+            regions += 1;
+            region[seed_id] = Some(regions);
+            let mut frontier = vec![(*seed).clone()];
+            while let Some(tp) = frontier.pop() {
+                for dir in directions.iter() {
+                    if let Some(next) = self.can_move(&tp, *dir) {
+                        if let Some(id) = self.tile_id(&next) {
+                            if region[id as usize].is_none() {
+                                region[id as usize] = Some(regions);
+                                frontier.push(next);
+                            }
+                        }
+                    }
+                }
             }
         }
-        Tile::Empty
-    }
+        let start_region = self.tile_id(start).and_then(|id| region[id as usize]);
 
-    pub fn board_complete(&self) -> bool {
-        // if this is too slow, we can store a private variable for the number of
-        // unpainted tiles
-        for row in &self.tiles {
-            for tile in row {
-                if tile.needs_paint() {
-                    return false;
+        BoardReport {
+            width: self.width,
+            height: self.height,
+            ragged_rows: self.ragged_rows.clone(),
+            walkable_tiles: walkable.len() as u32,
+            paint_tiles: walkable
+                .iter()
+                .filter(|tp| self.get_tile(tp).needs_paint())
+                .count() as u32,
+            junctions: self.junctions.len() as u32,
+            chase_junctions: self.chase_junctions.len() as u32,
+            boxes: self.boxes.len() as u32,
+            regions,
+            dead_ends: walkable
+                .iter()
+                .filter(|tp| {
+                    directions
+                        .iter()
+                        .filter(|dir| self.can_move(tp, **dir).is_some())
+                        .count()
+                        == 1
+                })
+                .map(|tp| (*tp).clone())
+                .collect(),
+            start: start.clone(),
+            start_walkable: start_region.is_some(),
+            unreachable: walkable
+                .iter()
+                .filter(|tp| self.get_tile(tp).needs_paint())
+                .filter(|tp| {
+                    let id = self.tile_id(tp).map(|id| region[id as usize]);
+                    start_region.is_none() || id != Some(start_region)
+                })
+                .map(|tp| (*tp).clone())
+                .collect(),
+        }
+    }
+    /// Check the board is playable from ``start``; see ``lint`` for the details behind any problems.
+    pub fn validate(&self, start: &TilePoint) -> Result<(), Vec<BoardProblem>> {
+        let problems = self.lint(start).errors();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+    /// Breadth-first search over walkable tiles from ``from``; returns each tile's id and the id it was reached from.
+    fn search(&self, from: &TilePoint) -> Vec<Option<(u32, Option<u32>)>> {
+        let mut found = vec![None; (self.width * self.height) as usize];
+        let start = match self.tile_id(from) {
+            Some(id) if self.get_tile(from).walkable() => id,
+            _ => return found,
+        };
+        found[start as usize] = Some((0, None));
+        let mut frontier = VecDeque::new();
+        frontier.push_back(from.clone());
+        while let Some(tp) = frontier.pop_front() {
+            let id = self.tile_id(&tp).unwrap();
+            let (steps, _) = found[id as usize].unwrap();
+            for dir in &[
+                Direction::Up,
+                Direction::Down,
+                Direction::Left,
+                Direction::Right,
+            ] {
+                if let Some(next) = self.can_move(&tp, *dir) {
+                    if let Some(next_id) = self.tile_id(&next) {
+                        if found[next_id as usize].is_none() {
+                            found[next_id as usize] = Some((steps + 1, Some(id)));
+                            frontier.push_back(next);
+                        }
+                    }
                 }
             }
         }
-        true
+        found
+    }
+    /// How many steps along the track each tile is from ``from``, row by row like ``tiles``; None where it can't be reached.
+    pub fn distance_map(&self, from: &TilePoint) -> Vec<Vec<Option<u32>>> {
+        let found = self.search(from);
+        found
+            .chunks(self.width as usize)
+            .map(|row| row.iter().map(|f| f.map(|(steps, _)| steps)).collect())
+            .collect()
+    }
+    /// The tiles walked through going from ``from`` to ``to`` along the track, both ends included; None if there is no way there.
+    /// Unlike Manhattan distance, this goes around boxes.
+    pub fn shortest_path(&self, from: &TilePoint, to: &TilePoint) -> Option<Vec<TilePoint>> {
+        let found = self.search(from);
+        let mut id = self.tile_id(to)?;
+        found[id as usize]?;
+        let mut path = Vec::new();
+        loop {
+            path.push(TilePoint::new(
+                (id % self.width) as i32,
+                (id / self.width) as i32,
+            ));
+            match found[id as usize] {
+                Some((_, Some(previous))) => id = previous,
+                _ => break,
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
+    /// Draw the tiles alone (no player, enemies or boxes) as a small image, ``scale`` pixels per tile.
+    /// Empty tiles are black, unpainted tiles purple, chase markers white, painted tiles yellow and penalty tiles teal.
+    pub fn render_minimap(&self, scale: u32) -> ImageBuffer {
+        let scale = scale.max(1);
+        let mut image =
+            ImageBuffer::alloc((self.width * scale) as i32, (self.height * scale) as i32);
+        let width = image.width as usize;
+        for (ty, row) in self.rows().enumerate() {
+            for (tx, tile) in row.iter().enumerate() {
+                let color = match tile {
+                    Tile::Empty => Color::black(),
+                    Tile::Unpainted => Color::rgb(148, 0, 211),
+                    Tile::ChaseMarker => Color::white(),
+                    Tile::Painted => Color::rgb(255, 255, 30),
+                    Tile::Penalty => Color::rgb(0, 160, 160),
+                };
+                for y in ty * scale as usize..(ty + 1) * scale as usize {
+                    for x in tx * scale as usize..(tx + 1) * scale as usize {
+                        let start = (y * width + x) * 4;
+                        image.data[start..start + 4]
+                            .copy_from_slice(&[color.r, color.g, color.b, 255]);
+                    }
+                }
+            }
+        }
+        image
+    }
+    /// The tile at ``tile``; off the board, everything is ``Tile::Empty``.
+    pub fn get_tile(&self, tile: &TilePoint) -> Tile {
+        match self.tile_id(tile) {
+            Some(id) => self.tiles[id as usize],
+            None => Tile::Empty,
+        }
+    }
+
+    pub fn board_complete(&self) -> bool {
+        self.tiles_to_paint == 0
     }
 }
 
 impl State {
     pub fn try_new(config: &Amidar) -> Result<State, String> {
-        let board = Board::try_new(&config.board)?;
+        if config.render_images && !cfg!(feature = "graphics") {
+            return Err(
+                "render_images requires amidar to be built with the `graphics` feature.".to_owned(),
+            );
+        }
+        if config.frame_skip == 0 {
+            return Err("frame_skip must be at least 1.".to_owned());
+        }
+        if config.frames_per_second.is_nan() || config.frames_per_second <= 0.0 {
+            return Err(format!(
+                "frames_per_second must be more than 0, not {}.",
+                config.frames_per_second
+            ));
+        }
+        if let Some(tolerance) = config.cornering_tolerance {
+            if tolerance < 0 {
+                return Err(format!(
+                    "cornering_tolerance must not be negative, not {}.",
+                    tolerance
+                ));
+            }
+        }
+        if let Some(radius) = config.collision_radius_world_units {
+            if radius < 0 {
+                return Err(format!(
+                    "collision_radius_world_units must not be negative, not {}.",
+                    radius
+                ));
+            }
+        }
+        if !(0.0..=1.0).contains(&config.action_repeat_probability) {
+            return Err(format!(
+                "action_repeat_probability must be between 0 and 1, not {}.",
+                config.action_repeat_probability
+            ));
+        }
+        for (i, layout) in config.level_board_order.iter().enumerate() {
+            if *layout > config.boards.len() {
+                return Err(format!(
+                    "level_board_order[{}] is {}, but there are only {} layouts.",
+                    i,
+                    layout,
+                    config.boards.len() + 1
+                ));
+            }
+        }
+        for layout in std::iter::once(&config.board).chain(config.boards.iter()) {
+            config.check_spawn_markers(&Board::try_new(layout)?)?;
+        }
+        let board = Board::try_new(&config.board_for_level(1))?;
+        let level_tiles_to_paint = board.tiles_to_paint();
         let mut config = config.clone();
+        if let Some(generator) = config.board_generator.clone() {
+            config.player_start = generator.player_start();
+            config.default_board_bugs = false;
+        }
 
         let enemies = config
             .enemies
             .iter()
+            .take(config.enemies_for_level(1))
             .map(|ai| board.make_enemy(ai.clone(), config.enemy_starting_speed))
             .collect();
         let player = Mob::new_player(config.player_start.to_world(), config.player_speed);
 
-        let core = StateCore {
+        let mut core = StateCore {
             rand: random::Gen::new_child(&mut config.rand),
             lives: config.start_lives,
             score: 0,
@@ -998,653 +2412,5327 @@ impl State {
             player,
             enemies,
             board,
+            ai_debug: Vec::new(),
+            reward: 0.0,
+            events: Vec::new(),
+            reward_breakdown: RewardBreakdown::default(),
+            last_board_update: None,
+            level_frames: 0,
+            total_frames: 0,
+            level_deaths: 0,
+            level_tiles_to_paint,
+            stalled_frames: 0,
+            visit_counts: Vec::new(),
+            event_log: VecDeque::new(),
+            events_logged: 0,
+            kills_by_enemy: vec![0; config.enemies.len()],
+            catches_by_enemy: vec![0; config.enemies.len()],
+            catch_freeze: None,
+            rng_audit: Vec::new(),
+            chase_catches: 0,
+            lives_gained: 0,
+            extra_life_awarded: false,
+            life_lost: false,
+            game_over: false,
+            mode_index: 0,
+            mode_timer: 0,
+            penalty_slow_timer: 0,
+            gates_reached: vec![false; config.bonus_gates.len()],
+            unpainter_tiles: 0,
+            grace_timer: 0,
+            phase: GamePhase::Playing,
+            phase_timer: 0,
+            last_buttons: Input::default(),
+            latched_direction: None,
+            score_popups: Vec::new(),
+            observations: VecDeque::new(),
+            elroy_stage: 0,
+            level_override: None,
         };
+        if config.audit_rng {
+            core.rng_audit.push(RngDraw {
+                level: core.level,
+                frame: core.level_frames,
+                mob: None,
+                purpose: RngPurpose::NewGame,
+            });
+        }
 
         let mut state = State {
             config,
             state: core,
         };
+        state.apply_spawn_markers();
         state.reset();
+        state.update_enemy_releases();
+        state.apply_enemy_speed();
+        state.enter_phase(GamePhase::Ready, state.config.ready_frames);
+        // Until there's some history, the stack repeats the first observation.
+        for _ in 0..state.config.observation_stack {
+            state.push_observation();
+        }
         Ok(state)
     }
     pub fn reset(&mut self) {
-        self.state
-            .player
-            .reset(&self.config.player_start, &self.state.board);
+        self.state.latched_direction = None;
+        self.state.mode_index = 0;
+        self.state.mode_timer = 0;
+        self.state.penalty_slow_timer = 0;
+        let player_start = self.player_start();
+        self.state.player.reset(&player_start, &self.state.board);
         // On the default board, we imagine starting from below the initial place.
         // This way going up paints the first segment.
         if self.config.default_board_bugs {
-            self.state.player.history.push_front(
-                self.state
-                    .board
-                    .get_junction_id(&TilePoint::new(31, 18))
-                    .unwrap(),
-            );
+            if let Some(id) = self.state.board.get_junction_id(&TilePoint::new(31, 18)) {
+                self.state.player.history.push_front(id);
+            }
         }
+        let chasing = self.state.chase_timer > 0;
         for enemy in &mut self.state.enemies {
-            enemy.reset(&self.config.player_start, &self.state.board);
+            enemy.reset(&player_start, &self.state.board);
+            enemy.chase_immune = chasing;
         }
+        self.stagger_enemy_starts();
     }
-    pub fn board_size(&self) -> WorldPoint {
-        let th = self.state.board.height as i32;
-        let tw = self.state.board.width as i32;
-        TilePoint::new(tw + 1, th + 1).to_world()
-    }
-    /// Determine whether an enemy and a player are colliding and what to do about it.
-    /// returns: (player_dead, enemy_caught)
-    fn check_enemy_player_collision(&self, enemy: &Mob, enemy_id: usize) -> EnemyPlayerState {
-        if self.state.player.position.to_tile() == enemy.position.to_tile() {
-            if self.state.chase_timer > 0 {
-                if !enemy.caught {
-                    EnemyPlayerState::EnemyCatch(enemy_id)
-                } else {
-                    EnemyPlayerState::Miss
-                }
-            } else if self.state.jump_timer > 0 {
-                EnemyPlayerState::Miss
-            } else {
-                EnemyPlayerState::PlayerDeath
-            }
-        } else {
-            // No overlap.
-            EnemyPlayerState::Miss
+    /// Where ``immobilized_time`` sends enemy ``enemy`` once it's caught; see ``respawn_point``.
+    fn respawn_tile(&self, enemy: usize) -> TilePoint {
+        let chosen = self
+            .config
+            .enemy_respawn_points
+            .get(enemy)
+            .cloned()
+            .flatten()
+            .or_else(|| self.config.respawn_point.clone());
+        match chosen {
+            Some(tile) if self.state.board.get_tile(&tile).walkable() => tile,
+            _ => self.state.enemies[enemy]
+                .ai
+                .start_position(&self.player_start(), &self.state.board),
         }
     }
-}
-
-impl toybox_core::Simulation for Amidar {
-    fn reset_seed(&mut self, seed: u32) {
-        self.rand.reset_seed(seed)
+    /// Hold enemy ``i`` on its start tile for ``start_immobilized_base * i`` frames.
+    fn stagger_enemy_starts(&mut self) {
+        let base = self.config.start_immobilized_base.max(0);
+        for (i, enemy) in self.state.enemies.iter_mut().enumerate() {
+            enemy.immobilized_timer = base * i as i32;
+        }
     }
-    fn game_size(&self) -> (i32, i32) {
-        screen::GAME_SIZE
+    /// Where does the player start on this level's board? Its ``P`` marker if it has one, otherwise ``player_start``.
+    pub fn player_start(&self) -> TilePoint {
+        match (&self.state.board.player_spawn, &self.config.board_generator) {
+            (Some(spawn), _) => spawn.clone(),
+            (None, Some(generator)) => generator.player_start(),
+            (None, None) => self.config.player_start.clone(),
+        }
     }
-    fn new_game(&mut self) -> Box<dyn toybox_core::State> {
-        Box::new(State::try_new(self).expect("new_game should succeed."))
+    /// Point every enemy's start at its spawn marker on this level's board, or back at its config's start if the board has none; ``reset`` then puts it there.
+    fn apply_spawn_markers(&mut self) {
+        let player_start = self.player_start();
+        let board = &self.state.board;
+        let enemies = &self.config.enemies;
+        for (i, enemy) in self.state.enemies.iter_mut().enumerate() {
+            let start = board.enemy_spawns.get(i).cloned().flatten().or_else(|| {
+                enemies
+                    .get(i)
+                    .map(|ai| ai.start_position(&player_start, board))
+            });
+            if let Some(tile) = start {
+                enemy.ai.set_start(&tile);
+            }
+        }
     }
-    fn to_json(&self) -> String {
-        serde_json::to_string(self).expect("Amidar should be JSON serializable!")
+    /// Add or remove enemies to match ``enemies_per_level`` on a new level. Enemy ``i`` is always ``Amidar::enemies[i]``, so per-enemy counts and settings stay lined up.
+    fn bring_in_enemies_for_level(&mut self) {
+        let count = self.config.enemies_for_level(self.state.level);
+        if count == self.state.enemies.len() {
+            return;
+        }
+        let speed = self.config.enemy_starting_speed;
+        self.state.enemies = self.config.enemies[..count]
+            .iter()
+            .map(|ai| self.state.board.make_enemy(ai.clone(), speed))
+            .collect();
+        let player_start = self.player_start();
+        for enemy in &mut self.state.enemies {
+            enemy.reset(&player_start, &self.state.board);
+        }
     }
-    /// Sync with [ALE impl](https://github.com/mgbellemare/Arcade-Learning-Environment/blob/master/src/games/supported/Amidar.cpp#L80)
-    /// Note, leaving a call to sort in this impl to remind users that these vecs are ordered!
-    fn legal_action_set(&self) -> Vec<AleAction> {
-        let mut actions = vec![
-            AleAction::NOOP,
-            AleAction::FIRE,
-            AleAction::UP,
-            AleAction::RIGHT,
-            AleAction::LEFT,
-            AleAction::DOWN,
-            AleAction::UPFIRE,
-            AleAction::RIGHTFIRE,
-            AleAction::LEFTFIRE,
-            AleAction::DOWNFIRE,
-        ];
-        actions.sort();
-        actions
+    /// Log a draw from ``rand`` if it moved on since ``before``; see ``audit_rng``.
+    fn audit_rng(&mut self, before: Option<random::Gen>, mob: Option<usize>, purpose: RngPurpose) {
+        if before.is_some() && before.as_ref() != Some(&self.state.rand) {
+            self.state.rng_audit.push(RngDraw {
+                level: self.state.level,
+                frame: self.state.level_frames,
+                mob,
+                purpose,
+            });
+        }
     }
+    /// Move the player and score any painting it does.
+    fn move_player(&mut self, buttons: Input) {
+        let rand_before = if self.config.audit_rng {
+            Some(self.state.rand.clone())
+        } else {
+            None
+        };
+        if self.state.penalty_slow_timer > 0 {
+            self.state.penalty_slow_timer -= 1;
+            self.state.player.speed = (self.player_speed() / 2).max(1);
+        } else {
+            self.state.player.speed = self.player_speed();
+        }
+        if self.config.reverse_mid_step {
+            self.state.player.turn_around(buttons);
+        }
+        let buttons = self.corner(buttons);
+        let stepping_to = self.state.player.step.clone();
+        // Move the player and determine whether the board changes.
+        if let Some(score_change) = self.state.player.update(
+            buttons,
+            &mut self.state.board,
+            Surroundings {
+                player: None,
+                enemies: &[],
+                mode: EnemyMode::Pursue,
+                catchable: false,
+                frightened_policy: FrightenedPolicy::Unchanged,
+                elroy: false,
+            },
+            self.config.history_limit,
+            &mut self.state.rand,
+            None,
+        ) {
+            // Don't award score for the first, semi-painted segment on a default Amidar board, but do paint it.
+            let mut allow_score_change = true;
+            if self.config.default_board_bugs {
+                let (start, end) = score_change.junctions.unwrap();
+                // Locations of the first, semi-painted segment.
+                if start == 607 && end == 415 {
+                    allow_score_change = false;
+                }
+            }
+            let mut points = RewardBreakdown::default();
+            if allow_score_change {
+                // max 1 point for vertical, for some reason.
+                points.painting = score_change.horizontal + score_change.vertical.signum();
+                points.boxes = self.box_bonus() * score_change.num_boxes;
+            }
+            if score_change.penalties > 0 {
+                points.penalties = -self.config.penalty_tile_cost * score_change.penalties;
+                self.state.penalty_slow_timer = self.config.penalty_slow_frames;
+            }
+            self.state.score += points.total();
+            self.state.reward_breakdown.add(&points);
+            if let Some((from, to)) = score_change.junctions {
+                let board = &self.state.board;
+                let (from, to) = (board.lookup_position(from), board.lookup_position(to));
+                self.emit(GameEvent::SegmentPainted { from, to });
+            }
+            if score_change.num_boxes > 0 {
+                self.emit(GameEvent::BoxesFilled {
+                    count: score_change.num_boxes,
+                });
+            }
 
-    fn new_state_from_json(
-        &self,
-        json_str: &str,
-    ) -> Result<Box<dyn toybox_core::State>, serde_json::Error> {
-        let state: StateCore = serde_json::from_str(json_str)?;
-        Ok(Box::new(State {
-            config: self.clone(),
-            state,
-        }))
+            if score_change.triggers_chase {
+                self.start_chase(self.chase_time().to_frames(self.config.frames_per_second));
+            }
+            self.state.last_board_update = Some(score_change);
+        }
+        let player = &mut self.state.player;
+        // Without cornering, a queued turn is only good for the tile the player was heading to.
+        let reached_tile = stepping_to.is_none() || player.step != stepping_to;
+        if player.heading == player.queued_direction
+            || (reached_tile && self.config.cornering_tolerance.is_none())
+        {
+            player.queued_direction = None;
+        }
+        self.audit_rng(rand_before, None, RngPurpose::PlayerMove);
     }
-
-    fn from_json(
-        &self,
-        json_config: &str,
-    ) -> Result<Box<dyn toybox_core::Simulation>, serde_json::Error> {
-        let config: Amidar = serde_json::from_str(json_config)?;
-        Ok(Box::new(config))
-    }
-
-    fn schema_for_config(&self) -> String {
-        let schema = schema_for!(Amidar);
-        serde_json::to_string(&schema).expect("JSONSchema should be flawless.")
-    }
-    fn schema_for_state(&self) -> String {
-        let schema = schema_for!(StateCore);
-        serde_json::to_string(&schema).expect("JSONSchema should be flawless.")
-    }
-}
-
-impl toybox_core::State for State
-where
-    State: Clone,
-{
-    fn lives(&self) -> i32 {
-        self.state.lives
-    }
-    fn score(&self) -> i32 {
-        self.state.score
-    }
-    fn level(&self) -> i32 {
-        self.state.level
-    }
-    fn update_mut(&mut self, buttons: Input) {
-        let pre_update_score: i32 = self.score();
-        let history_limit = self.config.history_limit;
-
-        // Move the player and determine whether the board changes.
-        if let Some(score_change) = self.state.player.update(
-            buttons,
-            &mut self.state.board,
-            None,
-            history_limit,
-            &mut self.state.rand,
-        ) {
-            // Don't award score for the first, semi-painted segment on a default Amidar board, but do paint it.
-            let mut allow_score_change = true;
-            if self.config.default_board_bugs {
-                let (start, end) = score_change.junctions.unwrap();
-                // Locations of the first, semi-painted segment.
-                if start == 607 && end == 415 {
-                    allow_score_change = false;
+    /// With ``buffer_turns`` or ``cornering_tolerance``, the buttons the player moves on: the queued direction, if it can be taken where the player next picks one.
+    /// Otherwise, a cornering player keeps heading the same way, and a buffering one goes by the buttons held; a cornering player close enough to the tile where the queued turn opens up jumps onto it, so it turns this frame.
+    fn corner(&mut self, buttons: Input) -> Input {
+        let tolerance = self.config.cornering_tolerance;
+        if !self.config.buffer_turns && tolerance.is_none() {
+            return buttons;
+        }
+        let player = &mut self.state.player;
+        if let Some(dir) = Direction::from_input(buttons) {
+            player.queued_direction = Some(dir);
+        }
+        let board = &self.state.board;
+        // The player next picks a direction on the tile it's stepping to, or on its own tile if it's standing still.
+        let turn_at = player
+            .step
+            .clone()
+            .unwrap_or_else(|| player.position.to_tile());
+        let open = |dir: Direction| board.get_tile(&turn_at.step(dir)).walkable();
+        let dir = match player.queued_direction {
+            Some(queued) if open(queued) => {
+                let there = turn_at.to_world();
+                let short =
+                    (there.x - player.position.x).abs() + (there.y - player.position.y).abs();
+                let sideways =
+                    matches!(player.heading, Some(h) if h != queued && h != queued.opposite());
+                if sideways && tolerance.filter(|&t| short <= t).is_some() {
+                    player.position = there;
                 }
+                Some(queued)
             }
-            if allow_score_change {
-                self.state.score += score_change.horizontal;
-                // max 1 point for vertical, for some reason.
-                self.state.score += score_change.vertical.signum();
-                self.state.score += self.config.box_bonus * score_change.num_boxes;
-            }
-
-            if score_change.triggers_chase {
-                self.state.chase_timer = self.config.chase_time;
-            }
+            _ if tolerance.is_some() => player.heading.filter(|&heading| open(heading)),
+            _ => return buttons,
+        };
+        Input {
+            button1: buttons.button1,
+            button2: buttons.button2,
+            ..dir.map(Direction::to_input).unwrap_or_default()
         }
-
+    }
+    /// Start (or restart) chase mode for ``frames`` frames: enemies slow down, turn around, and can all be caught again.
+    fn start_chase(&mut self, frames: i32) {
+        self.state.chase_timer = frames;
+        self.state.chase_catches = 0;
+        self.release_caught_enemies();
+        self.apply_enemy_speed();
+        self.reverse_enemies();
+        self.emit(GameEvent::ChaseStarted);
+    }
+    /// Tidy up once ``chase_timer`` has reached 0.
+    fn end_chase(&mut self) {
+        // The chain ends with chase mode, not with the next one starting.
+        self.state.chase_catches = 0;
+        self.release_caught_enemies();
+        self.apply_enemy_speed();
+    }
+    /// Count down chase and jump timers, and start a jump if one was requested.
+    fn update_timers(&mut self, buttons: Input) {
         if self.state.chase_timer > 0 {
             self.state.chase_timer -= 1;
+            if self.state.chase_timer == 0 {
+                self.end_chase();
+            }
         } else if self.state.jump_timer > 0 {
             // only support jump when not chasing.
             self.state.jump_timer -= 1;
         } else if (buttons.button1 || buttons.button2) && self.state.jumps > 0 {
-            self.state.jump_timer = self.config.jump_time;
+            self.state.jump_timer = self
+                .config
+                .jump_time
+                .to_frames(self.config.frames_per_second);
             self.state.jumps -= 1;
         }
-
-        let mut dead = false;
-        let mut changes: Vec<EnemyPlayerState> = Vec::new();
-
-        // check-collisions after player move:
-        for (i, e) in self.state.enemies.iter().enumerate() {
-            let state = self.check_enemy_player_collision(e, i);
-            if state != EnemyPlayerState::Miss {
-                changes.push(state);
-            }
+    }
+    /// Make every enemy catchable again, for a new chase mode or none.
+    fn release_caught_enemies(&mut self) {
+        for enemy in self.state.enemies.iter_mut() {
+            enemy.caught = false;
+            enemy.chase_immune = false;
         }
-
-        // move enemies:
-        for e in self.state.enemies.iter_mut() {
+    }
+    /// Move every enemy one step; ``player`` is what the enemy AIs get to see.
+    fn move_enemies(&mut self, player: Mob) {
+        let debug_ai = self.config.debug_ai;
+        self.state.ai_debug.clear();
+        let enemy_tiles = self.enemy_tiles();
+        let mode = self.enemy_mode();
+        let chasing = self.state.chase_timer > 0;
+        let elroy = self.state.elroy_stage > 0;
+        let homes: Vec<TilePoint> = (0..self.state.enemies.len())
+            .map(|i| self.respawn_tile(i))
+            .collect();
+        let player_start = self.player_start();
+        for (i, e) in self.state.enemies.iter_mut().enumerate() {
+            if e.eaten {
+                let speed = self.config.eyes_speed.unwrap_or(e.speed);
+                if e.return_home(&homes[i], speed, &self.state.board) {
+                    e.reset(&player_start, &self.state.board);
+                    e.position = homes[i].to_world();
+                    e.immobilized_timer = self.config.immobilized_time;
+                }
+                if debug_ai {
+                    self.state.ai_debug.push(None);
+                }
+                continue;
+            }
+            if e.immobilized_timer > 0 {
+                e.immobilized_timer -= 1;
+            }
+            if !e.released || e.immobilized_timer > 0 {
+                if debug_ai {
+                    self.state.ai_debug.push(None);
+                }
+                continue;
+            }
+            let mut decision = None;
+            let rand_before = if self.config.audit_rng {
+                Some(self.state.rand.clone())
+            } else {
+                None
+            };
             e.update(
                 Input::default(),
                 &mut self.state.board,
-                Some(self.state.player.clone()),
-                history_limit,
+                Surroundings {
+                    player: Some(player.clone()),
+                    enemies: &enemy_tiles,
+                    mode,
+                    catchable: e.catchable(chasing),
+                    frightened_policy: self.config.frightened_policy,
+                    elroy: elroy && i == 0,
+                },
+                self.config.history_limit,
                 &mut self.state.rand,
+                if debug_ai { Some(&mut decision) } else { None },
             );
+            if debug_ai {
+                self.state.ai_debug.push(decision);
+            }
+            if rand_before.is_some() && rand_before.as_ref() != Some(&self.state.rand) {
+                self.state.rng_audit.push(RngDraw {
+                    level: self.state.level,
+                    frame: self.state.level_frames,
+                    mob: Some(i),
+                    purpose: RngPurpose::EnemyMove,
+                });
+            }
+        }
+        self.unpaint_behind(&enemy_tiles);
+    }
+    /// Let the ``unpainter`` undo painting on the tiles it has just entered.
+    fn unpaint_behind(&mut self, previous_tiles: &[TilePoint]) {
+        let unpainter = match &self.config.unpainter {
+            Some(u) => u.clone(),
+            None => return,
+        };
+        let tile = match self.state.enemies.get(unpainter.enemy) {
+            Some(e) if !e.eaten => e.position.to_tile(),
+            _ => return,
+        };
+        if previous_tiles.get(unpainter.enemy) == Some(&tile) {
+            return;
+        }
+        self.state.unpainter_tiles += 1;
+        if self.state.unpainter_tiles < unpainter.every_tiles.max(1) {
+            return;
+        }
+        self.state.unpainter_tiles = 0;
+        if self.state.board.unpaint(&tile) {
+            self.emit(GameEvent::TileUnpainted {
+                enemy: unpainter.enemy,
+                tile,
+            });
+        }
+    }
+    /// Give the one extra life once the score reaches ``extra_life_score``.
+    fn check_extra_life(&mut self) {
+        match self.config.extra_life_score {
+            Some(threshold) if !self.state.extra_life_awarded && self.state.score >= threshold => {
+                self.state.extra_life_awarded = true;
+                self.state.lives += 1;
+                self.state.lives_gained += 1;
+            }
+            _ => {}
+        }
+    }
+    /// Pay out any bonus gate the player is standing on for the first time this level.
+    fn check_bonus_gates(&mut self) {
+        let tile = self.state.player.position.to_tile();
+        for i in 0..self.config.bonus_gates.len() {
+            let gate = &self.config.bonus_gates[i];
+            if self.state.gates_reached[i] || gate.tile != tile {
+                continue;
+            }
+            let bonus = gate.bonus_at(self.state.level_frames);
+            self.state.gates_reached[i] = true;
+            self.state.score += bonus;
+            self.state.reward_breakdown.bonuses += bonus;
+            self.emit(GameEvent::GateReached { gate: i, bonus });
+        }
+    }
+    /// Hold or release each enemy according to ``enemy_releases``.
+    fn update_enemy_releases(&mut self) {
+        if self.config.enemy_releases.is_empty() {
+            return;
+        }
+        let uses_tiles = self
+            .config
+            .enemy_releases
+            .iter()
+            .any(|r| r.after_tiles_painted.is_some());
+        let painted = if uses_tiles {
+            self.tiles_painted_this_level()
+        } else {
+            0
+        };
+        let frames = self.state.level_frames;
+        let chasing = self.state.chase_timer > 0;
+        for (e, release) in self
+            .state
+            .enemies
+            .iter_mut()
+            .zip(self.config.enemy_releases.iter())
+        {
+            let by_frames = release.after_frames.map(|f| frames >= f);
+            let by_tiles = release.after_tiles_painted.map(|t| painted >= t);
+            let released = match (by_frames, by_tiles) {
+                (None, None) => true,
+                (a, b) => a.unwrap_or(false) || b.unwrap_or(false),
+            };
+            if released && !e.released && chasing {
+                // Coming out in the middle of chase mode: not one of the enemies it frightened.
+                e.chase_immune = true;
+            }
+            e.released = released;
+        }
+    }
+    /// How many tiles has the player painted since the level started?
+    fn tiles_painted_this_level(&self) -> u32 {
+        self.state
+            .level_tiles_to_paint
+            .saturating_sub(self.state.board.tiles_to_paint())
+    }
+    /// How much is the next enemy caught in this chase mode worth? See ``chase_bonus_factor`` and ``chase_bonus_max``.
+    pub fn chase_bonus(&self) -> i32 {
+        let mut bonus = self.config.chase_score_bonus;
+        for _ in 0..self.state.chase_catches {
+            bonus = bonus.saturating_mul(self.config.chase_bonus_factor);
+        }
+        match self.config.chase_bonus_max {
+            Some(max) => bonus.min(max),
+            None => bonus,
+        }
+    }
+    /// A hash of this frame's draw commands: equal digests mean the frames look the same. See ``graphics::digest``.
+    pub fn draw_digest(&self) -> u64 {
+        graphics::digest(&toybox_core::State::draw(self))
+    }
+    /// What are the targeting enemies doing right now? See ``mode_schedule``.
+    pub fn enemy_mode(&self) -> EnemyMode {
+        self.config
+            .mode_schedule
+            .get(self.state.mode_index)
+            .map_or(EnemyMode::Pursue, |(mode, _)| *mode)
+    }
+    /// Step through ``mode_schedule``; the last entry never ends.
+    fn update_enemy_mode(&mut self) {
+        let schedule = &self.config.mode_schedule;
+        if self.state.mode_index + 1 >= schedule.len() {
+            return;
+        }
+        self.state.mode_timer += 1;
+        if self.state.mode_timer >= schedule[self.state.mode_index].1 {
+            self.state.mode_index += 1;
+            self.state.mode_timer = 0;
+            self.reverse_enemies();
+        }
+    }
+    /// With ``reverse_on_mode_change``, turn every moving enemy around.
+    fn reverse_enemies(&mut self) {
+        if !self.config.reverse_on_mode_change {
+            return;
+        }
+        for enemy in self.state.enemies.iter_mut().filter(|e| e.released) {
+            enemy.reverse();
+        }
+    }
+    /// Is the episode over? Every game ends when the lives run out; with ``episodic_life``, so does every lost life.
+    pub fn is_terminal(&self) -> bool {
+        self.state.game_over || (self.config.episodic_life && self.state.life_lost)
+    }
+    /// Play exactly one frame of ``buttons``; ``update_mut`` calls this ``frame_skip`` times.
+    fn update_frame(&mut self, buttons: Input) {
+        let pre_update_score: i32 = self.state.score;
+        self.state.events.clear();
+        self.state.reward_breakdown = RewardBreakdown::default();
+        self.state.last_board_update = None;
+        for enemy in self.state.enemies.iter_mut() {
+            enemy.caught_this_frame = false;
+        }
+        self.state.life_lost = false;
+        if self.state.game_over {
+            self.state.reward = 0.0;
+            return;
+        }
+        self.state.total_frames += 1;
+        if let Some(freeze) = self.state.catch_freeze.as_mut() {
+            // Nothing moves while the bonus is on screen.
+            freeze.frames_left -= 1;
+            if freeze.frames_left <= 0 {
+                self.state.catch_freeze = None;
+            }
+            self.state.reward = 0.0;
+            return;
+        }
+        if self.state.phase != GamePhase::Playing {
+            // Nothing moves and inputs are ignored until the pause runs out.
+            self.state.phase_timer -= 1;
+            if self.state.phase_timer <= 0 {
+                match self.state.phase {
+                    GamePhase::Dying => {
+                        self.reset();
+                        self.enter_phase(GamePhase::Ready, self.config.respawn_pause_frames);
+                    }
+                    GamePhase::LevelComplete => {
+                        self.next_level();
+                        self.enter_phase(GamePhase::Ready, self.config.ready_frames);
+                    }
+                    _ => self.enter_phase(GamePhase::Playing, 0),
+                }
+            }
+            self.state.reward = 0.0;
+            return;
+        }
+        self.state.level_frames += 1;
+        for popup in self.state.score_popups.iter_mut() {
+            popup.frames_left -= 1;
+        }
+        self.state.score_popups.retain(|p| p.frames_left > 0);
+        if self.state.grace_timer > 0 {
+            self.state.grace_timer -= 1;
         }
+        self.update_enemy_mode();
+        self.update_enemy_releases();
+        let start_tile = self.state.player.position.to_tile();
 
-        // check-collisions again (so we can't run through enemies):
-        for (i, e) in self.state.enemies.iter().enumerate() {
-            let state = self.check_enemy_player_collision(e, i);
-            if state != EnemyPlayerState::Miss {
-                changes.push(state);
+        let recheck = self.config.collision_model != CollisionModel::PassThrough;
+        let previous_tiles = self.enemy_tiles();
+        // After everyone has moved, an enemy's last tile counts too: always with Strict, and otherwise only if it swapped tiles with the player.
+        let previous = if recheck {
+            Some(previous_tiles.as_slice())
+        } else {
+            None
+        };
+        let swept_from = if self.config.collision_model == CollisionModel::Strict {
+            None
+        } else {
+            Some(&start_tile)
+        };
+        // What happened to each enemy (by index) this frame; the first collision found for an enemy sticks.
+        let mut collisions = vec![EnemyPlayerState::Miss; self.state.enemies.len()];
+
+        match self.config.update_order {
+            UpdateOrder::PlayerFirst => {
+                self.move_player(buttons);
+                self.update_timers(buttons);
+                // check-collisions after player move:
+                self.find_collisions(None, None, &mut collisions);
+
+                self.move_enemies(self.state.player.clone());
+
+                // check-collisions again (so we can't run through enemies):
+                if recheck {
+                    self.find_collisions(previous, swept_from, &mut collisions);
+                }
+            }
+            UpdateOrder::EnemiesFirst => {
+                self.move_enemies(self.state.player.clone());
+                self.find_collisions(previous, swept_from, &mut collisions);
+
+                self.move_player(buttons);
+                self.update_timers(buttons);
+                if recheck {
+                    self.find_collisions(previous, swept_from, &mut collisions);
+                }
+            }
+            UpdateOrder::Simultaneous => {
+                let previous_player = self.state.player.clone();
+                self.move_player(buttons);
+                self.update_timers(buttons);
+                self.move_enemies(previous_player);
+                self.find_collisions(previous, swept_from, &mut collisions);
             }
         }
 
-        // Process EnemyPlayerState that were interesting!
-        for change in changes {
+        self.check_bonus_gates();
+        self.update_elroy();
+        let mut dead = false;
+        let mut catch_points = 0;
+
+        // Resolve collisions in enemy index order: the lowest-numbered enemy touching the player gets the kill.
+        for change in collisions {
             match change {
                 EnemyPlayerState::Miss => {
-                    // This was filtered out.
+                    // This enemy didn't touch the player.
                 }
-                EnemyPlayerState::PlayerDeath => {
+                EnemyPlayerState::PlayerDeath(eid) => {
+                    self.state.kills_by_enemy[eid] += 1;
+                    self.emit(GameEvent::PlayerKilled { enemy: eid });
                     dead = true;
                     break;
                 }
                 EnemyPlayerState::EnemyCatch(eid) => {
                     if !self.state.enemies[eid].caught {
-                        self.state.score += self.config.chase_score_bonus;
+                        let bonus = self.chase_bonus();
+                        self.state.score += bonus;
+                        self.state.reward_breakdown.enemies += bonus;
+                        self.state.chase_catches += 1;
                         self.state.enemies[eid].caught = true;
+                        self.state.enemies[eid].caught_this_frame = true;
+                        self.state.catches_by_enemy[eid] += 1;
+                        self.emit(GameEvent::EnemyCaught { enemy: eid });
+                        catch_points += bonus;
+                        self.add_score_popup(self.state.enemies[eid].position.clone(), bonus);
+                        if self.config.catch_freeze_frames > 0 {
+                            self.state.catch_freeze = Some(CatchFreeze {
+                                frames_left: self.config.catch_freeze_frames,
+                                position: self.state.enemies[eid].position.clone(),
+                                bonus,
+                            });
+                        }
+                        if self.config.immobilized_time > 0 {
+                            let home = self.respawn_tile(eid);
+                            let player_start = self.player_start();
+                            let enemy = &mut self.state.enemies[eid];
+                            if self.config.eyes_speed.is_some() {
+                                // ``move_enemies`` takes it the rest of the way.
+                                enemy.eaten = true;
+                                enemy.step = None;
+                            } else {
+                                enemy.reset(&player_start, &self.state.board);
+                                enemy.position = home.to_world();
+                                enemy.immobilized_timer = self.config.immobilized_time;
+                            }
+                            // It comes back as if after a lost life: not caught, but not catchable again until the next chase mode.
+                            enemy.caught = false;
+                            enemy.chase_immune = true;
+                        }
                     }
                 }
             }
         }
 
+        self.check_extra_life();
+
         // If dead, reset. If alive, check to see if we have advanced to the next level.
         if dead {
             self.state.jumps = self.config.start_jumps;
             self.state.lives -= 1;
-            self.state.score = pre_update_score;
-            self.reset();
+            self.state.life_lost = true;
+            self.set_game_over(self.state.lives < 0);
+            if self.config.rollback_score_on_death {
+                self.state.reward_breakdown = RewardBreakdown {
+                    death: pre_update_score - self.state.score,
+                    ..self.state.reward_breakdown
+                };
+                self.state.score = pre_update_score;
+            }
+            self.state.level_deaths += 1;
+            self.state.catch_freeze = None;
+            self.state.grace_timer = self.config.respawn_grace_frames;
+            if self.state.game_over {
+                self.reset();
+            } else if self.config.death_pause_frames > 0 {
+                self.enter_phase(GamePhase::Dying, self.config.death_pause_frames);
+            } else {
+                self.reset();
+                self.enter_phase(GamePhase::Ready, self.config.respawn_pause_frames);
+            }
         } else {
             if self.state.board.board_complete() {
-                self.reset();
-                // Increment the level
-                self.state.level += 1;
-                // If we triggered the chase counter immediately before
-                // advancing, it will still be on and will mess up the sprites. Reset to 0.
-                self.state.chase_timer = 0;
-                // Time to paint again!
-                self.state.board = Board::fast_new();
-                // If you successfully complete a level, you can get a life back (up the maximum)
-                if self.lives() < self.config.start_lives {
-                    self.state.lives += 1;
-                }
-                if self.state.level > 2 {
-                    // Starting at level 3, there are six enemies.
-                    // We haven't observed an agent that can get to level 3 and can't find any description
-                    // of what level 3 looks like, so we are leaving this blank for now.
-                }
-                // Increase enemy speed.
-                // Make pretty later
-                let new_speed = {
-                    if self.state.level < 3 {
-                        self.config.enemy_starting_speed
-                    } else if self.state.level < 5 {
-                        self.config.enemy_starting_speed + 2
-                    } else {
-                        self.config.enemy_starting_speed + 4
-                    }
-                };
-                for e in &mut self.state.enemies {
-                    e.change_speed(new_speed);
+                self.state.score += self.config.level_clear_bonus;
+                self.state.reward_breakdown.bonuses += self.config.level_clear_bonus;
+                self.emit(GameEvent::LevelCleared {
+                    level: self.state.level,
+                    frames: self.state.level_frames,
+                    total_frames: self.state.total_frames,
+                    deaths: self.state.level_deaths,
+                });
+                if self.config.level_complete_frames > 0 {
+                    self.enter_phase(GamePhase::LevelComplete, self.config.level_complete_frames);
+                } else {
+                    self.next_level();
+                    self.enter_phase(GamePhase::Ready, self.config.ready_frames);
                 }
             }
         }
-    }
 
-    fn draw(&self) -> Vec<Drawable> {
-        let mut output = Vec::new();
-        output.push(Drawable::Clear(self.config.bg_color));
-        if self.state.lives < 0 {
-            return output;
+        if !dead {
+            let points = self.state.score - pre_update_score - catch_points;
+            self.add_score_popup(self.state.player.position.clone(), points);
         }
 
-        let (tile_w, tile_h) = screen::TILE_SIZE;
-        let (offset_x, offset_y) = screen::BOARD_OFFSET;
-
-        for (ty, row) in self.state.board.tiles.iter().enumerate() {
-            let ty = ty as i32;
-            for (tx, tile) in row.iter().enumerate() {
-                let tx = tx as i32;
-
-                // Use the level-1 sprites for odd levels less than the sixth level.
-                // Use the level-2 sprites for even levels and those greater than the sixth level.
-                // We will probably want to put some of this in the config later.
-                let ghosts = self.state.level % 2 == 1 && self.state.level < 6;
-
-                if self.config.render_images {
-                    let tile_sprite: &FixedSpriteData = match tile {
-                        &Tile::Painted => {
-                            if ghosts {
-                                &images::BLOCK_TILE_PAINTED_L1
-                            } else {
-                                &images::BLOCK_TILE_PAINTED_L2
-                            }
-                        }
-                        &Tile::Unpainted | &Tile::ChaseMarker => {
-                            if ghosts {
-                                &images::BLOCK_TILE_UNPAINTED_L1
-                            } else {
-                                &images::BLOCK_TILE_UNPAINTED_L2
-                            }
-                        }
-                        &Tile::Empty => continue,
-                    };
-                    output.push(Drawable::sprite(
-                        offset_x + tx * tile_w,
-                        offset_y + ty * tile_h,
-                        tile_sprite.clone(),
-                    ));
-                } else {
-                    let tile_color = match tile {
-                        &Tile::Painted => self.config.painted_color,
-                        &Tile::Unpainted | &Tile::ChaseMarker => self.config.unpainted_color,
-                        &Tile::Empty => continue,
-                    };
-                    output.push(Drawable::rect(
-                        tile_color,
-                        offset_x + tx * tile_w,
-                        offset_y + ty * tile_h,
-                        tile_w,
-                        tile_h,
-                    ));
-                }
+        self.check_stall(&start_tile);
+        let mut exploration_reward = 0.0;
+        if self.config.track_visits || self.config.exploration_bonus != ExplorationBonus::Off {
+            let tile = self.state.player.position.to_tile();
+            if tile != start_tile && self.record_visit(&tile) == 1 {
+                exploration_reward = self
+                    .config
+                    .exploration_bonus
+                    .first_visit_reward(&self.state.board, &tile);
             }
         }
-
-        for inner_box in self.state.board.boxes.iter().filter(|b| b.painted) {
-            if self.config.render_images {
-                let top_left_in = inner_box.top_left.translate(1, 1);
-                let x1 = top_left_in.tx;
-                let x2 = inner_box.bottom_right.tx;
-                let y1 = top_left_in.ty;
-                let y2 = inner_box.bottom_right.ty;
-
-                // generate all boxes inside:
-                for x in x1..x2 {
-                    for y in y1..y2 {
-                        let pt = TilePoint::new(x, y).to_world().to_screen();
-                        output.push(Drawable::sprite(
-                            pt.sx + offset_x,
-                            pt.sy + offset_y,
-                            images::PAINTED_BOX_BAR.clone(),
-                        ));
-                    }
-                }
-            } else {
-                let origin = inner_box.top_left.translate(1, 1).to_world().to_screen();
-                let dest = inner_box.bottom_right.to_world().to_screen();
-                let w = dest.sx - origin.sx;
-                let h = dest.sy - origin.sy;
-                output.push(Drawable::rect(
-                    self.config.inner_painted_color,
-                    offset_x + origin.sx,
-                    offset_y + origin.sy,
-                    w,
-                    h,
-                ));
-            }
+        self.state.reward = self
+            .config
+            .shape_reward(self.state.score - pre_update_score)
+            + exploration_reward;
+    }
+    /// Move to ``phase`` for ``frames`` frames; no frames skips straight to ``Playing``.
+    fn enter_phase(&mut self, phase: GamePhase, frames: i32) {
+        if frames > 0 {
+            self.state.phase = phase;
+            self.state.phase_timer = frames;
+        } else {
+            self.state.phase = GamePhase::Playing;
+            self.state.phase_timer = 0;
         }
-
-        let (player_x, player_y) = self.state.player.position.to_screen().pixels();
-        let (player_w, player_h) = screen::PLAYER_SIZE;
-        let player_sprite = match self.state.level % 2 {
-            1 => images::PLAYER_L1.clone(),
-            0 => images::PLAYER_L2.clone(),
-            _ => unreachable!(),
+    }
+    /// End the game (or, for an intervention, take that back).
+    fn set_game_over(&mut self, over: bool) {
+        self.state.game_over = over;
+        if over {
+            self.state.phase = GamePhase::GameOver;
+            self.state.phase_timer = 0;
+        } else if self.state.phase == GamePhase::GameOver {
+            self.state.phase = GamePhase::Playing;
+        }
+    }
+    /// Set up the level after a cleared one: a fresh board, and enemies, lives and difficulty to match.
+    fn next_level(&mut self) {
+        let stats = LevelStats {
+            level: self.state.level,
+            frames: self.state.level_frames,
+            deaths: self.state.level_deaths,
         };
-        if self.config.render_images {
-            output.push(Drawable::sprite(
-                offset_x + player_x - 1,
-                offset_y + player_y - 1,
-                player_sprite,
-            ))
-        } else {
-            output.push(Drawable::rect(
-                self.config.player_color,
-                offset_x + player_x - 1,
-                offset_y + player_y - 1,
-                player_w,
-                player_h,
-            ));
+        self.state.level_frames = 0;
+        for reached in self.state.gates_reached.iter_mut() {
+            *reached = false;
         }
-
-        for enemy in &self.state.enemies {
-            let (x, y) = enemy.position.to_screen().pixels();
-            let (w, h) = screen::ENEMY_SIZE;
-
-            if self.config.render_images {
-                output.push(Drawable::sprite(
-                    offset_x + x - 1,
-                    offset_y + y - 1,
-                    if self.state.chase_timer > 0 {
-                        if enemy.caught {
-                            match self.state.level % 2 {
-                                1 => images::ENEMY_CAUGHT_L1.clone(),
-                                0 => images::ENEMY_CAUGHT_L2.clone(),
-                                _ => unreachable!(),
-                            }
-                        } else {
-                            match self.state.level % 2 {
-                                1 => images::ENEMY_CHASE_L1.clone(),
-                                0 => images::ENEMY_CHASE_L2.clone(),
-                                _ => unreachable!(),
-                            }
-                        }
-                    } else if self.state.jump_timer > 0 {
-                        match self.state.level % 2 {
-                            1 => images::ENEMY_JUMP_L1.clone(),
-                            0 => images::ENEMY_JUMP_L2.clone(),
-                            _ => unreachable!(),
-                        }
-                    } else {
-                        match self.state.level % 2 {
-                            1 => images::ENEMY_L1.clone(),
-                            0 => images::ENEMY_L2.clone(),
-                            _ => unreachable!(),
-                        }
-                    },
-                ))
-            } else {
-                output.push(Drawable::rect(
-                    self.config.enemy_color,
-                    offset_x + x - 1,
-                    offset_y + y - 1,
-                    w,
-                    h,
-                ));
-            }
+        self.state.level_deaths = 0;
+        // Increment the level
+        self.state.level += 1;
+        // If we triggered the chase counter immediately before
+        // advancing, it will still be on and will mess up the sprites. Reset to 0.
+        self.state.chase_timer = 0;
+        self.state.elroy_stage = 0;
+        // Time to paint again!
+        // Every layout parsed when the game started; should a generated one not, play on
+        // with the default board rather than stop the game.
+        self.state.board = Board::try_new(&self.config.board_for_level(self.state.level))
+            .unwrap_or_else(|_| Board::fast_new());
+        self.state.level_tiles_to_paint = self.state.board.tiles_to_paint();
+        self.bring_in_enemies_for_level();
+        self.apply_spawn_markers();
+        // Everyone restarts on the new board.
+        self.reset();
+        // If you successfully complete a level, you can get a life back (up the maximum)
+        if self.config.refill_lives_on_level_clear && self.state.lives < self.config.start_lives {
+            self.state.lives += 1;
+            self.state.lives_gained += 1;
         }
-
-        output.extend(draw_score(
-            self.state.score,
-            screen::SCORE_X_POS,
-            screen::SCORE_Y_POS + 1,
-        ));
-        for i in 0..self.state.lives {
-            output.push(Drawable::rect(
-                self.config.player_color,
-                screen::LIVES_X_POS - i * screen::LIVES_X_STEP,
-                screen::LIVES_Y_POS,
-                1,
-                DIGIT_HEIGHT + 1,
-            ))
+        if self.state.level > 2 {
+            // Starting at level 3, there are six enemies.
+            // We haven't observed an agent that can get to level 3 and can't find any description
+            // of what level 3 looks like, so we are leaving this blank for now.
+        }
+        // Increase enemy speed.
+        self.apply_enemy_speed();
+        if let Some(mut controller) = self.config.adaptive_difficulty.clone() {
+            self.adjust_difficulty(&mut controller, &stats);
         }
-
-        output
     }
-
-    fn to_json(&self) -> String {
-        serde_json::to_string(&self.state).expect("Should be no JSON Serialization Errors.")
+    /// Show ``points`` at ``position`` for ``score_popup_frames``, if they are on and there are points to show.
+    fn add_score_popup(&mut self, position: WorldPoint, points: i32) {
+        if self.config.score_popup_frames > 0 && points > 0 {
+            self.state.score_popups.push(ScorePopup {
+                position,
+                points,
+                frames_left: self.config.score_popup_frames,
+            });
+        }
     }
-
-    fn query_json(&self, query: &str, args: &serde_json::Value) -> Result<String, QueryError> {
+    /// With ``latch_directions``, keep pressing the last direction pressed until another one is.
+    fn latch_direction(&mut self, buttons: Input) -> Input {
+        if !self.config.latch_directions {
+            return buttons;
+        }
+        if let Some(dir) = Direction::from_input(buttons) {
+            self.state.latched_direction = Some(dir);
+            return buttons;
+        }
+        match self.state.latched_direction {
+            Some(dir) => Input {
+                button1: buttons.button1,
+                button2: buttons.button2,
+                ..dir.to_input()
+            },
+            None => buttons,
+        }
+    }
+    /// The buttons actually pressed this frame: with ``action_repeat_probability``, the last frame's buttons again.
+    fn sticky_buttons(&mut self, buttons: Input) -> Input {
+        let pressed = if self.config.action_repeat_probability > 0.0 {
+            let rand_before = if self.config.audit_rng {
+                Some(self.state.rand.clone())
+            } else {
+                None
+            };
+            let repeat = self.state.rand.gen::<f32>() < self.config.action_repeat_probability;
+            self.audit_rng(rand_before, None, RngPurpose::StickyAction);
+            if repeat {
+                self.state.last_buttons
+            } else {
+                buttons
+            }
+        } else {
+            buttons
+        };
+        self.state.last_buttons = pressed;
+        pressed
+    }
+    /// The current frame as a compact, versioned binary savestate: much quicker to make and load than ``to_json`` when snapshotting many states.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = SAVESTATE_MAGIC.to_vec();
+        bytes.extend_from_slice(&SAVESTATE_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, &self.state)
+            .expect("Should be no bincode Serialization Errors.");
+        bytes
+    }
+    /// Pick up a game of ``config`` from a savestate made by ``to_bytes``.
+    pub fn from_bytes(config: &Amidar, bytes: &[u8]) -> Result<State, String> {
+        if bytes.len() < 6 || &bytes[..4] != SAVESTATE_MAGIC {
+            return Err("Not an Amidar savestate.".to_owned());
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != SAVESTATE_VERSION {
+            return Err(format!(
+                "Savestate version {} can't be loaded by version {}.",
+                version, SAVESTATE_VERSION
+            ));
+        }
+        let state: StateCore = bincode::deserialize(&bytes[6..]).map_err(|e| e.to_string())?;
+        Ok(State {
+            config: config.clone(),
+            state,
+        })
+    }
+    /// How many tiles still need painting before the level is cleared?
+    pub fn num_tiles_unpainted(&self) -> usize {
+        self.state.board.tiles_to_paint() as usize
+    }
+    /// The names of ``board_tensor``'s channels, in order: these, then ``enemy_0``, ``enemy_1``, ... one per enemy.
+    pub fn board_tensor_channels(&self) -> Vec<String> {
+        let mut names: Vec<String> = BOARD_TENSOR_CHANNELS
+            .iter()
+            .map(|&name| name.to_owned())
+            .collect();
+        names.extend((0..self.state.enemies.len()).map(|i| format!("enemy_{}", i)));
+        names
+    }
+    /// The last ``observation_stack`` board tensors, oldest first; empty if the config keeps none.
+    pub fn stacked_board_tensor(&self) -> Vec<Vec<Vec<Vec<u8>>>> {
+        self.state.observations.iter().cloned().collect()
+    }
+    /// Add the current board tensor to the stack, forgetting the oldest one if it's full.
+    fn push_observation(&mut self) {
+        let size = self.config.observation_stack;
+        if size == 0 {
+            return;
+        }
+        let tensor = self.board_tensor();
+        self.state.observations.push_back(tensor);
+        while self.state.observations.len() > size {
+            self.state.observations.pop_front();
+        }
+    }
+    /// The game as a dense rows × columns × channels grid of 0s and 1s, for learners that would rather skip pixels; see ``board_tensor_channels``.
+    pub fn board_tensor(&self) -> Vec<Vec<Vec<u8>>> {
+        let board = &self.state.board;
+        let channels = BOARD_TENSOR_CHANNELS.len() + self.state.enemies.len();
+        let mut tensor: Vec<Vec<Vec<u8>>> = board
+            .rows()
+            .map(|row| {
+                row.iter()
+                    .map(|tile| {
+                        let mut cell = vec![0; channels];
+                        cell[0] = tile.walkable() as u8;
+                        cell[1] = (tile.walkable() && tile.needs_paint()) as u8;
+                        cell[2] = (*tile == Tile::Painted) as u8;
+                        cell[3] = (*tile == Tile::ChaseMarker) as u8;
+                        cell[4] = (*tile == Tile::Penalty) as u8;
+                        cell
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut mark = |tile: TilePoint, channel: usize| {
+            if let Some(cell) = tensor
+                .get_mut(tile.ty as usize)
+                .and_then(|row| row.get_mut(tile.tx as usize))
+            {
+                cell[channel] = 1;
+            }
+        };
+        mark(self.state.player.position.to_tile(), 5);
+        for (i, enemy) in self.state.enemies.iter().enumerate() {
+            let tile = enemy.position.to_tile();
+            mark(tile.clone(), 6);
+            if enemy.catchable(self.state.chase_timer > 0) {
+                mark(tile.clone(), 7);
+            }
+            mark(tile, BOARD_TENSOR_CHANNELS.len() + i);
+        }
+        tensor
+    }
+    /// How fast should enemies move right now, given the level and whether we're in chase mode?
+    pub fn enemy_speed(&self) -> i32 {
+        self.enemy_speed_when(self.state.chase_timer > 0)
+    }
+    /// How fast do enemies move on this level, in or out of chase mode?
+    fn enemy_speed_when(&self, chasing: bool) -> i32 {
+        let params = self.level_params();
+        let tabled = if chasing {
+            params.and_then(|p| p.chase_enemy_speed)
+        } else {
+            params.and_then(|p| p.enemy_speed)
+        };
+        if let Some(speed) = tabled {
+            return speed;
+        }
+        let base = if chasing {
+            self.config.chase_enemy_speed
+        } else {
+            self.config.enemy_starting_speed
+        };
+        // Enemies get faster on later levels.
+        if self.state.level < 3 {
+            base
+        } else if self.state.level < 5 {
+            base + 2
+        } else {
+            base + 4
+        }
+    }
+    /// This level's entry in ``difficulty_table``, if any, unless a ``DifficultyController`` has replaced it.
+    fn level_params(&self) -> Option<&LevelParams> {
+        self.state
+            .level_override
+            .as_ref()
+            .or_else(|| self.config.level_params(self.state.level))
+    }
+    /// Everything ``LevelParams`` covers, as this level plays it; chase mode's length is in frames.
+    pub fn level_params_now(&self) -> LevelParams {
+        let chase_frames = self.chase_time().to_frames(self.config.frames_per_second);
+        LevelParams {
+            enemy_speed: Some(self.enemy_speed_when(false)),
+            chase_enemy_speed: Some(self.enemy_speed_when(true)),
+            player_speed: Some(self.player_speed()),
+            chase_time: Some(GameTime::Frames(chase_frames)),
+            box_bonus: Some(self.box_bonus()),
+        }
+    }
+    /// Let ``controller`` change what this level plays like, given how the last one went. Called on every new level with ``adaptive_difficulty``, and free to call with any other controller, e.g. on a ``LevelCleared`` event.
+    pub fn adjust_difficulty(
+        &mut self,
+        controller: &mut dyn DifficultyController,
+        stats: &LevelStats,
+    ) {
+        let params = controller.next_level(stats, self.level_params_now());
+        self.state.level_override = Some(params);
+        self.apply_enemy_speed();
+    }
+    /// How fast does the player move on this level (before any penalty slow-down)?
+    pub fn player_speed(&self) -> i32 {
+        self.level_params()
+            .and_then(|p| p.player_speed)
+            .unwrap_or(self.config.player_speed)
+    }
+    /// How long does chase mode last on this level?
+    pub fn chase_time(&self) -> GameTime {
+        self.level_params()
+            .and_then(|p| p.chase_time)
+            .unwrap_or(self.config.chase_time)
+    }
+    /// How many points is filling a box worth on this level?
+    pub fn box_bonus(&self) -> i32 {
+        self.level_params()
+            .and_then(|p| p.box_bonus)
+            .unwrap_or(self.config.box_bonus)
+    }
+    /// How fast does enemy ``enemy`` (by position in ``enemies``) move right now? ``enemy_speed`` plus its entry in ``enemy_speed_offsets``, but never slower than 1.
+    pub fn enemy_speed_of(&self, enemy: usize) -> i32 {
+        let offset = self
+            .config
+            .enemy_speed_offsets
+            .get(enemy)
+            .cloned()
+            .unwrap_or(0);
+        let elroy = match self.state.elroy_stage {
+            _ if enemy != 0 || self.state.chase_timer > 0 => 0,
+            1 => self.config.elroy_part1_speed,
+            2 => self.config.elroy_part2_speed,
+            _ => 0,
+        };
+        (self.enemy_speed() + offset + elroy).max(1)
+    }
+    /// Move the first enemy through the "Cruise Elroy" stages as the board fills in; see ``elroy_part1_tiles``.
+    fn update_elroy(&mut self) {
+        let left = self.state.board.tiles_to_paint();
+        let stage = if matches!(self.config.elroy_part2_tiles, Some(tiles) if left <= tiles) {
+            2
+        } else if matches!(self.config.elroy_part1_tiles, Some(tiles) if left <= tiles) {
+            1
+        } else {
+            0
+        };
+        if stage != self.state.elroy_stage {
+            self.state.elroy_stage = stage;
+            self.apply_enemy_speed();
+        }
+    }
+    fn apply_enemy_speed(&mut self) {
+        let speeds: Vec<i32> = (0..self.state.enemies.len())
+            .map(|i| self.enemy_speed_of(i))
+            .collect();
+        for (e, speed) in self.state.enemies.iter_mut().zip(speeds) {
+            e.change_speed(speed);
+        }
+    }
+    /// Which number should the HUD show this frame, if any? See ``score_display``.
+    fn displayed_score(&self) -> Option<i32> {
+        match self.config.score_display {
+            ScoreDisplay::Shown => Some(self.state.score),
+            ScoreDisplay::Hidden => None,
+            ScoreDisplay::Randomized => {
+                // Drawing can't advance the game's generator, which may go many frames untouched,
+                // so seed a throwaway one from it and the frame count to get a new number every frame.
+                let frame = (self.state.total_frames as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                let mut rng = random::Gen::new_child(&mut random::Gen::new([
+                    self.state.rand.digest(),
+                    frame,
+                ]));
+                let digits = self.state.score.to_string().len() as u32;
+                let low = if digits > 1 { 10i64.pow(digits - 1) } else { 0 };
+                let high = 10i64.pow(digits).min(i64::from(i32::MAX));
+                Some(rng.gen_range(low, high) as i32)
+            }
+        }
+    }
+    /// Record an event for this frame, and in the bounded log that survives until ``drain_events``.
+    fn emit(&mut self, event: GameEvent) {
+        self.state.events.push(event.clone());
+        self.state.event_log.push_back(event);
+        self.state.events_logged += 1;
+        while self.state.event_log.len() > self.config.event_log_limit {
+            self.state.event_log.pop_front();
+        }
+    }
+    /// Take every event logged since the last drain, oldest first.
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        self.state.event_log.drain(..).collect()
+    }
+    /// Watch for a player that isn't going anywhere; see ``stall_limit``.
+    fn check_stall(&mut self, start_tile: &TilePoint) {
+        let tile = self.state.player.position.to_tile();
+        if tile == *start_tile {
+            self.state.stalled_frames += 1;
+        } else {
+            self.state.stalled_frames = 0;
+        }
+        if Some(self.state.stalled_frames) == self.config.stall_limit {
+            self.emit(GameEvent::Stalled { tile });
+            if self.config.stall_ends_game {
+                self.state.lives = -1;
+                self.set_game_over(true);
+            }
+        }
+    }
+    /// Count the player entering ``tile`` and return how many times it has been entered; see ``track_visits``.
+    fn record_visit(&mut self, tile: &TilePoint) -> u32 {
+        let board = &self.state.board;
+        let size = (board.width * board.height) as usize;
+        if self.state.visit_counts.len() != size {
+            // First visit, or the board changed shape on a new level.
+            self.state.visit_counts = vec![0; size];
+        }
+        if let Some(id) = board.tile_id(tile) {
+            self.state.visit_counts[id as usize] += 1;
+            self.state.visit_counts[id as usize]
+        } else {
+            0
+        }
+    }
+    fn enemy_tiles(&self) -> Vec<TilePoint> {
+        self.state
+            .enemies
+            .iter()
+            .map(|e| e.position.to_tile())
+            .collect()
+    }
+    /// Check every enemy still missing in ``collisions`` against the player; ``previous_tiles`` and ``swept_from`` (by enemy index) are as in ``check_enemy_player_collision``.
+    fn find_collisions(
+        &self,
+        previous_tiles: Option<&[TilePoint]>,
+        swept_from: Option<&TilePoint>,
+        collisions: &mut [EnemyPlayerState],
+    ) {
+        for (i, e) in self.state.enemies.iter().enumerate() {
+            if collisions[i] == EnemyPlayerState::Miss {
+                let previous_tile = previous_tiles.map(|tiles| &tiles[i]);
+                collisions[i] = self.check_enemy_player_collision(e, i, previous_tile, swept_from);
+            }
+        }
+    }
+    /// Draw the board, player and enemies with sprites; only available with the ``graphics`` feature.
+    #[cfg(feature = "graphics")]
+    fn draw_images(&self, output: &mut Vec<Drawable>) {
+        let (tile_w, tile_h) = screen::TILE_SIZE;
+        let (offset_x, offset_y) = screen::BOARD_OFFSET;
+
+        // Use the level-1 sprites for odd levels less than the sixth level.
+        // Use the level-2 sprites for even levels and those greater than the sixth level.
+        // We will probably want to put some of this in the config later.
+        let ghosts = self.state.level % 2 == 1 && self.state.level < 6;
+
+        for (ty, row) in self.state.board.rows().enumerate() {
+            let ty = ty as i32;
+            for (tx, tile) in row.iter().enumerate() {
+                let tx = tx as i32;
+                let tile_sprite: &FixedSpriteData = match tile {
+                    &Tile::Painted => {
+                        if ghosts {
+                            &images::BLOCK_TILE_PAINTED_L1
+                        } else {
+                            &images::BLOCK_TILE_PAINTED_L2
+                        }
+                    }
+                    &Tile::Unpainted | &Tile::ChaseMarker => {
+                        if ghosts {
+                            &images::BLOCK_TILE_UNPAINTED_L1
+                        } else {
+                            &images::BLOCK_TILE_UNPAINTED_L2
+                        }
+                    }
+                    &Tile::Penalty => {
+                        output.push(Drawable::rect(
+                            self.config.penalty_color,
+                            offset_x + tx * tile_w,
+                            offset_y + ty * tile_h,
+                            tile_w,
+                            tile_h,
+                        ));
+                        continue;
+                    }
+                    &Tile::Empty => continue,
+                };
+                output.push(Drawable::sprite(
+                    offset_x + tx * tile_w,
+                    offset_y + ty * tile_h,
+                    tile_sprite.clone(),
+                ));
+            }
+        }
+
+        self.draw_gates(output);
+        for inner_box in self.state.board.boxes.iter().filter(|b| b.painted) {
+            let top_left_in = inner_box.top_left.translate(1, 1);
+            let x1 = top_left_in.tx;
+            let x2 = inner_box.bottom_right.tx;
+            let y1 = top_left_in.ty;
+            let y2 = inner_box.bottom_right.ty;
+
+            // generate all boxes inside:
+            for x in x1..x2 {
+                for y in y1..y2 {
+                    let pt = TilePoint::new(x, y).to_world().to_screen();
+                    output.push(Drawable::sprite(
+                        pt.sx + offset_x,
+                        pt.sy + offset_y,
+                        images::PAINTED_BOX_BAR.clone(),
+                    ));
+                }
+            }
+        }
+
+        let (player_x, player_y) = self.state.player.position.to_screen().pixels();
+        let player_sprite = match self.state.level % 2 {
+            1 => images::PLAYER_L1.clone(),
+            0 => images::PLAYER_L2.clone(),
+            _ => unreachable!(),
+        };
+        if self.player_visible() {
+            output.push(Drawable::sprite(
+                offset_x + player_x - 1,
+                offset_y + player_y - 1,
+                player_sprite,
+            ));
+        }
+
+        for enemy in &self.state.enemies {
+            let (x, y) = enemy.position.to_screen().pixels();
+            output.push(Drawable::sprite(
+                offset_x + x - 1,
+                offset_y + y - 1,
+                if enemy.eaten {
+                    match self.state.level % 2 {
+                        1 => images::ENEMY_CAUGHT_L1.clone(),
+                        0 => images::ENEMY_CAUGHT_L2.clone(),
+                        _ => unreachable!(),
+                    }
+                } else if enemy.immobilized_timer > 0 {
+                    match self.state.level % 2 {
+                        1 => images::ENEMY_HELD_L1.clone(),
+                        0 => images::ENEMY_HELD_L2.clone(),
+                        _ => unreachable!(),
+                    }
+                } else if self.state.chase_timer > 0 && !enemy.chase_immune {
+                    if enemy.caught {
+                        match self.state.level % 2 {
+                            1 => images::ENEMY_CAUGHT_L1.clone(),
+                            0 => images::ENEMY_CAUGHT_L2.clone(),
+                            _ => unreachable!(),
+                        }
+                    } else if self.chase_warning_flash() {
+                        match self.state.level % 2 {
+                            1 => images::ENEMY_L1.clone(),
+                            0 => images::ENEMY_L2.clone(),
+                            _ => unreachable!(),
+                        }
+                    } else {
+                        match self.state.level % 2 {
+                            1 => images::ENEMY_CHASE_L1.clone(),
+                            0 => images::ENEMY_CHASE_L2.clone(),
+                            _ => unreachable!(),
+                        }
+                    }
+                } else if self.state.jump_timer > 0 {
+                    match self.state.level % 2 {
+                        1 => images::ENEMY_JUMP_L1.clone(),
+                        0 => images::ENEMY_JUMP_L2.clone(),
+                        _ => unreachable!(),
+                    }
+                } else {
+                    match self.state.level % 2 {
+                        1 => images::ENEMY_L1.clone(),
+                        0 => images::ENEMY_L2.clone(),
+                        _ => unreachable!(),
+                    }
+                },
+            ))
+        }
+    }
+    /// Do frightened enemies look like their usual selves this frame? They flash for the last ``vulnerable_warning_frames`` of chase mode, switching every ``flash_period_frames`` of the game's frame counter.
+    pub fn chase_warning_flash(&self) -> bool {
+        let timer = self.state.chase_timer;
+        let period = self.config.flash_period_frames.max(1);
+        timer > 0
+            && timer <= self.config.vulnerable_warning_frames
+            && (self.state.total_frames / period) % 2 == 1
+    }
+    /// Is the player drawn this frame? Only not while blinking during a grace period or the death animation, every other four frames.
+    fn player_visible(&self) -> bool {
+        let blinking = |timer: i32| timer > 0 && (timer / 4) % 2 == 1;
+        !((self.config.blink_during_grace && blinking(self.state.grace_timer))
+            || (self.state.phase == GamePhase::Dying && blinking(self.state.phase_timer)))
+    }
+    /// Draw the bonus gates not yet reached this level over their tiles.
+    fn draw_gates(&self, output: &mut Vec<Drawable>) {
+        let (tile_w, tile_h) = screen::TILE_SIZE;
+        let (offset_x, offset_y) = screen::BOARD_OFFSET;
+        for (gate, reached) in self
+            .config
+            .bonus_gates
+            .iter()
+            .zip(self.state.gates_reached.iter())
+        {
+            if *reached {
+                continue;
+            }
+            output.push(Drawable::rect(
+                self.config.gate_color,
+                offset_x + gate.tile.tx * tile_w,
+                offset_y + gate.tile.ty * tile_h,
+                tile_w,
+                tile_h,
+            ));
+        }
+    }
+    /// Draw the board, player and enemies as colored rectangles.
+    fn draw_rects(&self, output: &mut Vec<Drawable>) {
+        let (tile_w, tile_h) = screen::TILE_SIZE;
+        let (offset_x, offset_y) = screen::BOARD_OFFSET;
+
+        for (ty, row) in self.state.board.rows().enumerate() {
+            let ty = ty as i32;
+            for (tx, tile) in row.iter().enumerate() {
+                let tx = tx as i32;
+                let tile_color = match tile {
+                    &Tile::Painted => self.config.painted_color,
+                    &Tile::Unpainted | &Tile::ChaseMarker => self.config.unpainted_color,
+                    &Tile::Penalty => self.config.penalty_color,
+                    &Tile::Empty => continue,
+                };
+                output.push(Drawable::rect(
+                    tile_color,
+                    offset_x + tx * tile_w,
+                    offset_y + ty * tile_h,
+                    tile_w,
+                    tile_h,
+                ));
+            }
+        }
+
+        self.draw_gates(output);
+        for inner_box in self.state.board.boxes.iter().filter(|b| b.painted) {
+            let origin = inner_box.top_left.translate(1, 1).to_world().to_screen();
+            let dest = inner_box.bottom_right.to_world().to_screen();
+            let w = dest.sx - origin.sx;
+            let h = dest.sy - origin.sy;
+            output.push(Drawable::rect(
+                self.config.inner_painted_color,
+                offset_x + origin.sx,
+                offset_y + origin.sy,
+                w,
+                h,
+            ));
+        }
+
+        let (player_x, player_y) = self.state.player.position.to_screen().pixels();
+        let (player_w, player_h) = screen::PLAYER_SIZE;
+        if self.player_visible() {
+            output.push(Drawable::rect(
+                self.config.player_color,
+                offset_x + player_x - 1,
+                offset_y + player_y - 1,
+                player_w,
+                player_h,
+            ));
+        }
+
+        for enemy in &self.state.enemies {
+            let (x, y) = enemy.position.to_screen().pixels();
+            let (w, h) = screen::ENEMY_SIZE;
+            let color = if enemy.immobilized_timer > 0 || enemy.eaten {
+                self.config.enemy_color.halfway_to(self.config.bg_color)
+            } else {
+                self.config.enemy_color
+            };
+            output.push(Drawable::rect(
+                color,
+                offset_x + x - 1,
+                offset_y + y - 1,
+                w,
+                h,
+            ));
+        }
+    }
+    /// The board dimmed toward the background, with the final score in the middle; see ``game_over_screen``.
+    fn draw_game_over(&self, output: &mut Vec<Drawable>) {
+        let bg = self.config.bg_color;
+        let mut board = Vec::new();
+        self.draw_rects(&mut board);
+        output.extend(board.into_iter().map(|drawable| match drawable {
+            Drawable::Rectangle { color, x, y, w, h } => {
+                Drawable::rect(color.halfway_to(bg), x, y, w, h)
+            }
+            other => other,
+        }));
+
+        let (offset_x, offset_y) = screen::BOARD_OFFSET;
+        let (tile_w, tile_h) = screen::TILE_SIZE;
+        let board_w = self.state.board.width as i32 * tile_w;
+        let board_h = self.state.board.height as i32 * tile_h;
+        // ``draw_score`` puts the last digit at x and the rest to its left.
+        let digits = self.state.score.to_string().len() as i32;
+        output.extend(draw_score(
+            self.state.score,
+            offset_x + (board_w + (digits - 2) * DIGIT_WIDTH) / 2,
+            offset_y + (board_h - DIGIT_HEIGHT) / 2,
+        ));
+    }
+    /// Pixel, world and tile sizes, and where the HUD lives; for tools that work from rendered frames.
+    pub fn geometry(&self) -> Geometry {
+        let score_digits = format!("{}", self.state.score).len() as i32;
+        let max_lives = self.config.start_lives.max(1);
+        let (shift_x, shift_y) = self.config.hud_shift();
+        Geometry {
+            game_size: self.config.screen_size(),
+            board_offset: screen::BOARD_OFFSET,
+            board_tiles: (
+                self.state.board.width as i32,
+                self.state.board.height as i32,
+            ),
+            tile_size: screen::TILE_SIZE,
+            world_scale: world::SCALE,
+            player_size: screen::PLAYER_SIZE,
+            enemy_size: screen::ENEMY_SIZE,
+            score_region: (
+                shift_x + screen::SCORE_X_POS - (score_digits - 1) * DIGIT_WIDTH,
+                shift_y + screen::SCORE_Y_POS + 1,
+                score_digits * DIGIT_WIDTH,
+                DIGIT_HEIGHT,
+            ),
+            lives_region: (
+                shift_x + screen::LIVES_X_POS - (max_lives - 1) * screen::LIVES_X_STEP,
+                shift_y + screen::LIVES_Y_POS,
+                (max_lives - 1) * screen::LIVES_X_STEP + 1,
+                DIGIT_HEIGHT + 1,
+            ),
+        }
+    }
+    pub fn board_size(&self) -> WorldPoint {
+        let th = self.state.board.height as i32;
+        let tw = self.state.board.width as i32;
+        TilePoint::new(tw + 1, th + 1).to_world()
+    }
+    /// Determine whether an enemy and a player are colliding and what to do about it.
+    /// If given, the enemy's ``previous_tile`` also counts as overlapping it; with ``swept_from`` (where the player was before), only when the two swapped tiles, passing through each other on the way.
+    fn check_enemy_player_collision(
+        &self,
+        enemy: &Mob,
+        enemy_id: usize,
+        previous_tile: Option<&TilePoint>,
+        swept_from: Option<&TilePoint>,
+    ) -> EnemyPlayerState {
+        if !enemy.released || enemy.immobilized_timer > 0 || enemy.eaten {
+            return EnemyPlayerState::Miss;
+        }
+        let player_tile = self.state.player.position.to_tile();
+        let enemy_tile = enemy.position.to_tile();
+        let met_on_the_way = previous_tile == Some(&player_tile)
+            && !matches!(swept_from, Some(from) if *from != enemy_tile);
+        let overlap = match self.config.collision_radius_world_units {
+            Some(radius) => {
+                let radius = i64::from(radius);
+                self.state.player.position.distance_squared(&enemy.position) <= radius * radius
+            }
+            None => player_tile == enemy_tile,
+        };
+        if overlap || met_on_the_way {
+            if self.state.chase_timer > 0 && !enemy.chase_immune {
+                if !enemy.caught {
+                    EnemyPlayerState::EnemyCatch(enemy_id)
+                } else {
+                    EnemyPlayerState::Miss
+                }
+            } else if self.state.jump_timer > 0 || self.state.grace_timer > 0 {
+                EnemyPlayerState::Miss
+            } else {
+                EnemyPlayerState::PlayerDeath(enemy_id)
+            }
+        } else {
+            // No overlap.
+            EnemyPlayerState::Miss
+        }
+    }
+}
+
+impl toybox_core::Simulation for Amidar {
+    fn reset_seed(&mut self, seed: u32) {
+        self.rand.reset_seed(seed)
+    }
+    fn game_size(&self) -> (i32, i32) {
+        self.screen_size()
+    }
+    fn new_game(&mut self) -> Box<dyn toybox_core::State> {
+        self.try_new_game().expect("new_game should succeed.")
+    }
+    fn try_new_game(&mut self) -> Result<Box<dyn toybox_core::State>, SimulationError> {
+        let state = State::try_new(self).map_err(SimulationError::InvalidConfig)?;
+        Ok(Box::new(state))
+    }
+    fn to_json(&self) -> String {
+        self.try_to_json()
+            .expect("Amidar should be JSON serializable!")
+    }
+    fn try_to_json(&self) -> Result<String, SimulationError> {
+        Ok(serde_json::to_string(self)?)
+    }
+    /// Sync with [ALE impl](https://github.com/mgbellemare/Arcade-Learning-Environment/blob/master/src/games/supported/Amidar.cpp#L80)
+    /// Note, leaving a call to sort in this impl to remind users that these vecs are ordered!
+    fn legal_action_set(&self) -> Vec<AleAction> {
+        let mut actions = vec![
+            AleAction::NOOP,
+            AleAction::FIRE,
+            AleAction::UP,
+            AleAction::RIGHT,
+            AleAction::LEFT,
+            AleAction::DOWN,
+            AleAction::UPFIRE,
+            AleAction::RIGHTFIRE,
+            AleAction::LEFTFIRE,
+            AleAction::DOWNFIRE,
+        ];
+        actions.sort();
+        actions
+    }
+
+    fn new_state_from_json(
+        &self,
+        json_str: &str,
+    ) -> Result<Box<dyn toybox_core::State>, serde_json::Error> {
+        let mut state: StateCore = serde_json::from_str(json_str)?;
+        self.upgrade_state(&mut state);
+        Ok(Box::new(State {
+            config: self.clone(),
+            state,
+        }))
+    }
+
+    fn from_json(
+        &self,
+        json_config: &str,
+    ) -> Result<Box<dyn toybox_core::Simulation>, serde_json::Error> {
+        let mut json: serde_json::Value = serde_json::from_str(json_config)?;
+        let theme = json
+            .get("theme")
+            .cloned()
+            .map(serde_json::from_value::<Option<Theme>>)
+            .transpose()?
+            .flatten();
+        if let (Some(theme), Some(fields)) = (theme, json.as_object_mut()) {
+            let themed = serde_json::to_value(theme.config())?;
+            for &field in THEME_COLOR_FIELDS.iter() {
+                if !fields.contains_key(field) {
+                    fields.insert(field.to_owned(), themed[field].clone());
+                }
+            }
+        }
+        let config: Amidar = serde_json::from_value(json)?;
+        config
+            .validate()
+            .map_err(<serde_json::Error as serde::de::Error>::custom)?;
+        Ok(Box::new(config))
+    }
+
+    fn schema_for_config(&self) -> String {
+        let schema = schema_for!(Amidar);
+        serde_json::to_string(&schema).expect("JSONSchema should be flawless.")
+    }
+    fn schema_for_state(&self) -> String {
+        let schema = schema_for!(StateCore);
+        serde_json::to_string(&schema).expect("JSONSchema should be flawless.")
+    }
+}
+
+impl toybox_core::State for State
+where
+    State: Clone,
+{
+    fn lives(&self) -> i32 {
+        if self.config.count_life_in_play {
+            self.state.lives + 1
+        } else {
+            self.state.lives
+        }
+    }
+    fn score(&self) -> i32 {
+        self.state.score
+    }
+    fn level(&self) -> i32 {
+        self.state.level
+    }
+    fn game_over(&self) -> bool {
+        self.state.game_over
+    }
+    fn update_mut(&mut self, buttons: Input) {
+        let buttons = self.latch_direction(buttons);
+        let mut reward = 0.0;
+        let mut events = Vec::new();
+        let mut breakdown = RewardBreakdown::default();
+        let mut board_update = None;
+        let mut life_lost = false;
+        for _ in 0..self.config.frame_skip.max(1) {
+            let buttons = self.sticky_buttons(buttons);
+            self.update_frame(buttons);
+            reward += self.state.reward;
+            events.append(&mut self.state.events);
+            breakdown.add(&self.state.reward_breakdown);
+            board_update = self.state.last_board_update.take().or(board_update);
+            life_lost |= self.state.life_lost;
+            if self.is_terminal() {
+                break;
+            }
+        }
+        self.state.reward = reward;
+        self.state.events = events;
+        self.state.reward_breakdown = breakdown;
+        self.state.last_board_update = board_update;
+        self.state.life_lost = life_lost;
+        self.push_observation();
+    }
+
+    fn draw(&self) -> Vec<Drawable> {
+        let mut output = Vec::new();
+        output.push(Drawable::Clear(self.config.bg_color));
+        if self.state.game_over {
+            if self.config.game_over_screen {
+                self.draw_game_over(&mut output);
+            }
+            return output;
+        }
+
+        #[cfg(feature = "graphics")]
+        {
+            if self.config.render_images {
+                self.draw_images(&mut output);
+            } else {
+                self.draw_rects(&mut output);
+            }
+        }
+        #[cfg(not(feature = "graphics"))]
+        self.draw_rects(&mut output);
+
+        for popup in &self.state.score_popups {
+            let (offset_x, offset_y) = screen::BOARD_OFFSET;
+            let (x, y) = popup.position.to_screen().pixels();
+            output.extend(draw_score(popup.points, offset_x + x, offset_y + y));
+        }
+        if let Some(freeze) = &self.state.catch_freeze {
+            let (offset_x, offset_y) = screen::BOARD_OFFSET;
+            let (x, y) = freeze.position.to_screen().pixels();
+            output.extend(draw_score(freeze.bonus, offset_x + x, offset_y + y));
+        }
+        let (shift_x, shift_y) = self.config.hud_shift();
+        if let Some(score) = self.displayed_score() {
+            output.extend(draw_score(
+                score,
+                shift_x + screen::SCORE_X_POS,
+                shift_y + screen::SCORE_Y_POS + 1,
+            ));
+        }
+        for i in 0..self.state.lives {
+            output.push(Drawable::rect(
+                self.config.player_color,
+                shift_x + screen::LIVES_X_POS - i * screen::LIVES_X_STEP,
+                shift_y + screen::LIVES_Y_POS,
+                1,
+                DIGIT_HEIGHT + 1,
+            ))
+        }
+
+        output
+    }
+
+    fn to_json(&self) -> String {
+        self.try_to_json()
+            .expect("Should be no JSON Serialization Errors.")
+    }
+    fn try_to_json(&self) -> Result<String, SimulationError> {
+        Ok(serde_json::to_string(&self.state)?)
+    }
+
+    fn query_json(&self, query: &str, args: &serde_json::Value) -> Result<String, QueryError> {
         if let Ok(parsed) = JSONQuery::parse(query) {
             if let Ok(Some(found)) = parsed.execute(&self) {
                 return Ok(serde_json::to_string(&found)?);
             }
         }
 
-        let state = &self.state;
-        Ok(match query {
-            "world_to_tile" => {
-                let world_pt: WorldPoint = serde_json::from_value(args.clone())?;
-                let tile = world_pt.to_tile();
-                serde_json::to_string(&(tile.tx, tile.ty))?
-            }
-            "tile_to_world" => {
-                let tile_pt: TilePoint = serde_json::from_value(args.clone())?;
-                let world = tile_pt.to_world();
-                serde_json::to_string(&(world.x, world.y))?
-            }
-            "num_tiles_unpainted" => {
-                let mut sum = 0;
-                for row in state.board.tiles.iter() {
-                    sum += row
-                        .iter()
-                        .filter(|t| t.walkable() && t.needs_paint())
-                        .count();
-                }
-                serde_json::to_string(&sum)?
-            }
-            "regular_mode" => {
-                serde_json::to_string(&(state.chase_timer == 0 && state.jump_timer == 0))?
-            }
-            "jump_mode" => serde_json::to_string(&(state.jump_timer > 0))?,
-            "chase_mode" => serde_json::to_string(&(state.chase_timer > 0))?,
-            "jumps_remaining" => serde_json::to_string(&(state.jumps > 0))?,
-            "num_enemies" => serde_json::to_string(&state.enemies.len())?,
-            "enemy_tiles" => {
-                let positions: Vec<(i32, i32)> = state
-                    .enemies
-                    .iter()
-                    .map(|e| {
-                        let tile = e.position.to_tile();
-                        (tile.tx, tile.ty)
-                    })
-                    .collect();
-                serde_json::to_string(&positions)?
-            }
-            "enemy_tile" => {
-                if let Some(index) = args.as_u64() {
-                    let tile = state.enemies[index as usize].position.to_tile();
-                    serde_json::to_string(&(tile.tx, tile.ty))?
+        let state = &self.state;
+        Ok(match query {
+            "world_to_tile" => {
+                let world_pt: WorldPoint = serde_json::from_value(args.clone())?;
+                let tile = world_pt.to_tile();
+                serde_json::to_string(&(tile.tx, tile.ty))?
+            }
+            "tile_to_world" => {
+                let tile_pt: TilePoint = serde_json::from_value(args.clone())?;
+                let world = tile_pt.to_world();
+                serde_json::to_string(&(world.x, world.y))?
+            }
+            "num_tiles_unpainted" => serde_json::to_string(&self.num_tiles_unpainted())?,
+            "level_frames" => serde_json::to_string(&state.level_frames)?,
+            "total_frames" => serde_json::to_string(&state.total_frames)?,
+            "board_tensor" => serde_json::to_string(&self.board_tensor())?,
+            "board_tensor_channels" => serde_json::to_string(&self.board_tensor_channels())?,
+            "stacked_board_tensor" => serde_json::to_string(&self.stacked_board_tensor())?,
+            "lives" => serde_json::to_string(&toybox_core::State::lives(self))?,
+            "terminal" => serde_json::to_string(&self.is_terminal())?,
+            "game_over" => serde_json::to_string(&state.game_over)?,
+            "phase" => serde_json::to_string(&state.phase)?,
+            "last_reward_breakdown" => serde_json::to_string(&state.reward_breakdown)?,
+            "last_board_update" => serde_json::to_string(&state.last_board_update)?,
+            "elroy_stage" => serde_json::to_string(&state.elroy_stage)?,
+            "level_params" => serde_json::to_string(&self.level_params_now())?,
+            "enemy_mode" => serde_json::to_string(&self.enemy_mode())?,
+            "lives_gained" => serde_json::to_string(&state.lives_gained)?,
+            "next_extra_life" => {
+                // With ``extra_life_score``, the points still to score before its life, until it's awarded.
+                // Otherwise lives come back one per cleared level (up to start_lives), so the "threshold"
+                // is how much painting is left before the next one, if one is due.
+                let left = match self.config.extra_life_score {
+                    Some(threshold) if !state.extra_life_awarded => {
+                        Some((threshold - state.score).max(0) as usize)
+                    }
+                    _ if self.config.refill_lives_on_level_clear
+                        && state.lives < self.config.start_lives =>
+                    {
+                        Some(self.num_tiles_unpainted())
+                    }
+                    _ => None,
+                };
+                serde_json::to_string(&left)?
+            }
+            "regular_mode" => {
+                serde_json::to_string(&(state.chase_timer == 0 && state.jump_timer == 0))?
+            }
+            "jump_mode" => serde_json::to_string(&(state.jump_timer > 0))?,
+            "chase_mode" => serde_json::to_string(&(state.chase_timer > 0))?,
+            "jumps_remaining" => serde_json::to_string(&(state.jumps > 0))?,
+            "num_enemies" => serde_json::to_string(&state.enemies.len())?,
+            "enemy_tiles" => {
+                let positions: Vec<(i32, i32)> = state
+                    .enemies
+                    .iter()
+                    .map(|e| {
+                        let tile = e.position.to_tile();
+                        (tile.tx, tile.ty)
+                    })
+                    .collect();
+                serde_json::to_string(&positions)?
+            }
+            "enemy_tile" => {
+                let enemy = args
+                    .as_u64()
+                    .and_then(|index| state.enemies.get(index as usize))
+                    .ok_or(QueryError::BadInputArg)?;
+                let tile = enemy.position.to_tile();
+                serde_json::to_string(&(tile.tx, tile.ty))?
+            }
+            "caught_this_frame" => {
+                let caught: Vec<bool> = state.enemies.iter().map(|e| e.caught_this_frame).collect();
+                serde_json::to_string(&caught)?
+            }
+            "immobilized_timers" => {
+                let timers: Vec<i32> = state.enemies.iter().map(|e| e.immobilized_timer).collect();
+                serde_json::to_string(&timers)?
+            }
+            "enemy_caught" => {
+                let enemy = args
+                    .as_u64()
+                    .and_then(|index| state.enemies.get(index as usize))
+                    .ok_or(QueryError::BadInputArg)?;
+                serde_json::to_string(&enemy.caught)?
+            }
+            "player_tile" => {
+                let tile = state.player.position.to_tile();
+                serde_json::to_string(&(tile.tx, tile.ty))?
+            }
+            "ai_debug_trace" => serde_json::to_string(&state.ai_debug)?,
+            "reward" => serde_json::to_string(&state.reward)?,
+            "events" => serde_json::to_string(&state.events)?,
+            "event_log" => {
+                // Queries can't clear the log, so pollers pass back the count they last saw instead.
+                let since = args.as_u64().unwrap_or(0);
+                let first = state.events_logged - state.event_log.len() as u64;
+                let skip = since.saturating_sub(first) as usize;
+                let events: Vec<&GameEvent> = state.event_log.iter().skip(skip).collect();
+                serde_json::to_string(&(state.events_logged, events))?
+            }
+            "distance_map" => {
+                let from: TilePoint = serde_json::from_value(args.clone())?;
+                serde_json::to_string(&state.board.distance_map(&from))?
+            }
+            "shortest_path" => {
+                let (from, to): (TilePoint, TilePoint) = serde_json::from_value(args.clone())?;
+                serde_json::to_string(&state.board.shortest_path(&from, &to))?
+            }
+            "bonus_gates" => {
+                // What each gate would pay if reached now; null once it has been reached.
+                let bonuses: Vec<Option<i32>> = self
+                    .config
+                    .bonus_gates
+                    .iter()
+                    .zip(state.gates_reached.iter())
+                    .map(|(gate, reached)| {
+                        if *reached {
+                            None
+                        } else {
+                            Some(gate.bonus_at(state.level_frames))
+                        }
+                    })
+                    .collect();
+                serde_json::to_string(&bonuses)?
+            }
+            "geometry" => serde_json::to_string(&self.geometry())?,
+            "chase_bonus" => serde_json::to_string(&self.chase_bonus())?,
+            "chase_warning_flash" => serde_json::to_string(&self.chase_warning_flash())?,
+            "chase_chain" => serde_json::to_string(&serde_json::json!({
+                "catches": state.chase_catches,
+                "next_bonus": self.chase_bonus(),
+                "max_bonus": self.config.chase_bonus_max,
+            }))?,
+            "score_popups" => serde_json::to_string(&state.score_popups)?,
+            "rng_audit" => serde_json::to_string(&state.rng_audit)?,
+            "rng_digest" => serde_json::to_string(&state.rand.digest())?,
+            "kills_by_enemy" => serde_json::to_string(&state.kills_by_enemy)?,
+            "catches_by_enemy" => serde_json::to_string(&state.catches_by_enemy)?,
+            "visit_counts" => {
+                let width = state.board.width as usize;
+                let rows: Vec<Vec<u32>> = (0..state.board.height as usize)
+                    .map(|y| {
+                        (0..width)
+                            .map(|x| state.visit_counts.get(y * width + x).cloned().unwrap_or(0))
+                            .collect()
+                    })
+                    .collect();
+                serde_json::to_string(&rows)?
+            }
+            _ => Err(QueryError::NoSuchQuery)?,
+        })
+    }
+    fn set_query_json(&mut self, query: &str, args: &serde_json::Value) -> Result<(), QueryError> {
+        match query {
+            "set_player_tile" => {
+                let tile: TilePoint = serde_json::from_value(args.clone())?;
+                if !self.state.board.get_tile(&tile).walkable() {
+                    return Err(QueryError::BadInputArg);
+                }
+                let player = &mut self.state.player;
+                player.position = tile.to_world();
+                player.step = None;
+                player.history.clear();
+            }
+            "set_enemy_tile" => {
+                let (index, tile): (usize, TilePoint) = serde_json::from_value(args.clone())?;
+                if !self.state.board.get_tile(&tile).walkable() {
+                    return Err(QueryError::BadInputArg);
+                }
+                let enemy = self
+                    .state
+                    .enemies
+                    .get_mut(index)
+                    .ok_or(QueryError::BadInputArg)?;
+                enemy.position = tile.to_world();
+                enemy.step = None;
+                enemy.history.clear();
+            }
+            "set_score" => self.state.score = serde_json::from_value(args.clone())?,
+            "set_lives" => {
+                self.state.lives = serde_json::from_value(args.clone())?;
+                self.set_game_over(self.state.lives < 0);
+            }
+            "set_jumps" => self.state.jumps = serde_json::from_value(args.clone())?,
+            "set_chase_timer" => {
+                let frames: i32 = serde_json::from_value(args.clone())?;
+                let chasing = self.state.chase_timer > 0;
+                if frames <= 0 {
+                    self.state.chase_timer = 0;
+                    if chasing {
+                        self.end_chase();
+                    }
+                } else if chasing {
+                    self.state.chase_timer = frames;
+                } else {
+                    self.start_chase(frames);
+                }
+            }
+            "paint_tile" => {
+                // No score: this is an intervention, not play.
+                let tile: TilePoint = serde_json::from_value(args.clone())?;
+                if !self.state.board.get_tile(&tile).walkable() {
+                    return Err(QueryError::BadInputArg);
+                }
+                self.state.board.paint(&tile);
+                self.state.board.check_box_painting(&tile, &tile);
+            }
+            "unpaint_tile" => {
+                let tile: TilePoint = serde_json::from_value(args.clone())?;
+                if !self.state.board.get_tile(&tile).walkable() {
+                    return Err(QueryError::BadInputArg);
+                }
+                self.state.board.unpaint(&tile);
+            }
+            _ => Err(QueryError::NoSuchQuery)?,
+        }
+        Ok(())
+    }
+    fn copy(&self) -> Box<dyn toybox_core::State> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use toybox_core::script::parse_script;
+    use toybox_core::State;
+
+    #[test]
+    fn test_colors_unique_in_gray() {
+        let config = Amidar::default();
+        let num_colors = config.colors().len();
+        let uniq_grays: HashSet<u8> = config
+            .colors()
+            .into_iter()
+            .map(|c| c.grayscale_byte())
+            .collect();
+        // Don't allow a grayscale agent to be confused where a human wouldn't be.
+        assert_eq!(uniq_grays.len(), num_colors);
+    }
+
+    #[test]
+    fn board_included() {
+        let board_ch: Vec<Vec<char>> = AMIDAR_BOARD
+            .lines()
+            .map(|line| line.chars().collect::<Vec<char>>())
+            .collect();
+        for row in board_ch.iter() {
+            assert_eq!(Some('='), row.iter().cloned().find(|c| *c == '='));
+        }
+    }
+
+    #[test]
+    fn board_corners() {
+        let board = Board::fast_new();
+        assert!(board.is_corner(0, 0));
+        assert!(board.is_corner(0, 30));
+        assert!(board.is_corner(31, 0));
+        assert!(board.is_corner(31, 30));
+    }
+    #[test]
+    fn player_start_position() {
+        let board = Board::fast_new();
+        assert_eq!(TilePoint::new(31, 15), board.lookup_position(511));
+        assert!(board.get_junction_id(&TilePoint::new(31, 18)).is_some());
+    }
+
+    #[test]
+    fn num_grid_boxes() {
+        let board = Board::fast_new();
+        let mut ordered = board.boxes.to_vec();
+        ordered.sort_by_key(|it| it.top_left.tx + it.top_left.ty * 32);
+        for gb in ordered {
+            println!("Box-found: {:?}", gb.top_left);
+        }
+        assert_eq!(board.boxes.len(), 29);
+    }
+
+    #[test]
+    #[cfg(feature = "graphics")]
+    fn test_load_png() {
+        let img = &images::PLAYER_L1;
+        assert!(img.width() > 0);
+        assert!(img.height() > 0);
+        assert!(img.find_visible_color().is_some());
+    }
+
+    #[test]
+    fn what_json_do_we_want() {
+        let data = MovementAI::EnemyPerimeterAI {
+            start: TilePoint::new(0, 0),
+        };
+        println!("{}", serde_json::to_string_pretty(&data).unwrap());
+        let data = MovementAI::EnemyTargetPlayer {
+            vision_distance: 15,
+            line_of_sight: true,
+            start: TilePoint::new(0, 0),
+            start_dir: Direction::Up,
+            dir: Direction::Up,
+            player_seen: None,
+        };
+        println!("{}", serde_json::to_string_pretty(&data).unwrap());
+    }
+
+    fn player_tile(state: &dyn State) -> (i32, i32) {
+        serde_json::from_str(
+            &state
+                .query_json("player_tile", &serde_json::Value::Null)
+                .unwrap(),
+        )
+        .unwrap()
+    }
+    fn num_tiles_unpainted(state: &dyn State) -> usize {
+        serde_json::from_str(
+            &state
+                .query_json("num_tiles_unpainted", &serde_json::Value::Null)
+                .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_q_num_tiles_unpainted() {
+        let mut state = super::State::try_new(&Amidar::default()).unwrap();
+
+        let (px, py) = player_tile(&state);
+        let first = num_tiles_unpainted(&state);
+
+        let mut go_up = Input::default();
+        go_up.up = true;
+
+        // Move the user up (be a little robust to how long the animation takes.)
+        for _ in 0..5000 {
+            state.update_mut(go_up);
+            if state.score() > 0 {
+                // we must have painted something!
+                break;
+            }
+        }
+
+        let (nx, ny) = player_tile(&state);
+        if py == ny {
+            panic!("Player can't move upward!")
+        }
+        println!("Moved player to ({},{}) from ({},{})", nx, ny, px, py);
+
+        let painted_now = num_tiles_unpainted(&state);
+        println!("painted_now: {} ... before: {}", painted_now, first);
+        assert!(painted_now < first);
+    }
+
+    #[test]
+    fn test_ai_debug_trace() {
+        let config = Amidar {
+            debug_ai: true,
+            enemies: vec![MovementAI::EnemyRandomMvmt {
+                start: TilePoint::new(0, 0),
+                start_dir: Direction::Right,
+                dir: Direction::Right,
+            }],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+
+        let mut decisions = 0;
+        for _ in 0..200 {
+            state.update_mut(Input::default());
+            assert_eq!(state.state.ai_debug.len(), 1);
+            if let Some(decision) = &state.state.ai_debug[0] {
+                decisions += 1;
+                let chosen = decision.chosen.expect("random AI always moves");
+                assert!(decision.considered.contains(&chosen));
+            }
+        }
+        assert!(decisions > 0);
+        // The trace is for the query, not for savestates.
+        assert!(!state.to_json().contains("ai_debug"));
+
+        let config = Amidar {
+            debug_ai: false,
+            ..config
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        state.update_mut(Input::default());
+        assert!(state.state.ai_debug.is_empty());
+    }
+
+    #[test]
+    fn test_scheduled_ai_cycles() {
+        let random = MovementAI::EnemyRandomMvmt {
+            start: TilePoint::new(0, 0),
+            start_dir: Direction::Right,
+            dir: Direction::Right,
+        };
+        let target = MovementAI::EnemyTargetPlayer {
+            start: TilePoint::new(0, 0),
+            start_dir: Direction::Right,
+            vision_distance: 15,
+            line_of_sight: true,
+            dir: Direction::Right,
+            player_seen: None,
+        };
+        let mut ai = MovementAI::EnemyScheduled {
+            schedule: vec![(random, 3), (target, 2)],
+            cursor: 0,
+            frames: 0,
+        };
+        let mut cursors = Vec::new();
+        for _ in 0..10 {
+            ai.tick();
+            if let MovementAI::EnemyScheduled { cursor, .. } = ai {
+                cursors.push(cursor);
+            }
+        }
+        assert_eq!(cursors, vec![0, 0, 1, 1, 0, 0, 0, 1, 1, 0]);
+
+        ai.reset();
+        if let MovementAI::EnemyScheduled { cursor, frames, .. } = ai {
+            assert_eq!((cursor, frames), (0, 0));
+        }
+
+        // A scheduled enemy should play like any other.
+        let config = Amidar {
+            enemies: vec![ai],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..100 {
+            state.update_mut(Input::default());
+        }
+    }
+
+    #[test]
+    fn test_level_cleared_event() {
+        let config = Amidar {
+            level_clear_bonus: 1000,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..10 {
+            state.update_mut(Input::default());
+        }
+        state.state.board.edit_tiles(|tiles| {
+            for tile in tiles.iter_mut() {
+                if tile.walkable() {
+                    *tile = Tile::Painted;
+                }
+            }
+        });
+        state.update_mut(Input::default());
+
+        assert_eq!(state.level(), 2);
+        assert_eq!(state.score(), 1000);
+        assert_eq!(
+            state.state.events,
+            vec![GameEvent::LevelCleared {
+                level: 1,
+                frames: 11,
+                total_frames: 11,
+                deaths: 0
+            }]
+        );
+        assert_eq!(state.state.level_frames, 0);
+        assert_eq!(state.state.total_frames, 11);
+        state.update_mut(Input::default());
+        let clock = |query: &str| -> i32 {
+            serde_json::from_str(&state.query_json(query, &serde_json::Value::Null).unwrap())
+                .unwrap()
+        };
+        assert_eq!(clock("level_frames"), 1);
+        assert_eq!(clock("total_frames"), 12);
+    }
+
+    #[test]
+    fn test_lives_queries() {
+        let config = Amidar {
+            enemies: Vec::new(),
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let query =
+            |state: &super::State, q: &str| state.query_json(q, &serde_json::Value::Null).unwrap();
+        assert_eq!(query(&state, "lives"), "3");
+        assert_eq!(query(&state, "next_extra_life"), "null");
+
+        state.state.lives -= 1;
+        assert_eq!(
+            query(&state, "next_extra_life"),
+            state.num_tiles_unpainted().to_string()
+        );
+
+        state.state.board.edit_tiles(|tiles| {
+            for tile in tiles.iter_mut() {
+                if tile.walkable() {
+                    *tile = Tile::Painted;
+                }
+            }
+        });
+        state.update_mut(Input::default());
+        assert_eq!(query(&state, "lives"), "3");
+        assert_eq!(query(&state, "lives_gained"), "1");
+
+        // Under the ALE rules the one extra life comes at 10,000 points.
+        let mut config = config;
+        config.apply_ale_mspacman_scoring();
+        let mut state = super::State::try_new(&config).unwrap();
+        assert_eq!(
+            query(&state, "lives"),
+            toybox_core::State::lives(&state).to_string()
+        );
+        assert_eq!(query(&state, "lives"), "3");
+        state.state.score = 2500;
+        assert_eq!(query(&state, "next_extra_life"), "7500");
+        state.state.score = 10_000;
+        state.update_mut(Input::default());
+        assert_eq!(query(&state, "lives_gained"), "1");
+        assert_eq!(query(&state, "next_extra_life"), "null");
+    }
+
+    #[test]
+    fn test_episodic_life() {
+        let config = Amidar::default();
+        let start = config.player_start.clone();
+        let config = Amidar {
+            episodic_life: true,
+            enemies: vec![MovementAI::EnemyRandomMvmt {
+                start,
+                start_dir: Direction::Right,
+                dir: Direction::Right,
+            }],
+            ..config
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let terminal = |state: &super::State| {
+            state
+                .query_json("terminal", &serde_json::Value::Null)
+                .unwrap()
+        };
+        assert_eq!(terminal(&state), "false");
+        state.update_mut(Input::default());
+        assert_eq!(state.lives(), config.start_lives - 1);
+        assert_eq!(terminal(&state), "true");
+
+        // The game carries on from where it was.
+        state.state.enemies[0].position = TilePoint::new(0, 0).to_world();
+        state.update_mut(Input::default());
+        assert_eq!(terminal(&state), "false");
+        assert_eq!(state.lives(), config.start_lives - 1);
+    }
+
+    #[test]
+    fn test_draw_digest() {
+        let config = Amidar::default();
+        let mut state = super::State::try_new(&config).unwrap();
+        let other = super::State::try_new(&config).unwrap();
+        assert_eq!(state.draw_digest(), other.draw_digest());
+
+        for _ in 0..10 {
+            state.update_mut(Input {
+                left: true,
+                ..Input::default()
+            });
+        }
+        assert_ne!(state.draw_digest(), other.draw_digest());
+
+        // Only what's drawn counts: a hidden state change leaves the digest alone.
+        let digest = state.draw_digest();
+        state.state.rng_audit.clear();
+        state.state.level_frames += 100;
+        assert_eq!(state.draw_digest(), digest);
+    }
+
+    #[test]
+    fn test_stall_ends_game() {
+        let config = Amidar {
+            enemies: Vec::new(),
+            stall_limit: Some(20),
+            stall_ends_game: true,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..19 {
+            state.update_mut(Input::default());
+            assert!(state.state.events.is_empty());
+        }
+        state.update_mut(Input::default());
+        assert_eq!(
+            state.state.events,
+            vec![GameEvent::Stalled {
+                tile: config.player_start.clone()
+            }]
+        );
+        assert!(state.lives() < 0);
+        assert!(state.game_over());
+    }
+
+    #[test]
+    fn test_game_over_stops_play() {
+        let config = Amidar {
+            enemies: Vec::new(),
+            stall_limit: Some(5),
+            stall_ends_game: true,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let query =
+            |state: &super::State, q: &str| state.query_json(q, &serde_json::Value::Null).unwrap();
+        for _ in 0..5 {
+            assert!(!state.game_over());
+            state.update_mut(Input::default());
+        }
+        assert!(state.game_over());
+        assert!(state.is_terminal());
+        assert_eq!(query(&state, "game_over"), "true");
+
+        // Nothing moves or counts once the game is over.
+        let frozen = state.state.clone();
+        let right = AleAction::RIGHT.to_input();
+        for _ in 0..10 {
+            state.update_mut(right);
+            assert_eq!(state.state.reward, 0.0);
+            assert!(state.state.events.is_empty());
+        }
+        assert_eq!(
+            state.state.player.position.to_tile(),
+            frozen.player.position.to_tile()
+        );
+        assert_eq!(state.state.total_frames, frozen.total_frames);
+
+        // Giving lives back is an intervention that resumes the game.
+        state
+            .set_query_json("set_lives", &serde_json::json!(2))
+            .unwrap();
+        assert!(!state.game_over());
+        state.update_mut(right);
+        assert_eq!(state.state.total_frames, frozen.total_frames + 1);
+    }
+
+    #[test]
+    fn test_game_time_frames() {
+        assert_eq!(GameTime::Seconds(10.0).to_frames(30.0), 300);
+        assert_eq!(GameTime::Seconds(2.5).to_frames(30.0), 75);
+        assert_eq!(GameTime::Frames(75).to_frames(60.0), 75);
+        assert_eq!(GameTime::Frames(75).to_seconds(30.0), 2.5);
+
+        // Halving the frame rate (e.g., a frame skip of 2) keeps chase mode 10 seconds long.
+        let config = Amidar {
+            frames_per_second: 15.0,
+            ..Amidar::default()
+        };
+        assert_eq!(config.chase_time.to_frames(config.frames_per_second), 150);
+
+        // Configs from before ``GameTime`` gave plain frame counts.
+        let frames: GameTime = serde_json::from_str("300").unwrap();
+        assert_eq!(frames, GameTime::Frames(300));
+        let seconds = serde_json::to_string(&GameTime::Seconds(2.5)).unwrap();
+        assert_eq!(
+            serde_json::from_str::<GameTime>(&seconds).unwrap(),
+            GameTime::Seconds(2.5)
+        );
+
+        let stopped = Amidar {
+            frames_per_second: 0.0,
+            ..Amidar::default()
+        };
+        assert!(stopped.validate().is_err());
+        assert!(super::State::try_new(&stopped).is_err());
+    }
+
+    #[test]
+    fn test_score_display() {
+        fn score_sprites(state: &super::State) -> usize {
+            state
+                .draw()
+                .iter()
+                .filter(|d| matches!(d, Drawable::StaticSprite { .. }))
+                .count()
+        }
+        let config = Amidar {
+            render_images: false,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        state.state.score = 1234;
+        assert_eq!(score_sprites(&state), 4);
+
+        state.config.score_display = ScoreDisplay::Hidden;
+        assert_eq!(score_sprites(&state), 0);
+        assert_eq!(state.score(), 1234);
+
+        state.config.score_display = ScoreDisplay::Randomized;
+        assert_eq!(score_sprites(&state), 4);
+        let shown = state.displayed_score().unwrap();
+        assert!((1000..10000).contains(&shown));
+        assert_eq!(state.score(), 1234);
+
+        // The next frame shows another number, though neither the score nor the game's generator changed.
+        let rand = state.state.rand.clone();
+        state.update_mut(Input::default());
+        assert_eq!(state.score(), 1234);
+        assert_eq!(state.state.rand, rand);
+        assert_ne!(state.displayed_score().unwrap(), shown);
+    }
+
+    #[test]
+    fn test_enemy_attribution() {
+        let config = Amidar::default();
+        let start = config.player_start.clone();
+        let config = Amidar {
+            enemies: vec![
+                MovementAI::EnemyRandomMvmt {
+                    start: TilePoint::new(0, 0),
+                    start_dir: Direction::Right,
+                    dir: Direction::Right,
+                },
+                MovementAI::EnemyRandomMvmt {
+                    start: start.clone(),
+                    start_dir: Direction::Right,
+                    dir: Direction::Right,
+                },
+            ],
+            ..config
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        state.update_mut(Input::default());
+        assert_eq!(state.state.kills_by_enemy, vec![0, 1]);
+        assert_eq!(
+            state.state.events,
+            vec![GameEvent::PlayerKilled { enemy: 1 }]
+        );
+
+        state.state.chase_timer = 100;
+        state.state.enemies[0].position = state.state.player.position.clone();
+        state.state.enemies[1].position = TilePoint::new(0, 0).to_world();
+        state.update_mut(Input::default());
+        assert_eq!(state.state.catches_by_enemy, vec![1, 0]);
+        assert!(state
+            .state
+            .events
+            .contains(&GameEvent::EnemyCaught { enemy: 0 }));
+        assert_eq!(
+            state
+                .query_json("kills_by_enemy", &serde_json::Value::Null)
+                .unwrap(),
+            "[0,1]"
+        );
+    }
+
+    #[test]
+    fn test_catch_freeze() {
+        let config = Amidar {
+            catch_freeze_frames: 3,
+            enemies: vec![MovementAI::EnemyRandomMvmt {
+                start: TilePoint::new(0, 0),
+                start_dir: Direction::Right,
+                dir: Direction::Right,
+            }],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        state.state.chase_timer = 100;
+        state.state.enemies[0].position = state.state.player.position.clone();
+        state.update_mut(Input::default());
+        let freeze = state
+            .state
+            .catch_freeze
+            .clone()
+            .expect("catch should freeze");
+        assert_eq!(freeze.frames_left, 3);
+        assert_eq!(freeze.bonus, config.chase_score_bonus);
+
+        let frozen = state.state.player.position.to_screen().pixels();
+        let right = Input {
+            right: true,
+            ..Input::default()
+        };
+        for _ in 0..3 {
+            state.update_mut(right);
+            assert_eq!(state.state.player.position.to_screen().pixels(), frozen);
+            assert_eq!(state.state.chase_timer, 99);
+        }
+        assert!(state.state.catch_freeze.is_none());
+        state.update_mut(right);
+        assert_eq!(state.state.chase_timer, 98);
+    }
+
+    #[test]
+    fn test_render_minimap() {
+        let board = Board::fast_new();
+        let image = board.render_minimap(2);
+        assert_eq!(image.width, board.width as i32 * 2);
+        assert_eq!(image.height, board.height as i32 * 2);
+
+        let pixel = |x: i32, y: i32| {
+            let start = ((y * image.width + x) * 4) as usize;
+            image.data[start..start + 3].to_vec()
+        };
+        // The top-left corner of the default board is a chase marker, then the top edge is unpainted; the tile inside it is empty.
+        assert_eq!(board.get_tile(&TilePoint::new(0, 0)), Tile::ChaseMarker);
+        assert_eq!(pixel(1, 1), vec![255, 255, 255]);
+        assert_eq!(board.get_tile(&TilePoint::new(1, 0)), Tile::Unpainted);
+        assert_eq!(pixel(3, 1), vec![148, 0, 211]);
+        assert_eq!(board.get_tile(&TilePoint::new(1, 1)), Tile::Empty);
+        assert_eq!(pixel(3, 3), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn test_lint_board() {
+        let start = Amidar::default().player_start;
+        let report = Board::fast_new().lint(&start);
+        assert!(report.problems().is_empty(), "{:?}", report.problems());
+        assert_eq!(report.regions, 1);
+        assert!(report.dead_ends.is_empty());
+        assert_eq!(report.junctions, Board::fast_new().junctions.len() as u32);
+
+        let lines: Vec<String> = vec!["=====", "=   =", "=====", "     ", "==   "]
+            .into_iter()
+            .map(|s| s.to_owned())
+            .collect();
+        let board = Board::try_new(&lines).unwrap();
+        let report = board.lint(&TilePoint::new(0, 0));
+        assert_eq!(report.regions, 2);
+        assert_eq!(
+            report.unreachable,
+            vec![TilePoint::new(0, 4), TilePoint::new(1, 4)]
+        );
+        assert_eq!(report.dead_ends.len(), 2);
+        assert_eq!(report.problems().len(), 2);
+
+        assert!(Board::try_new(&[]).is_err());
+    }
+
+    #[test]
+    fn test_rng_audit() {
+        let config = Amidar {
+            audit_rng: true,
+            enemies: vec![
+                MovementAI::EnemyRandomMvmt {
+                    start: TilePoint::new(0, 0),
+                    start_dir: Direction::Right,
+                    dir: Direction::Right,
+                },
+                MovementAI::EnemyPerimeterAI {
+                    start: TilePoint::new(0, 0),
+                },
+            ],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..200 {
+            state.update_mut(Input::default());
+        }
+        let audit = &state.state.rng_audit;
+        assert_eq!(audit[0].purpose, RngPurpose::NewGame);
+        assert!(audit.len() > 1);
+        // Only the random enemy ever draws.
+        assert!(audit[1..]
+            .iter()
+            .all(|draw| draw.mob == Some(0) && draw.purpose == RngPurpose::EnemyMove));
+
+        let config = Amidar {
+            audit_rng: false,
+            ..config
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        state.update_mut(Input::default());
+        assert!(state.state.rng_audit.is_empty());
+    }
+
+    #[test]
+    fn test_caught_enemies_immobilized() {
+        let config = Amidar {
+            immobilized_time: 10,
+            enemies: vec![MovementAI::EnemyPerimeterAI {
+                start: TilePoint::new(0, 0),
+            }],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..5 {
+            state.update_mut(Input::default());
+        }
+        let start = TilePoint::new(0, 0);
+
+        state.state.chase_timer = 100;
+        state.state.enemies[0].position = state.state.player.position.clone();
+        state.update_mut(Input::default());
+        assert!(state.state.enemies[0].caught_this_frame);
+        assert!(!state.state.enemies[0].caught);
+        assert!(state.state.enemies[0].chase_immune);
+        assert_eq!(state.state.enemies[0].position.to_tile(), start);
+        assert_eq!(state.state.enemies[0].immobilized_timer, 10);
+
+        // It sits still and can't hurt the player, even once chase mode is over.
+        state.state.chase_timer = 1;
+        state.state.player.position = state.state.enemies[0].position.clone();
+        let lives = state.lives();
+        for _ in 0..5 {
+            state.update_mut(Input::default());
+        }
+        assert_eq!(state.lives(), lives);
+        assert_eq!(state.state.enemies[0].position.to_tile(), start);
+        let timers: Vec<i32> = serde_json::from_str(
+            &state
+                .query_json("immobilized_timers", &serde_json::Value::Null)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(timers, vec![5]);
+
+        // Then it goes back to its route.
+        state.state.player.position = state.config.player_start.to_world();
+        for _ in 0..5 {
+            state.update_mut(Input::default());
+        }
+        assert_eq!(state.state.enemies[0].immobilized_timer, 0);
+        for _ in 0..50 {
+            state.update_mut(Input::default());
+        }
+        assert_ne!(state.state.enemies[0].position.to_tile(), start);
+    }
+
+    #[test]
+    fn test_start_immobilized_base() {
+        let enemy = MovementAI::EnemyPerimeterAI {
+            start: TilePoint::new(0, 0),
+        };
+        let config = Amidar {
+            start_immobilized_base: 20,
+            enemies: vec![enemy.clone(), enemy.clone(), enemy],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let timers = |state: &super::State| -> Vec<i32> {
+            state
+                .state
+                .enemies
+                .iter()
+                .map(|e| e.immobilized_timer)
+                .collect()
+        };
+        assert_eq!(timers(&state), vec![0, 20, 40]);
+
+        // Only the first enemy sets off straight away.
+        for _ in 0..10 {
+            state.update_mut(Input::default());
+        }
+        assert_ne!(
+            state.state.enemies[0].position.to_tile(),
+            TilePoint::new(0, 0)
+        );
+        assert_eq!(
+            state.state.enemies[2].position.to_tile(),
+            TilePoint::new(0, 0)
+        );
+
+        // Losing a life staggers them again.
+        state.state.enemies[0].position = state.state.player.position.clone();
+        state.update_mut(Input::default());
+        assert!(state.state.life_lost);
+        assert_eq!(timers(&state), vec![0, 20, 40]);
+
+        // So does a new level.
+        state.state.board.edit_tiles(|tiles| {
+            for tile in tiles.iter_mut() {
+                if tile.walkable() {
+                    *tile = Tile::Painted;
+                }
+            }
+        });
+        state.update_mut(Input::default());
+        assert_eq!(state.level(), 2);
+        assert_eq!(timers(&state), vec![0, 20, 40]);
+    }
+
+    #[test]
+    fn test_respawn_points() {
+        let enemy = MovementAI::EnemyPerimeterAI {
+            start: TilePoint::new(0, 0),
+        };
+        let config = Amidar {
+            immobilized_time: 10,
+            respawn_point: Some(TilePoint::new(10, 0)),
+            enemy_respawn_points: vec![
+                None,
+                Some(TilePoint::new(0, 6)),
+                Some(TilePoint::new(1, 1)),
+            ],
+            enemies: vec![enemy.clone(), enemy.clone(), enemy],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..5 {
+            state.update_mut(Input::default());
+        }
+        state.state.chase_timer = 100;
+        for e in state.state.enemies.iter_mut() {
+            e.position = state.state.player.position.clone();
+        }
+        state.update_mut(Input::default());
+        let tiles: Vec<TilePoint> = state
+            .state
+            .enemies
+            .iter()
+            .map(|e| e.position.to_tile())
+            .collect();
+        // The last one's tile is inside a box, so it goes back to its own start.
+        assert_eq!(
+            tiles,
+            vec![
+                TilePoint::new(10, 0),
+                TilePoint::new(0, 6),
+                TilePoint::new(0, 0)
+            ]
+        );
+
+        // A new life starts them at their starts, as ever.
+        state.reset();
+        assert!(state
+            .state
+            .enemies
+            .iter()
+            .all(|e| e.position.to_tile() == TilePoint::new(0, 0)));
+    }
+
+    #[test]
+    fn test_eyes_return_home() {
+        let config = Amidar {
+            immobilized_time: 10,
+            eyes_speed: Some(40),
+            enemies: vec![MovementAI::EnemyPerimeterAI {
+                start: TilePoint::new(0, 0),
+            }],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..5 {
+            state.update_mut(Input::default());
+        }
+        let caught_at = state.state.player.position.to_tile();
+        state.state.chase_timer = 1000;
+        state.state.enemies[0].position = state.state.player.position.clone();
+        state.update_mut(Input::default());
+        assert_eq!(state.state.catches_by_enemy, vec![1]);
+        assert!(state.state.enemies[0].eaten);
+
+        // On the way home it's harmless, and a saved state carries it on the way.
+        let lives = state.lives();
+        state.state.enemies[0].position = state.state.player.position.clone();
+        state.update_mut(Input::default());
+        assert_eq!(state.lives(), lives);
+        let saved: StateCore = serde_json::from_str(&state.to_json()).unwrap();
+        assert!(saved.enemies[0].eaten);
+
+        let mut frames = 0;
+        while state.state.enemies[0].eaten {
+            state.update_mut(Input::default());
+            frames += 1;
+            assert!(frames < 200, "the eyes never got home");
+        }
+        // Faster than walking, but not a jump.
+        assert!(frames > 1);
+        assert_ne!(caught_at, TilePoint::new(0, 0));
+        let enemy = &state.state.enemies[0];
+        assert_eq!(enemy.position.to_tile(), TilePoint::new(0, 0));
+        assert_eq!(enemy.immobilized_timer, 10);
+        assert!(!enemy.catchable(true));
+    }
+
+    #[test]
+    fn test_immobilized_enemy_outlasted_by_chase() {
+        let config = Amidar {
+            immobilized_time: 5,
+            enemies: vec![MovementAI::EnemyPerimeterAI {
+                start: TilePoint::new(0, 0),
+            }],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..5 {
+            state.update_mut(Input::default());
+        }
+        state.state.chase_timer = 100;
+        state.state.enemies[0].position = state.state.player.position.clone();
+        state.update_mut(Input::default());
+        assert_eq!(state.state.catches_by_enemy, vec![1]);
+        for _ in 0..10 {
+            state.update_mut(Input::default());
+        }
+        assert_eq!(state.state.enemies[0].immobilized_timer, 0);
+        assert!(state.state.chase_timer > 0);
+
+        // Back on the move mid-chase: no second catch, and touching it costs a life.
+        assert!(!state.state.enemies[0].catchable(true));
+        let lives = state.lives();
+        state.state.enemies[0].position = state.state.player.position.clone();
+        state.update_mut(Input::default());
+        assert_eq!(state.state.catches_by_enemy, vec![1]);
+        assert_eq!(state.lives(), lives - 1);
+
+        // The next chase mode can catch it again.
+        state.state.chase_timer = 1;
+        state.update_mut(Input::default());
+        assert!(state.state.enemies[0].catchable(true));
+    }
+
+    #[test]
+    fn test_simultaneous_catches() {
+        let enemy = MovementAI::EnemyPerimeterAI {
+            start: TilePoint::new(0, 0),
+        };
+        let config = Amidar {
+            chase_bonus_factor: 2,
+            enemies: vec![enemy.clone(), enemy.clone(), enemy],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let caught = |state: &super::State| -> Vec<bool> {
+            serde_json::from_str(
+                &state
+                    .query_json("caught_this_frame", &serde_json::Value::Null)
+                    .unwrap(),
+            )
+            .unwrap()
+        };
+        let pile_on = |state: &mut super::State| {
+            for e in state.state.enemies.iter_mut().take(2) {
+                e.position = state.state.player.position.clone();
+            }
+        };
+
+        // Two different enemies on the same frame: both caught, once each.
+        state.state.chase_timer = 100;
+        pile_on(&mut state);
+        let before = state.score();
+        state.update_mut(Input::default());
+        assert_eq!(caught(&state), vec![true, true, false]);
+        assert_eq!(state.score() - before, 100 + 200);
+        assert_eq!(state.state.catches_by_enemy, vec![1, 1, 0]);
+
+        // Still touching on the next frame: no second catch.
+        pile_on(&mut state);
+        let before = state.score();
+        state.update_mut(Input::default());
+        assert_eq!(caught(&state), vec![false, false, false]);
+        assert_eq!(state.score(), before);
+
+        // Once chase mode is over, the same enemies can be caught in the next one.
+        state.state.chase_timer = 1;
+        state.update_mut(Input::default());
+        assert!(state.state.enemies.iter().all(|e| !e.caught));
+        state.state.chase_timer = 100;
+        pile_on(&mut state);
+        state.update_mut(Input::default());
+        assert_eq!(caught(&state), vec![true, true, false]);
+        assert_eq!(state.state.catches_by_enemy, vec![2, 2, 0]);
+    }
+
+    #[test]
+    fn test_late_enemies_not_frightened() {
+        let enemy = MovementAI::EnemyPerimeterAI {
+            start: TilePoint::new(0, 0),
+        };
+        let config = Amidar {
+            enemies: vec![enemy.clone(), enemy],
+            enemy_releases: vec![
+                EnemyRelease {
+                    after_frames: None,
+                    after_tiles_painted: None,
+                },
+                EnemyRelease {
+                    after_frames: Some(5),
+                    after_tiles_painted: None,
+                },
+            ],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        state.state.chase_timer = 100;
+        for _ in 0..5 {
+            state.update_mut(Input::default());
+        }
+        assert!(state.state.enemies[1].released);
+        assert!(!state.state.enemies[0].chase_immune);
+        assert!(state.state.enemies[1].chase_immune);
+        assert!(state.state.enemies[0].catchable(true));
+        assert!(!state.state.enemies[1].catchable(true));
+
+        // The late enemy is as dangerous as ever.
+        state.state.enemies[1].position = state.state.player.position.clone();
+        state.update_mut(Input::default());
+        assert_eq!(state.lives(), config.start_lives - 1);
+        assert_eq!(state.state.catches_by_enemy, vec![0, 0]);
+
+        // Everyone is catchable again in the next chase mode.
+        state.state.chase_timer = 1;
+        state.update_mut(Input::default());
+        assert!(state.state.enemies.iter().all(|e| !e.chase_immune));
+    }
+
+    #[test]
+    fn test_chase_warning_flash() {
+        let config = Amidar {
+            enemies: Vec::new(),
+            vulnerable_warning_frames: 20,
+            flash_period_frames: 4,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        state.state.chase_timer = 40;
+        let mut flashes = Vec::new();
+        while state.state.chase_timer > 0 {
+            state.update_mut(Input::default());
+            flashes.push((state.state.chase_timer, state.chase_warning_flash()));
+        }
+        assert!(flashes
+            .iter()
+            .all(|&(timer, flash)| !flash || (timer > 0 && timer <= 20)));
+        let warned: Vec<bool> = flashes
+            .iter()
+            .filter(|&&(timer, _)| timer > 0 && timer <= 20)
+            .map(|&(_, flash)| flash)
+            .collect();
+        // Four frames on, four frames off, by the frame counter.
+        assert!(warned.contains(&true) && warned.contains(&false));
+        assert!(warned
+            .windows(5)
+            .all(|w| w.iter().any(|&f| f) && w.iter().any(|&f| !f)));
+        assert_eq!(
+            state
+                .query_json("chase_warning_flash", &serde_json::Value::Null)
+                .unwrap(),
+            "false"
+        );
+    }
+
+    #[test]
+    fn test_chase_bonus_chain() {
+        let enemy = MovementAI::EnemyPerimeterAI {
+            start: TilePoint::new(0, 0),
+        };
+        let config = Amidar {
+            chase_bonus_factor: 2,
+            chase_bonus_max: Some(350),
+            enemies: vec![enemy.clone(), enemy.clone(), enemy],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        assert_eq!(state.chase_bonus(), 100);
+
+        state.state.chase_timer = 100;
+        for e in state.state.enemies.iter_mut() {
+            e.position = state.state.player.position.clone();
+        }
+        let before = state.score();
+        state.update_mut(Input::default());
+        assert_eq!(state.state.chase_catches, 3);
+        assert_eq!(state.score() - before, 100 + 200 + 350);
+        assert_eq!(
+            state
+                .query_json("chase_bonus", &serde_json::Value::Null)
+                .unwrap(),
+            "350"
+        );
+        let chain = |state: &super::State| -> serde_json::Value {
+            serde_json::from_str(
+                &state
+                    .query_json("chase_chain", &serde_json::Value::Null)
+                    .unwrap(),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            chain(&state),
+            serde_json::json!({"catches": 3, "next_bonus": 350, "max_bonus": 350})
+        );
+
+        // The chain is over as soon as chase mode is.
+        state.state.chase_timer = 1;
+        state.update_mut(Input::default());
+        assert_eq!(state.state.chase_timer, 0);
+        assert_eq!(chain(&state)["catches"], 0);
+        assert_eq!(state.chase_bonus(), 100);
+    }
+
+    #[test]
+    fn test_ghost_personalities() {
+        let corner = TilePoint::new(0, 0);
+        let play = |ai: MovementAI| {
+            let config = Amidar {
+                enemies: vec![ai],
+                ..Amidar::default()
+            };
+            let mut state = super::State::try_new(&config).unwrap();
+            for _ in 0..2000 {
+                state.update_mut(Input::default());
+                if state.state.kills_by_enemy[0] > 0 {
+                    break;
+                }
+            }
+            state.state.kills_by_enemy[0]
+        };
+        // A standing player gets hunted down...
+        assert_eq!(
+            play(MovementAI::EnemyChase {
+                start: corner.clone(),
+                start_dir: Direction::Right,
+                dir: Direction::Right,
+            }),
+            1
+        );
+        // ...unless the enemy is too shy to come close.
+        assert_eq!(
+            play(MovementAI::EnemyShy {
+                start: corner.clone(),
+                start_dir: Direction::Right,
+                dir: Direction::Right,
+                shy_distance: 100,
+                retreat: corner.clone(),
+            }),
+            0
+        );
+
+        let mut player = Mob::new_player(TilePoint::new(5, 5).to_world(), 1);
+        assert_eq!(
+            MovementAI::ahead_of_player(&player, 4),
+            TilePoint::new(5, 5)
+        );
+        player.step = Some(TilePoint::new(5, 4));
+        assert_eq!(
+            MovementAI::ahead_of_player(&player, 4),
+            TilePoint::new(5, 1)
+        );
+    }
+
+    #[test]
+    fn test_enemy_mode_schedule() {
+        let chaser = MovementAI::EnemyChase {
+            start: TilePoint::new(0, 0),
+            start_dir: Direction::Right,
+            dir: Direction::Right,
+        };
+        let config = Amidar {
+            enemies: vec![chaser],
+            mode_schedule: vec![(EnemyMode::Scatter, 3), (EnemyMode::Pursue, 1)],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let mut modes = Vec::new();
+        for _ in 0..5 {
+            state.update_mut(Input::default());
+            modes.push(state.enemy_mode());
+        }
+        use EnemyMode::*;
+        assert_eq!(modes, vec![Scatter, Scatter, Pursue, Pursue, Pursue]);
+
+        // Scattering forever, the chaser never comes for the player.
+        let config = Amidar {
+            mode_schedule: vec![(EnemyMode::Scatter, 1)],
+            ..config
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..2000 {
+            state.update_mut(Input::default());
+        }
+        assert_eq!(state.state.kills_by_enemy, vec![0]);
+    }
+
+    #[test]
+    fn test_event_log() {
+        let config = Amidar {
+            enemies: Vec::new(),
+            stall_limit: Some(5),
+            event_log_limit: 3,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..10 {
+            state.update_mut(Input::default());
+        }
+        // The per-frame list has moved on, but the log still has it.
+        let stalled = GameEvent::Stalled {
+            tile: config.player_start.clone(),
+        };
+        assert!(state.state.events.is_empty());
+        assert_eq!(
+            state
+                .query_json("event_log", &serde_json::Value::Null)
+                .unwrap(),
+            serde_json::to_string(&(1, vec![&stalled])).unwrap()
+        );
+        assert_eq!(
+            state
+                .query_json("event_log", &serde_json::json!(1))
+                .unwrap(),
+            serde_json::to_string(&(1, Vec::<GameEvent>::new())).unwrap()
+        );
+
+        for _ in 0..4 {
+            state.emit(stalled.clone());
+        }
+        assert_eq!(state.state.events_logged, 5);
+        assert_eq!(state.drain_events(), vec![stalled.clone(); 3]);
+        assert!(state.drain_events().is_empty());
+    }
+
+    #[test]
+    fn test_large_board() {
+        // A 101x101 grid with a line every fourth tile.
+        let board: Vec<String> = (0..101)
+            .map(|ty| {
+                (0..101)
+                    .map(|tx| if tx % 4 == 0 || ty % 4 == 0 { '=' } else { ' ' })
+                    .collect()
+            })
+            .collect();
+        let start = TilePoint::new(0, 0);
+        let config = Amidar {
+            board,
+            player_start: start.clone(),
+            default_board_bugs: false,
+            enemies: vec![MovementAI::EnemyRandomMvmt {
+                start: TilePoint::new(100, 100),
+                start_dir: Direction::Left,
+                dir: Direction::Left,
+            }],
+            ..Amidar::default()
+        };
+        assert_eq!(Amidar::default().screen_size(), screen::GAME_SIZE);
+        assert_eq!(config.screen_size(), (16 * 2 + 101 * 4, 37 + 101 * 5 + 58));
+
+        let mut state = super::State::try_new(&config).unwrap();
+        let corner = TilePoint::new(100, 100);
+        assert_eq!(corner.to_world().to_tile(), corner);
+        let geometry = state.geometry();
+        assert_eq!(geometry.game_size, config.screen_size());
+        assert!(geometry.lives_region.1 > 37 + 101 * 5);
+
+        let script = parse_script("R×50, D×50, R×50, D×50, R×50, D×50").unwrap();
+        for input in script {
+            state.update_mut(input);
+        }
+        let tile = state.state.player.position.to_tile();
+        assert!(tile.tx > 0 && tile.ty > 0);
+
+        // Clearing the level repaints the same board, not the default one.
+        state.state.board.edit_tiles(|tiles| {
+            for tile in tiles.iter_mut() {
+                if tile.walkable() {
+                    *tile = Tile::Painted;
+                }
+            }
+        });
+        state.update_mut(Input::default());
+        assert_eq!(state.level(), 2);
+        assert_eq!(
+            (state.state.board.width, state.state.board.height),
+            (101, 101)
+        );
+        assert!(state.num_tiles_unpainted() > 0);
+
+        let too_big = vec!["=".repeat(world::MAX_BOARD_TILES as usize + 1)];
+        assert!(Board::try_new(&too_big).is_err());
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let board = Board::fast_new();
+        let start = Amidar::default().player_start;
+        assert_eq!(
+            board.shortest_path(&start, &start),
+            Some(vec![start.clone()])
+        );
+
+        // Across the top-left box: straight along the edge, not through it.
+        let from = TilePoint::new(0, 0);
+        let to = TilePoint::new(0, 6);
+        let path = board.shortest_path(&from, &to).unwrap();
+        assert_eq!(path.len(), 7);
+        assert_eq!(path[0], from);
+        assert_eq!(path[6], to);
+        for pair in path.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            assert_eq!((a.tx - b.tx).abs() + (a.ty - b.ty).abs(), 1);
+            assert!(board.get_tile(b).walkable());
+        }
+
+        // The far side of a box is further away along the track than the crow flies.
+        let inside = TilePoint::new(1, 1);
+        assert!(!board.get_tile(&inside).walkable());
+        assert_eq!(board.shortest_path(&from, &inside), None);
+        let distances = board.distance_map(&TilePoint::new(0, 3));
+        assert_eq!(distances[3][0], Some(0));
+        assert_eq!(distances[1][1], None);
+        assert_eq!(distances[3][6], Some(12));
+
+        let state = super::State::try_new(&Amidar::default()).unwrap();
+        let args = serde_json::to_value(&(from.clone(), to.clone())).unwrap();
+        assert_eq!(
+            state.query_json("shortest_path", &args).unwrap(),
+            serde_json::to_string(&Some(path)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_set_query_json() {
+        let config = Amidar {
+            chase_enemy_speed: 4,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        state
+            .set_query_json("set_player_tile", &serde_json::json!({"tx": 0, "ty": 6}))
+            .unwrap();
+        assert_eq!(state.state.player.position.to_tile(), TilePoint::new(0, 6));
+        assert_eq!(
+            state.set_query_json("set_player_tile", &serde_json::json!({"tx": 1, "ty": 1})),
+            Err(QueryError::BadInputArg)
+        );
+        state
+            .set_query_json(
+                "set_enemy_tile",
+                &serde_json::json!([2, {"tx": 6, "ty": 0}]),
+            )
+            .unwrap();
+        assert_eq!(
+            state.state.enemies[2].position.to_tile(),
+            TilePoint::new(6, 0)
+        );
+        assert_eq!(
+            state.set_query_json(
+                "set_enemy_tile",
+                &serde_json::json!([99, {"tx": 6, "ty": 0}])
+            ),
+            Err(QueryError::BadInputArg)
+        );
+
+        state
+            .set_query_json("set_score", &serde_json::json!(1234))
+            .unwrap();
+        state
+            .set_query_json("set_lives", &serde_json::json!(1))
+            .unwrap();
+        state
+            .set_query_json("set_chase_timer", &serde_json::json!(90))
+            .unwrap();
+        assert_eq!((state.score(), state.lives()), (1234, 1));
+        assert_eq!(state.state.chase_timer, 90);
+        // Starting and stopping chase mode this way works just like the real thing.
+        let speeds = |state: &super::State| -> Vec<i32> {
+            state.state.enemies.iter().map(|e| e.speed).collect()
+        };
+        let chase_speeds = speeds(&state);
+        assert!(chase_speeds
+            .iter()
+            .all(|&s| s == state.enemy_speed_when(true)));
+        state.state.enemies[0].caught = true;
+        state.state.chase_catches = 1;
+        state
+            .set_query_json("set_chase_timer", &serde_json::json!(0))
+            .unwrap();
+        assert!(!state.state.enemies[0].caught);
+        assert_eq!(state.state.chase_catches, 0);
+        assert_ne!(speeds(&state), chase_speeds);
+        assert!(speeds(&state)
+            .iter()
+            .all(|&s| s == state.enemy_speed_when(false)));
+
+        // Painting the whole outline of the top-left box fills it in; unpainting a side empties it again.
+        let before = state.num_tiles_unpainted();
+        let outline: Vec<TilePoint> = (0..=6)
+            .flat_map(|i| {
+                vec![
+                    TilePoint::new(i, 0),
+                    TilePoint::new(i, 6),
+                    TilePoint::new(0, i),
+                    TilePoint::new(6, i),
+                ]
+            })
+            .collect();
+        for tile in &outline {
+            state
+                .set_query_json("paint_tile", &serde_json::to_value(tile).unwrap())
+                .unwrap();
+        }
+        assert!(state.num_tiles_unpainted() < before);
+        let top_left = |state: &super::State| {
+            state
+                .state
+                .board
+                .boxes
+                .iter()
+                .find(|b| b.top_left == TilePoint::new(0, 0))
+                .unwrap()
+                .painted
+        };
+        assert!(top_left(&state));
+        state
+            .set_query_json(
+                "unpaint_tile",
+                &serde_json::to_value(TilePoint::new(0, 3)).unwrap(),
+            )
+            .unwrap();
+        assert!(!top_left(&state));
+        assert_eq!(state.score(), 1234);
+
+        assert_eq!(
+            state.set_query_json("set_weather", &serde_json::Value::Null),
+            Err(QueryError::NoSuchQuery)
+        );
+    }
+
+    #[test]
+    fn test_penalty_tiles() {
+        // The player starts going up the right-hand edge of the default board; make part of it a penalty.
+        let mut board = Amidar::default().board;
+        for line in board.iter_mut().take(12).skip(8) {
+            line.replace_range(31..32, "x");
+        }
+        let config = Amidar {
+            board,
+            enemies: Vec::new(),
+            penalty_tile_cost: 7,
+            penalty_slow_frames: 20,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        assert_eq!(
+            state.state.board.get_tile(&TilePoint::new(31, 9)),
+            Tile::Penalty
+        );
+        // Penalty tiles don't count towards clearing the level.
+        assert_eq!(
+            state.num_tiles_unpainted(),
+            super::State::try_new(&Amidar::default())
+                .unwrap()
+                .num_tiles_unpainted()
+                - 4
+        );
+
+        let mut score_change = None;
+        for input in parse_script("U×150").unwrap() {
+            let before = state.score();
+            state.update_mut(input);
+            if state.state.board.get_tile(&TilePoint::new(31, 9)) == Tile::Painted {
+                score_change = Some(state.score() - before);
+                break;
+            }
+        }
+        // One point for the vertical segment, less four penalty tiles.
+        assert_eq!(score_change, Some(1 - 4 * 7));
+        assert_eq!(state.state.penalty_slow_timer, 20);
+        state.update_mut(Input::default());
+        assert_eq!(state.state.player.speed, config.player_speed / 2);
+    }
+
+    #[test]
+    fn test_enemy_releases() {
+        let start = Amidar::default().player_start;
+        let wanderer = MovementAI::EnemyRandomMvmt {
+            start: TilePoint::new(0, 0),
+            start_dir: Direction::Right,
+            dir: Direction::Right,
+        };
+        let config = Amidar {
+            enemies: vec![wanderer.clone(), wanderer.clone(), wanderer],
+            enemy_releases: vec![
+                EnemyRelease {
+                    after_frames: None,
+                    after_tiles_painted: None,
+                },
+                EnemyRelease {
+                    after_frames: Some(30),
+                    after_tiles_painted: None,
+                },
+                EnemyRelease {
+                    after_frames: Some(10_000),
+                    after_tiles_painted: Some(1),
+                },
+            ],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let released = |state: &super::State| -> Vec<bool> {
+            state.state.enemies.iter().map(|e| e.released).collect()
+        };
+        assert_eq!(released(&state), vec![true, false, false]);
+
+        // Held enemies stay put and are harmless, even on top of the player.
+        let waiting = state.state.enemies[1].position.to_tile();
+        state.state.enemies[2].position = start.to_world();
+        for _ in 0..29 {
+            state.update_mut(Input::default());
+        }
+        assert_eq!(state.state.enemies[1].position.to_tile(), waiting);
+        assert_eq!(state.lives(), config.start_lives);
+        state.update_mut(Input::default());
+        assert_eq!(released(&state), vec![true, true, false]);
+
+        // Painting (the first segment up from the start) releases the last one.
+        state.state.enemies[2].position = TilePoint::new(0, 30).to_world();
+        for input in parse_script("U×100").unwrap() {
+            state.update_mut(input);
+            if state.state.enemies[2].released {
+                break;
+            }
+        }
+        assert_eq!(released(&state), vec![true, true, true]);
+    }
+
+    #[test]
+    fn test_bonus_gates() {
+        let gate = BonusGate {
+            tile: TilePoint::new(31, 6),
+            max_bonus: 1000,
+            decay_frames: 400,
+        };
+        assert_eq!(gate.bonus_at(0), 1000);
+        assert_eq!(gate.bonus_at(100), 750);
+        assert_eq!(gate.bonus_at(400), 0);
+        assert_eq!(gate.bonus_at(5000), 0);
+
+        let config = Amidar {
+            enemies: Vec::new(),
+            bonus_gates: vec![gate.clone()],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let drawn = |state: &super::State| {
+            state.draw().iter().any(|d| match d {
+                Drawable::Rectangle { color, .. } => *color == config.gate_color,
+                _ => false,
+            })
+        };
+        assert!(drawn(&state));
+
+        let mut frames = 0;
+        for input in parse_script("U×200").unwrap() {
+            state.update_mut(input);
+            if state.state.gates_reached[0] {
+                frames = state.state.level_frames;
+                break;
+            }
+        }
+        assert!(frames > 0);
+        let expected = gate.bonus_at(frames);
+        assert_eq!(
+            state.state.events.last(),
+            Some(&GameEvent::GateReached {
+                gate: 0,
+                bonus: expected
+            })
+        );
+        assert!(!drawn(&state));
+        assert_eq!(
+            state
+                .query_json("bonus_gates", &serde_json::Value::Null)
+                .unwrap(),
+            "[null]"
+        );
+    }
+
+    #[test]
+    fn test_unpainter() {
+        let wanderer = MovementAI::EnemyRandomMvmt {
+            start: TilePoint::new(0, 0),
+            start_dir: Direction::Right,
+            dir: Direction::Right,
+        };
+        let config = Amidar {
+            enemies: vec![wanderer],
+            unpainter: Some(Unpainter {
+                enemy: 0,
+                every_tiles: 2,
+            }),
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        // Paint everything but the far corner, so there's something to undo without clearing the level.
+        state.state.board.edit_tiles(|tiles| {
+            for tile in tiles.iter_mut() {
+                if tile.walkable() {
+                    *tile = Tile::Painted;
+                }
+            }
+        });
+        state
+            .state
+            .board
+            .set_tile(&TilePoint::new(31, 30), Tile::Unpainted);
+        let painted = state.num_tiles_unpainted();
+        let mut unpainted = Vec::new();
+        for _ in 0..100 {
+            state.update_mut(Input::default());
+            for event in &state.state.events {
+                if let GameEvent::TileUnpainted { enemy, tile } = event {
+                    assert_eq!(*enemy, 0);
+                    unpainted.push(tile.clone());
+                }
+            }
+        }
+        assert!(!unpainted.is_empty());
+        assert_eq!(state.num_tiles_unpainted(), painted + unpainted.len());
+        for tile in &unpainted {
+            assert_eq!(state.state.board.get_tile(tile), Tile::Unpainted);
+        }
+    }
+
+    #[test]
+    fn test_level_board_order() {
+        // A plain 11x11 grid as a second layout.
+        let grid: Vec<String> = (0..11)
+            .map(|ty| {
+                (0..11)
+                    .map(|tx| if tx % 5 == 0 || ty % 5 == 0 { '=' } else { ' ' })
+                    .collect()
+            })
+            .collect();
+        let config = Amidar {
+            boards: vec![grid],
+            level_board_order: vec![0, 1, 1],
+            enemies: Vec::new(),
+            player_start: TilePoint::new(0, 0),
+            default_board_bugs: false,
+            ..Amidar::default()
+        };
+        assert_eq!(config.board_for_level(1), config.board);
+        assert_eq!(config.board_for_level(2), config.boards[0]);
+        assert_eq!(config.board_for_level(4), config.board);
+        assert_eq!(config.screen_size(), screen::GAME_SIZE);
+
+        let mut state = super::State::try_new(&config).unwrap();
+        let clear = |state: &mut super::State| {
+            state.state.board.edit_tiles(|tiles| {
+                for tile in tiles.iter_mut() {
+                    if tile.walkable() {
+                        *tile = Tile::Painted;
+                    }
+                }
+            });
+            state.update_mut(Input::default());
+        };
+        let widths: Vec<u32> = (0..4)
+            .map(|_| {
+                clear(&mut state);
+                state.state.board.width
+            })
+            .collect();
+        assert_eq!(state.level(), 5);
+        assert_eq!(widths, vec![11, 11, 32, 11]);
+
+        let bad = Amidar {
+            level_board_order: vec![2],
+            ..config
+        };
+        assert!(super::State::try_new(&bad).is_err());
+    }
+
+    #[test]
+    fn test_board_generator() {
+        let generator = BoardGenerator {
+            width: 21,
+            height: 19,
+            symmetry: Symmetry::Vertical,
+            seed: 5,
+        };
+        let lines = generator.generate(1);
+        assert_eq!(lines.len(), 19);
+        for line in &lines {
+            assert_eq!(line.chars().count(), 21);
+            let mirrored: String = line.chars().rev().collect();
+            assert_eq!(*line, mirrored);
+        }
+        assert_eq!(generator.generate(1), lines);
+        assert_ne!(generator.generate(2), lines);
+        let board = Board::try_new(&lines).unwrap();
+        assert!(board.lint(&generator.player_start()).problems().is_empty());
+        assert_eq!(board.chase_junctions.len(), 4);
+
+        for seed in 0..20 {
+            let generator = BoardGenerator {
+                width: 15 + seed,
+                height: 12 + seed,
+                symmetry: Symmetry::None,
+                seed,
+            };
+            let board = Board::try_new(&generator.generate(1)).unwrap();
+            let report = board.lint(&generator.player_start());
+            assert!(report.problems().is_empty(), "{:?}", report.problems());
+        }
+
+        let config = Amidar {
+            board_generator: Some(generator.clone()),
+            enemies: vec![MovementAI::EnemyRandomMvmt {
+                start: TilePoint::new(0, 0),
+                start_dir: Direction::Right,
+                dir: Direction::Right,
+            }],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        assert_eq!(state.state.board.width, 21);
+        assert_eq!(state.config.player_start, generator.player_start());
+        for input in parse_script("U×50, L×50, D×50").unwrap() {
+            state.update_mut(input);
+        }
+    }
+
+    #[test]
+    fn test_respawn_grace() {
+        // An enemy that starts on the player's start kills them at once; with grace, only once.
+        let start = Amidar::default().player_start;
+        let config = Amidar {
+            enemies: vec![MovementAI::EnemyRandomMvmt {
+                start: start.clone(),
+                start_dir: Direction::Right,
+                dir: Direction::Right,
+            }],
+            respawn_grace_frames: 40,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        state.update_mut(Input::default());
+        assert_eq!(state.lives(), config.start_lives - 1);
+        assert_eq!(state.state.grace_timer, 40);
+
+        let mut hidden = 0;
+        for _ in 0..39 {
+            state.update_mut(Input::default());
+            // The enemy is back on the start tile after the reset, and can't hurt us yet.
+            assert_eq!(state.lives(), config.start_lives - 1);
+            if !state.player_visible() {
+                hidden += 1;
+            }
+        }
+        assert!(hidden > 0);
+        assert_eq!(state.state.grace_timer, 1);
+        state.state.enemies[0].position = state.state.player.position.clone();
+        state.update_mut(Input::default());
+        assert_eq!(state.lives(), config.start_lives - 2);
+    }
+
+    #[test]
+    fn test_death_pause() {
+        let config = Amidar {
+            enemies: vec![MovementAI::EnemyRandomMvmt {
+                start: TilePoint::new(0, 0),
+                start_dir: Direction::Right,
+                dir: Direction::Right,
+            }],
+            death_pause_frames: 8,
+            respawn_pause_frames: 5,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let up = AleAction::UP.to_input();
+        for _ in 0..50 {
+            state.update_mut(up);
+        }
+        let caught_at = state.state.player.position.clone();
+        state.state.enemies[0].position = caught_at.clone();
+        state.update_mut(up);
+        assert_ne!(caught_at.to_tile(), config.player_start);
+        assert_eq!(state.lives(), config.start_lives - 1);
+        assert_eq!(state.state.phase, GamePhase::Dying);
+        assert_eq!(state.state.phase_timer, 8);
+
+        // Everything stays put while the player blinks, whatever is pressed.
+        let mut hidden = 0;
+        for _ in 0..7 {
+            state.update_mut(up);
+            assert_eq!(state.state.player.position.to_tile(), caught_at.to_tile());
+            assert_eq!(state.state.reward, 0.0);
+            if !state.player_visible() {
+                hidden += 1;
+            }
+        }
+        assert!(hidden > 0);
+        state.update_mut(up);
+        assert_eq!(state.state.phase, GamePhase::Ready);
+        assert_eq!(state.state.player.position.to_tile(), config.player_start);
+
+        // Then the game holds still at the start for a while longer.
+        for _ in 0..5 {
+            state.update_mut(up);
+            assert_eq!(state.state.player.position.to_tile(), config.player_start);
+        }
+        assert_eq!(state.state.phase, GamePhase::Playing);
+        for _ in 0..10 {
+            state.update_mut(up);
+        }
+        assert_ne!(state.state.player.position.to_tile(), config.player_start);
+        assert_eq!(state.lives(), config.start_lives - 1);
+    }
+
+    #[test]
+    fn test_try_new_game() {
+        use toybox_core::Simulation;
+        let mut config = Amidar::default();
+        let state = config.try_new_game().unwrap();
+        assert_eq!(state.try_to_json().unwrap(), state.to_json());
+        assert_eq!(config.try_to_json().unwrap(), config.to_json());
+
+        let mut broken = Amidar {
+            board: vec!["=====".to_owned(), "= ? =".to_owned()],
+            ..Amidar::default()
+        };
+        match broken.try_new_game() {
+            Err(SimulationError::InvalidConfig(_)) => {}
+            other => panic!("expected an invalid config, got {:?}", other.map(|_| ())),
+        }
+
+        // Bad enemy indices from Python are errors, not panics.
+        for query in &["enemy_tile", "enemy_caught"] {
+            assert!(state.query_json(query, &serde_json::json!(0)).is_ok());
+            for bad in &[serde_json::json!(99), serde_json::json!("0")] {
+                match state.query_json(query, bad) {
+                    Err(QueryError::BadInputArg) => {}
+                    other => panic!(
+                        "{} {}: expected a bad argument, got {:?}",
+                        query, bad, other
+                    ),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_game_phases() {
+        let config = Amidar {
+            enemies: Vec::new(),
+            ready_frames: 3,
+            level_complete_frames: 4,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let phase = |state: &super::State| -> GamePhase {
+            serde_json::from_str(&state.query_json("phase", &serde_json::Value::Null).unwrap())
+                .unwrap()
+        };
+        let up = AleAction::UP.to_input();
+        assert_eq!(phase(&state), GamePhase::Ready);
+        for _ in 0..3 {
+            state.update_mut(up);
+            assert_eq!(state.state.player.position.to_tile(), config.player_start);
+        }
+        assert_eq!(phase(&state), GamePhase::Playing);
+        state.update_mut(up);
+        assert_eq!(state.state.level_frames, 1);
+
+        state.state.board.edit_tiles(|tiles| {
+            for tile in tiles.iter_mut() {
+                if tile.walkable() {
+                    *tile = Tile::Painted;
+                }
+            }
+        });
+        state.update_mut(up);
+        assert_eq!(phase(&state), GamePhase::LevelComplete);
+        assert_eq!(state.level(), 1);
+        for _ in 0..3 {
+            state.update_mut(up);
+            assert_eq!(state.level(), 1);
+        }
+        state.update_mut(up);
+        assert_eq!(state.level(), 2);
+        assert_eq!(phase(&state), GamePhase::Ready);
+        assert_eq!(state.state.level_frames, 0);
+        assert!(!state.state.board.board_complete());
+
+        state
+            .set_query_json("set_lives", &serde_json::json!(-1))
+            .unwrap();
+        assert_eq!(phase(&state), GamePhase::GameOver);
+        assert!(state.game_over());
+    }
+
+    #[test]
+    fn test_rollback_score_on_death() {
+        for &rollback in &[false, true] {
+            let config = Amidar {
+                enemies: vec![MovementAI::EnemyRandomMvmt {
+                    start: TilePoint::new(0, 0),
+                    start_dir: Direction::Right,
+                    dir: Direction::Right,
+                }],
+                rollback_score_on_death: rollback,
+                ..Amidar::default()
+            };
+            let mut state = super::State::try_new(&config).unwrap();
+            let up = AleAction::UP.to_input();
+            // Walk up until the next frame scores, then have an enemy waiting where that frame ends.
+            let scored = loop {
+                let mut next = state.clone();
+                next.update_mut(up);
+                if next.score() > state.score() {
+                    break next;
+                }
+                state = next;
+            };
+            let before = state.score();
+            state.state.enemies[0].position = scored.state.player.position.clone();
+            state.update_mut(up);
+            assert_eq!(state.lives(), config.start_lives - 1);
+            let expected = if rollback { before } else { scored.score() };
+            assert_eq!(state.score(), expected);
+        }
+    }
+
+    #[test]
+    fn test_reward_breakdown() {
+        let config = Amidar {
+            enemies: vec![MovementAI::EnemyRandomMvmt {
+                start: TilePoint::new(0, 0),
+                start_dir: Direction::Right,
+                dir: Direction::Right,
+            }],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let breakdown = |state: &super::State| -> RewardBreakdown {
+            serde_json::from_str(
+                &state
+                    .query_json("last_reward_breakdown", &serde_json::Value::Null)
+                    .unwrap(),
+            )
+            .unwrap()
+        };
+        let mut painted = 0;
+        for input in parse_script("U×100, L×100").unwrap() {
+            let before = state.score();
+            state.update_mut(input);
+            let parts = breakdown(&state);
+            assert_eq!(parts.total(), state.score() - before);
+            painted += parts.painting;
+            if parts.painting > 0 {
+                assert!(state.state.last_board_update.is_some());
+            }
+        }
+        assert!(painted > 0);
+        assert_eq!(painted, state.score());
+
+        // Catch the enemy in chase mode.
+        state.state.chase_timer = 100;
+        state.state.enemies[0].position = state.state.player.position.clone();
+        state.update_mut(Input::default());
+        assert_eq!(breakdown(&state).enemies, config.chase_score_bonus);
+        assert_eq!(breakdown(&state).painting, 0);
+    }
+
+    #[test]
+    fn test_painting_events() {
+        let config = Amidar {
+            enemies: Vec::new(),
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let events = |state: &super::State| -> Vec<GameEvent> {
+            serde_json::from_str(
+                &state
+                    .query_json("events", &serde_json::Value::Null)
+                    .unwrap(),
+            )
+            .unwrap()
+        };
+        let mut segments = Vec::new();
+        for input in parse_script("U×100, L×100").unwrap() {
+            state.update_mut(input);
+            for event in events(&state) {
+                if let GameEvent::SegmentPainted { from, to } = event {
+                    assert_eq!(state.state.board.get_tile(&from), Tile::Painted);
+                    assert_eq!(state.state.board.get_tile(&to), Tile::Painted);
+                    segments.push((from, to));
+                }
+            }
+        }
+        assert!(segments.len() > 1);
+        // Each segment picks up where the one before ended.
+        for pair in segments.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+        }
+    }
+
+    #[test]
+    fn test_load_legacy_json() {
+        use toybox_core::Simulation;
+        // Written by the game before any of the newer config or state fields existed.
+        let config_json = include_str!("resources/amidar_legacy_config.json");
+        let state_json = include_str!("resources/amidar_legacy_state.json");
+
+        let sim = Amidar::default().from_json(config_json).unwrap();
+        let config: Amidar = serde_json::from_str(&sim.to_json()).unwrap();
+        assert_eq!(config.chase_time, GameTime::Frames(300));
+        assert_eq!(config.jump_time, GameTime::Frames(75));
+        assert_eq!(
+            config.frames_per_second,
+            Amidar::default().frames_per_second
+        );
+
+        let mut state = sim.new_state_from_json(state_json).unwrap();
+        let core: StateCore = serde_json::from_str(&state.to_json()).unwrap();
+        assert_eq!(core.phase, GamePhase::Playing);
+        assert!(core.enemies.iter().all(|e| e.released));
+        assert_eq!(core.kills_by_enemy.len(), config.enemies.len());
+        let fresh = super::State::try_new(&config).unwrap();
+        assert_eq!(core.level_tiles_to_paint, fresh.state.level_tiles_to_paint);
+        for _ in 0..100 {
+            state.update_mut(AleAction::UP.to_input());
+        }
+        assert!(state.score() > 0);
+    }
+
+    #[test]
+    fn test_validate_config() {
+        use toybox_core::Simulation;
+        let config = Amidar::default();
+        assert_eq!(config.validate(), Ok(()));
+        assert!(config.from_json(&config.to_json()).is_ok());
+
+        let ragged = Amidar {
+            board: vec!["=====".to_string(), "= =".to_string(), "=====".to_string()],
+            player_start: TilePoint::new(1, 1),
+            ..Amidar::default()
+        };
+        let board = Board::try_new(&ragged.board).unwrap();
+        let errors = board.validate(&ragged.player_start).unwrap_err();
+        assert_eq!(
+            errors[0],
+            BoardProblem::RaggedRows {
+                rows: vec![1],
+                width: 5
+            }
+        );
+        assert_eq!(
+            errors[1],
+            BoardProblem::StartNotWalkable {
+                start: TilePoint::new(1, 1)
+            }
+        );
+        // Nothing is reachable from a start that isn't on the track.
+        assert!(matches!(errors[2], BoardProblem::Unreachable { .. }));
+        let err = Amidar::default()
+            .from_json(&ragged.to_json())
+            .err()
+            .expect("a ragged board should not load");
+        assert!(err.to_string().contains("Rows [1] are not 5 tiles wide."));
+    }
+
+    #[test]
+    fn test_themes() {
+        use toybox_core::Simulation;
+
+        let classic = Theme::Classic.config();
+        let default = Amidar::default();
+        assert_eq!(classic.colors(), default.colors());
+
+        for &theme in &[Theme::Classic, Theme::Mono, Theme::Neon, Theme::Paper] {
+            let config = theme.config();
+            let grays: HashSet<u8> = config.colors().iter().map(|c| c.grayscale_byte()).collect();
+            assert_eq!(grays.len(), config.colors().len(), "{:?}", theme);
+        }
+
+        // Colors left out of the JSON come from the theme; colors written out win.
+        let mut json: serde_json::Value = serde_json::from_str(&default.to_json()).unwrap();
+        let fields = json.as_object_mut().unwrap();
+        for field in THEME_COLOR_FIELDS.iter() {
+            fields.remove(*field);
+        }
+        fields.insert("theme".to_owned(), serde_json::json!("Paper"));
+        fields.insert(
+            "player_color".to_owned(),
+            serde_json::to_value(Color::rgb(1, 2, 3)).unwrap(),
+        );
+        let loaded: Amidar =
+            serde_json::from_str(&default.from_json(&json.to_string()).unwrap().to_json()).unwrap();
+        let paper = Theme::Paper.config();
+        assert_eq!(loaded.theme, Some(Theme::Paper));
+        assert_eq!(loaded.bg_color, paper.bg_color);
+        assert_eq!(loaded.gate_color, paper.gate_color);
+        assert_eq!(loaded.player_color, Color::rgb(1, 2, 3));
+
+        // Without a theme, colors left out are the default ones, like any other field.
+        json.as_object_mut().unwrap().remove("theme");
+        let loaded: Amidar =
+            serde_json::from_str(&default.from_json(&json.to_string()).unwrap().to_json()).unwrap();
+        assert_eq!(loaded.bg_color, default.bg_color);
+        assert_eq!(loaded.player_color, Color::rgb(1, 2, 3));
+    }
+
+    #[test]
+    fn test_board_tensor() {
+        let config = Amidar::default();
+        let mut state = super::State::try_new(&config).unwrap();
+        let channels = state.board_tensor_channels();
+        assert_eq!(channels.len(), 8 + state.state.enemies.len());
+        assert_eq!(channels[8], "enemy_0");
+
+        let tensor = state.board_tensor();
+        assert_eq!(tensor.len(), state.state.board.height as usize);
+        assert_eq!(tensor[0].len(), state.state.board.width as usize);
+        assert!(tensor
+            .iter()
+            .flatten()
+            .all(|cell| cell.len() == channels.len()));
+        let count = |tensor: &Vec<Vec<Vec<u8>>>, channel: usize| -> usize {
+            tensor
+                .iter()
+                .flatten()
+                .filter(|cell| cell[channel] == 1)
+                .count()
+        };
+        assert_eq!(count(&tensor, 1), state.num_tiles_unpainted());
+        assert_eq!(count(&tensor, 5), 1);
+        assert!(count(&tensor, 6) >= 1);
+        assert_eq!(count(&tensor, 7), 0);
+        let start = &config.player_start;
+        assert_eq!(tensor[start.ty as usize][start.tx as usize][5], 1);
+        for (i, enemy) in state.state.enemies.iter().enumerate() {
+            let tile = enemy.position.to_tile();
+            assert_eq!(tensor[tile.ty as usize][tile.tx as usize][8 + i], 1);
+            assert_eq!(count(&tensor, 8 + i), 1);
+        }
+
+        // Every uncaught enemy is catchable in chase mode.
+        state.state.chase_timer = 10;
+        assert_eq!(count(&state.board_tensor(), 7), count(&tensor, 6));
+
+        let queried: Vec<Vec<Vec<u8>>> = serde_json::from_str(
+            &state
+                .query_json("board_tensor", &serde_json::Value::Null)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(queried, state.board_tensor());
+    }
+
+    #[test]
+    fn test_frame_skip_and_sticky_actions() {
+        let left = AleAction::LEFT.to_input();
+        let mut single = super::State::try_new(&Amidar::default()).unwrap();
+        let mut skipping = super::State::try_new(&Amidar {
+            frame_skip: 4,
+            ..Amidar::default()
+        })
+        .unwrap();
+        let mut reward = 0.0;
+        for _ in 0..40 {
+            single.update_mut(left);
+            reward += single.state.reward;
+        }
+        for _ in 0..10 {
+            skipping.update_mut(left);
+        }
+        assert_eq!(skipping.state.level_frames, single.state.level_frames);
+        assert_eq!(skipping.state.score, single.state.score);
+        assert_eq!(
+            skipping.state.player.position.to_tile(),
+            single.state.player.position.to_tile()
+        );
+
+        // The rewards of skipped frames are not lost.
+        let mut skipping = super::State::try_new(&Amidar {
+            frame_skip: 40,
+            ..Amidar::default()
+        })
+        .unwrap();
+        skipping.update_mut(left);
+        assert_eq!(skipping.state.reward, reward);
+
+        // Always repeating means the first (empty) buttons stick forever.
+        let mut stuck = super::State::try_new(&Amidar {
+            action_repeat_probability: 1.0,
+            ..Amidar::default()
+        })
+        .unwrap();
+        let start = stuck.state.player.position.to_tile();
+        for _ in 0..20 {
+            stuck.update_mut(left);
+        }
+        assert_eq!(stuck.state.player.position.to_tile(), start);
+
+        let mut sticky = super::State::try_new(&Amidar {
+            action_repeat_probability: 0.25,
+            audit_rng: true,
+            ..Amidar::default()
+        })
+        .unwrap();
+        sticky.update_mut(left);
+        assert!(sticky
+            .state
+            .rng_audit
+            .iter()
+            .any(|draw| draw.purpose == RngPurpose::StickyAction));
+
+        for bad in &[
+            Amidar {
+                frame_skip: 0,
+                ..Amidar::default()
+            },
+            Amidar {
+                action_repeat_probability: 1.5,
+                ..Amidar::default()
+            },
+        ] {
+            assert!(super::State::try_new(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn test_score_popups() {
+        for &frames in &[0, 5] {
+            let config = Amidar {
+                score_popup_frames: frames,
+                enemies: vec![MovementAI::EnemyRandomMvmt {
+                    start: TilePoint::new(0, 0),
+                    start_dir: Direction::Right,
+                    dir: Direction::Right,
+                }],
+                ..Amidar::default()
+            };
+            let mut state = super::State::try_new(&config).unwrap();
+            let plain_draw = state.draw().len();
+            state.state.chase_timer = 100;
+            state.state.enemies[0].position = state.state.player.position.clone();
+            state.update_mut(Input::default());
+            if frames == 0 {
+                assert!(state.state.score_popups.is_empty());
+                continue;
+            }
+            let popup = state.state.score_popups[0].clone();
+            assert_eq!(popup.points, config.chase_score_bonus);
+            assert_eq!(popup.frames_left, 5);
+            assert_eq!(
+                popup.position.to_tile(),
+                state.state.enemies[0].position.to_tile()
+            );
+            assert!(state.draw().len() > plain_draw);
+
+            for _ in 0..5 {
+                state.update_mut(Input::default());
+            }
+            assert!(state.state.score_popups.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_savestate_bytes() {
+        let config = Amidar::default();
+        let mut state = super::State::try_new(&config).unwrap();
+        for input in parse_script("L×20, U×15, R×10").unwrap() {
+            state.update_mut(input);
+        }
+        let bytes = state.to_bytes();
+        assert!(bytes.len() < state.to_json().len());
+
+        let mut loaded = super::State::from_bytes(&config, &bytes).unwrap();
+        assert_eq!(loaded.to_json(), state.to_json());
+        for input in parse_script("D×30").unwrap() {
+            state.update_mut(input);
+            loaded.update_mut(input);
+        }
+        assert_eq!(loaded.to_json(), state.to_json());
+
+        let mut stale = bytes.clone();
+        stale[4] = stale[4].wrapping_add(1);
+        assert!(super::State::from_bytes(&config, &stale).is_err());
+        assert!(super::State::from_bytes(&config, b"{}").is_err());
+        assert!(super::State::from_bytes(&config, &bytes[..bytes.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn test_board_copy_on_write() {
+        let board = Board::fast_new();
+        let mut copy = board.clone();
+        assert!(Arc::ptr_eq(&board.tiles, &copy.tiles));
+        assert!(Arc::ptr_eq(&board.boxes, &copy.boxes));
+
+        // The first change to a shared board copies it.
+        let tile = TilePoint::new(1, 0);
+        assert!(copy.paint(&tile));
+        assert!(!Arc::ptr_eq(&board.tiles, &copy.tiles));
+        assert_eq!(board.get_tile(&tile), Tile::Unpainted);
+        assert_eq!(copy.get_tile(&tile), Tile::Painted);
+        // Painting a painted tile changes nothing, so nothing is copied.
+        let mut again = copy.clone();
+        assert!(!again.paint(&tile));
+        assert!(Arc::ptr_eq(&again.tiles, &copy.tiles));
+        assert!(Arc::ptr_eq(&board.junctions, &again.junctions));
+    }
+
+    #[test]
+    fn test_enemies_per_level() {
+        let config = Amidar {
+            enemies_per_level: vec![2, 2, 4],
+            ..Amidar::default()
+        };
+        assert_eq!(config.enemies_for_level(1), 2);
+        assert_eq!(config.enemies_for_level(3), 4);
+        assert_eq!(config.enemies_for_level(9), 4);
+        assert_eq!(Amidar::default().enemies_for_level(1), 5);
+        let all = Amidar {
+            enemies_per_level: vec![99],
+            ..Amidar::default()
+        };
+        assert_eq!(all.enemies_for_level(1), 5);
+
+        let mut state = super::State::try_new(&config).unwrap();
+        assert_eq!(state.state.enemies.len(), 2);
+        let clear = |state: &mut super::State| {
+            state.state.board.edit_tiles(|tiles| {
+                for tile in tiles.iter_mut() {
+                    if tile.walkable() {
+                        *tile = Tile::Painted;
+                    }
+                }
+            });
+            state.update_mut(Input::default());
+        };
+        clear(&mut state);
+        assert_eq!(state.state.level, 2);
+        assert_eq!(state.state.enemies.len(), 2);
+        clear(&mut state);
+        assert_eq!(state.state.level, 3);
+        assert_eq!(state.state.enemies.len(), 4);
+
+        // The newcomers are the next enemies in the config, starting where they always do.
+        let full = super::State::try_new(&Amidar::default()).unwrap();
+        for (enemy, expected) in state.state.enemies.iter().zip(full.state.enemies.iter()) {
+            assert_eq!(enemy.ai, expected.ai);
+            assert_eq!(enemy.position.to_tile(), expected.position.to_tile());
+        }
+        for _ in 0..10 {
+            state.update_mut(Input::default());
+        }
+        let tiles: Vec<(i32, i32)> = serde_json::from_str(
+            &state
+                .query_json("enemy_tiles", &serde_json::Value::Null)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(tiles.len(), 4);
+    }
+
+    #[test]
+    fn test_board_json_rows() {
+        let board = Board::fast_new();
+        let json = serde_json::to_value(&board).unwrap();
+        let rows = json["tiles"].as_array().unwrap();
+        assert_eq!(rows.len(), board.height as usize);
+        assert!(rows
+            .iter()
+            .all(|row| row.as_array().unwrap().len() == board.width as usize));
+
+        let loaded: Board = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(loaded.tiles, board.tiles);
+        assert!(loaded.ragged_rows.is_empty());
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), json);
+
+        // Rows that don't fit are padded or cut to the board's width.
+        let ragged =
+            Board::try_new(&["=====".to_owned(), "=  ==".to_owned(), "=".to_owned()]).unwrap();
+        assert_eq!(ragged.ragged_rows, vec![2]);
+        assert_eq!(ragged.tiles.len(), 15);
+        assert_eq!(ragged.get_tile(&TilePoint::new(1, 2)), Tile::Empty);
+        assert_eq!(ragged.rows().count(), 3);
+    }
+
+    #[test]
+    fn test_latch_directions() {
+        let up = AleAction::UP.to_input();
+        let mut held = super::State::try_new(&Amidar::default()).unwrap();
+        let mut latched = super::State::try_new(&Amidar {
+            latch_directions: true,
+            ..Amidar::default()
+        })
+        .unwrap();
+
+        // Tapping up once keeps the latched player going, like holding it.
+        latched.update_mut(up);
+        for _ in 0..20 {
+            latched.update_mut(Input::default());
+        }
+        for _ in 0..21 {
+            held.update_mut(up);
+        }
+        assert_eq!(
+            latched.state.player.position.to_tile(),
+            held.state.player.position.to_tile()
+        );
+        assert_eq!(latched.state.latched_direction, Some(Direction::Up));
+
+        // Without the latch, letting go stops the player once they reach the next tile.
+        let mut tapped = super::State::try_new(&Amidar::default()).unwrap();
+        tapped.update_mut(up);
+        for _ in 0..20 {
+            tapped.update_mut(Input::default());
+        }
+        assert!(
+            tapped.state.player.position.to_tile().ty > latched.state.player.position.to_tile().ty
+        );
+        assert_eq!(tapped.state.latched_direction, None);
+
+        // A new life starts with the joystick centered.
+        latched.reset();
+        assert_eq!(latched.state.latched_direction, None);
+    }
+
+    #[test]
+    fn test_reverse_mid_step() {
+        let up = AleAction::UP.to_input();
+        let down = AleAction::DOWN.to_input();
+        let mut normal = super::State::try_new(&Amidar::default()).unwrap();
+        let mut reversing = super::State::try_new(&Amidar {
+            reverse_mid_step: true,
+            ..Amidar::default()
+        })
+        .unwrap();
+        let start = reversing.state.player.position.to_tile();
+        let above = TilePoint::new(start.tx, start.ty - 1);
+
+        for state in &mut [&mut normal, &mut reversing] {
+            state.update_mut(up);
+            state.update_mut(up);
+            assert_eq!(state.state.player.step, Some(above.clone()));
+            state.update_mut(down);
+        }
+        // Only the arcade-style player turns back before reaching the next tile.
+        assert_eq!(normal.state.player.step, Some(above));
+        assert_eq!(reversing.state.player.step, Some(start.clone()));
+        assert_eq!(reversing.state.player.heading, Some(Direction::Down));
+        for _ in 0..10 {
+            reversing.update_mut(Input::default());
+        }
+        assert_eq!(reversing.state.player.position.to_tile(), start);
+    }
+
+    #[test]
+    fn test_reverse_on_mode_change() {
+        let chase = MovementAI::EnemyChase {
+            start: TilePoint::new(10, 0),
+            start_dir: Direction::Left,
+            dir: Direction::Left,
+        };
+        let config = Amidar {
+            enemies: vec![chase, Amidar::default().enemies[0].clone()],
+            mode_schedule: vec![(EnemyMode::Scatter, 20), (EnemyMode::Pursue, 1)],
+            reverse_on_mode_change: true,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        for _ in 0..19 {
+            state.update_mut(Input::default());
+        }
+        assert_eq!(state.state.mode_index, 0);
+        let before = state.state.enemies.clone();
+        state.update_mut(Input::default());
+        assert_eq!(state.state.mode_index, 1);
+
+        let (was, now) = (&before[0], &state.state.enemies[0]);
+        let heading = was.heading.unwrap();
+        assert_eq!(now.heading, Some(heading.opposite()));
+        let (dx, dy) = heading.delta();
+        let target = was.step.clone().unwrap();
+        assert_eq!(
+            now.step,
+            Some(TilePoint::new(target.tx - dx, target.ty - dy))
+        );
+        if let MovementAI::EnemyChase { dir, .. } = now.ai {
+            assert_eq!(dir, heading.opposite());
+        }
+        // An enemy following a lookup table stays on its route.
+        assert_eq!(state.state.enemies[1].heading, before[1].heading);
+    }
+
+    #[test]
+    fn test_rng_digest() {
+        let random = MovementAI::EnemyRandomMvmt {
+            start: TilePoint::new(10, 0),
+            start_dir: Direction::Left,
+            dir: Direction::Left,
+        };
+        let mut config = Amidar {
+            enemies: vec![random; 2],
+            ..Amidar::default()
+        };
+        let mut first = super::State::try_new(&config).unwrap();
+        let mut second = super::State::try_new(&config).unwrap();
+        config.rand = random::Gen::new_from_seed(99);
+        let mut other = super::State::try_new(&config).unwrap();
+
+        let rng_digest = |state: &super::State| {
+            state
+                .query_json("rng_digest", &serde_json::Value::Null)
+                .unwrap()
+        };
+        let mut digests = BTreeSet::new();
+        let mut diverged = None;
+        for frame in 0..200 {
+            for state in &mut [&mut first, &mut second, &mut other] {
+                state.update_mut(Input::default());
+            }
+            let digest = rng_digest(&first);
+            assert_eq!(digest, rng_digest(&second));
+            if diverged.is_none() && digest != rng_digest(&other) {
+                diverged = Some(frame);
+            }
+            digests.insert(digest);
+        }
+        // The enemies draw as they go, and a different seed shows up right away.
+        assert!(digests.len() > 1);
+        assert_eq!(diverged, Some(0));
+    }
+
+    #[test]
+    fn test_enemy_speed_offsets() {
+        let config = Amidar {
+            enemy_speed_offsets: vec![4, -20],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let speeds = |state: &super::State| -> Vec<i32> {
+            state.state.enemies.iter().map(|e| e.speed).collect()
+        };
+        let base = config.enemy_starting_speed;
+        assert_eq!(speeds(&state)[..3], [base + 4, 1, base]);
+
+        // The offsets carry over to chase mode and later levels.
+        state.state.chase_timer = 100;
+        state.apply_enemy_speed();
+        assert_eq!(speeds(&state)[0], config.chase_enemy_speed + 4);
+        state.state.chase_timer = 0;
+        state.state.level = 5;
+        state.apply_enemy_speed();
+        assert_eq!(speeds(&state)[..3], [base + 8, 1, base + 4]);
+    }
+
+    #[test]
+    fn test_game_over_screen() {
+        let query =
+            |state: &super::State, q: &str| state.query_json(q, &serde_json::Value::Null).unwrap();
+        let config = Amidar {
+            game_over_screen: true,
+            ..Amidar::default()
+        };
+        let mut blank = super::State::try_new(&Amidar::default()).unwrap();
+        let mut shown = super::State::try_new(&config).unwrap();
+        assert_eq!(query(&shown, "game_over"), "false");
+        for state in &mut [&mut blank, &mut shown] {
+            state.state.score = 1234;
+            state.state.lives = -1;
+            state.state.game_over = true;
+        }
+        assert_eq!(query(&shown, "game_over"), "true");
+        assert_eq!(blank.draw().len(), 1);
+
+        let frame = shown.draw();
+        let colors: BTreeSet<(u8, u8, u8)> = frame
+            .iter()
+            .filter_map(|d| match d {
+                Drawable::Rectangle { color, .. } => Some((color.r, color.g, color.b)),
+                _ => None,
+            })
+            .collect();
+        let unpainted = config.unpainted_color.halfway_to(config.bg_color);
+        assert!(colors.contains(&(unpainted.r, unpainted.g, unpainted.b)));
+        let unchanged = config.unpainted_color;
+        assert!(!colors.contains(&(unchanged.r, unchanged.g, unchanged.b)));
+        let digits = frame
+            .iter()
+            .filter(|d| matches!(d, Drawable::StaticSprite { .. }))
+            .count();
+        assert_eq!(digits, 4);
+    }
+
+    #[test]
+    fn test_elroy_stages() {
+        let query =
+            |state: &super::State, q: &str| state.query_json(q, &serde_json::Value::Null).unwrap();
+        let random = MovementAI::EnemyRandomMvmt {
+            start: TilePoint::new(10, 0),
+            start_dir: Direction::Left,
+            dir: Direction::Left,
+        };
+        let to_paint = super::State::try_new(&Amidar::default())
+            .unwrap()
+            .state
+            .board
+            .tiles_to_paint();
+        let config = Amidar {
+            enemies: vec![random; 2],
+            elroy_part1_tiles: Some(to_paint),
+            elroy_part2_tiles: Some(10),
+            debug_ai: true,
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let base = config.enemy_starting_speed;
+        assert_eq!(query(&state, "elroy_stage"), "0");
+
+        // Stage one starts after the first frame, and only the first enemy hunts the player.
+        state.update_mut(Input::default());
+        let mut reasons = BTreeSet::new();
+        for _ in 0..200 {
+            state.update_mut(Input::default());
+            if let Some(Some(decision)) = state.state.ai_debug.first() {
+                reasons.insert(format!("{:?}", decision.reason));
+            }
+        }
+        assert_eq!(query(&state, "elroy_stage"), "1");
+        let speeds: Vec<i32> = state.state.enemies.iter().map(|e| e.speed).collect();
+        assert_eq!(speeds, vec![base + config.elroy_part1_speed, base]);
+        assert!(reasons.contains("TargetSeeking"));
+        assert!(!reasons.contains("Junction"));
+
+        // Paint all but a few tiles for stage two.
+        let mut left = 5;
+        state.state.board.edit_tiles(|tiles| {
+            for tile in tiles.iter_mut().filter(|t| t.needs_paint()) {
+                if left > 0 {
+                    left -= 1;
                 } else {
-                    Err(QueryError::BadInputArg)?
+                    *tile = Tile::Painted;
                 }
             }
-            "enemy_caught" => {
-                if let Some(index) = args.as_u64() {
-                    let status = state.enemies[index as usize].caught;
-                    serde_json::to_string(&status)?
-                } else {
-                    Err(QueryError::BadInputArg)?
-                }
+        });
+        state.update_mut(Input::default());
+        assert_eq!(query(&state, "elroy_stage"), "2");
+        assert_eq!(
+            state.state.enemies[0].speed,
+            base + config.elroy_part2_speed
+        );
+    }
+
+    #[test]
+    fn test_difficulty_table() {
+        let config = Amidar {
+            difficulty_table: vec![
+                LevelParams {
+                    enemy_speed: Some(5),
+                    chase_enemy_speed: None,
+                    player_speed: Some(6),
+                    chase_time: None,
+                    box_bonus: Some(100),
+                },
+                LevelParams {
+                    enemy_speed: Some(9),
+                    chase_enemy_speed: Some(3),
+                    player_speed: None,
+                    chase_time: Some(GameTime::Frames(20)),
+                    box_bonus: None,
+                },
+            ],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        assert_eq!(state.state.enemies[0].speed, 5);
+        assert_eq!(
+            (state.player_speed(), state.box_bonus(), state.chase_time()),
+            (6, 100, config.chase_time)
+        );
+        state.update_mut(Input::default());
+        assert_eq!(state.state.player.speed, 6);
+
+        // Levels past the table play like its last entry, without the built-in speed-ups.
+        for &level in &[2, 7] {
+            state.state.level = level;
+            assert_eq!(state.enemy_speed(), 9);
+            assert_eq!(
+                (state.player_speed(), state.box_bonus(), state.chase_time()),
+                (config.player_speed, config.box_bonus, GameTime::Frames(20))
+            );
+        }
+        state.state.chase_timer = 10;
+        assert_eq!(state.enemy_speed(), 3);
+    }
+
+    #[test]
+    fn test_collision_order() {
+        let start = Amidar::default().player_start;
+        let on_player = MovementAI::EnemyRandomMvmt {
+            start: start.clone(),
+            start_dir: Direction::Right,
+            dir: Direction::Right,
+        };
+        for &order in &[
+            UpdateOrder::PlayerFirst,
+            UpdateOrder::EnemiesFirst,
+            UpdateOrder::Simultaneous,
+        ] {
+            let config = Amidar {
+                enemies: vec![on_player.clone(); 3],
+                collision_model: CollisionModel::Strict,
+                update_order: order,
+                ..Amidar::default()
+            };
+            let mut state = super::State::try_new(&config).unwrap();
+            // Enemy 1 is elsewhere; 0 and 2 both touch the player, and the lowest index gets the kill.
+            state.state.enemies[1].position = TilePoint::new(0, 0).to_world();
+            state.update_mut(Input::default());
+            assert_eq!(state.state.kills_by_enemy, vec![1, 0, 0], "{:?}", order);
+
+            // In chase mode, everyone touching the player is caught, in index order.
+            state.state.chase_timer = 100;
+            state.state.enemies[1].position = TilePoint::new(0, 0).to_world();
+            for i in &[0, 2] {
+                state.state.enemies[*i].position = state.state.player.position.clone();
             }
-            "player_tile" => {
-                let tile = state.player.position.to_tile();
-                serde_json::to_string(&(tile.tx, tile.ty))?
+            state.update_mut(Input::default());
+            assert_eq!(
+                state.state.events,
+                vec![
+                    GameEvent::EnemyCaught { enemy: 0 },
+                    GameEvent::EnemyCaught { enemy: 2 }
+                ],
+                "{:?}",
+                order
+            );
+        }
+    }
+
+    #[test]
+    fn test_swap_through() {
+        let start = Amidar::default().player_start;
+        let next = [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+        ]
+        .iter()
+        .find_map(|&dir| DEFAULT_BOARD.can_move(&start, dir))
+        .unwrap();
+        for &order in &[
+            UpdateOrder::PlayerFirst,
+            UpdateOrder::EnemiesFirst,
+            UpdateOrder::Simultaneous,
+        ] {
+            for &model in &[
+                CollisionModel::PassThrough,
+                CollisionModel::Recheck,
+                CollisionModel::Strict,
+            ] {
+                let config = Amidar {
+                    enemies: vec![MovementAI::EnemyRandomMvmt {
+                        start: next.clone(),
+                        start_dir: Direction::Left,
+                        dir: Direction::Left,
+                    }],
+                    player_speed: 1000,
+                    enemy_starting_speed: 1000,
+                    update_order: order,
+                    collision_model: model,
+                    ..Amidar::default()
+                };
+                let mut state = super::State::try_new(&config).unwrap();
+                // The player and the enemy trade tiles in a single frame.
+                state.state.player.step = Some(next.clone());
+                state.state.enemies[0].step = Some(start.clone());
+                state.update_mut(Input::default());
+                let passed =
+                    order == UpdateOrder::Simultaneous && model == CollisionModel::PassThrough;
+                assert_eq!(state.state.life_lost, !passed, "{:?} {:?}", order, model);
             }
-            _ => Err(QueryError::NoSuchQuery)?,
-        })
+        }
     }
-    fn copy(&self) -> Box<dyn toybox_core::State> {
-        Box::new(self.clone())
+
+    #[test]
+    fn test_spawn_markers() {
+        let board: Vec<String> = ["=====2===", "=   =   =", "=   =   =", "1========"]
+            .iter()
+            .map(|&row| row.to_owned())
+            .collect();
+        let random = MovementAI::EnemyRandomMvmt {
+            start: TilePoint::new(8, 0),
+            start_dir: Direction::Left,
+            dir: Direction::Left,
+        };
+        let config = Amidar {
+            board: board.clone(),
+            player_start: TilePoint::new(4, 1),
+            default_board_bugs: false,
+            enemies: vec![random.clone(), random.clone(), random.clone()],
+            ..Amidar::default()
+        };
+        let parsed = Board::try_new(&board).unwrap();
+        assert_eq!(
+            parsed.enemy_spawns,
+            vec![Some(TilePoint::new(0, 3)), Some(TilePoint::new(5, 0))]
+        );
+        // Markers are plain track that still needs painting.
+        assert_eq!(parsed.get_tile(&TilePoint::new(0, 3)), Tile::Unpainted);
+        assert_eq!(parsed.tiles_to_paint(), 24);
+
+        let state = super::State::try_new(&config).unwrap();
+        let starts: Vec<TilePoint> = state
+            .state
+            .enemies
+            .iter()
+            .map(|e| e.position.to_tile())
+            .collect();
+        assert_eq!(
+            starts,
+            vec![
+                TilePoint::new(0, 3),
+                TilePoint::new(5, 0),
+                TilePoint::new(8, 0)
+            ]
+        );
+        assert_eq!(
+            state.state.enemies[1]
+                .ai
+                .start_position(&config.player_start, &parsed),
+            TilePoint::new(5, 0)
+        );
+        assert_eq!(state.config.enemies, config.enemies);
+
+        let mut twice = board.clone();
+        twice[3] = "1=======1".to_owned();
+        assert!(Board::try_new(&twice).is_err());
+
+        // The player's marker moves player_start, and there can only be one.
+        let mut with_player = board.clone();
+        with_player[3] = "1===P====".to_owned();
+        let state = super::State::try_new(&Amidar {
+            board: with_player.clone(),
+            ..config.clone()
+        })
+        .unwrap();
+        assert_eq!(state.player_start(), TilePoint::new(4, 3));
+        assert_eq!(state.config.player_start, config.player_start);
+        assert_eq!(state.state.player.position.to_tile(), TilePoint::new(4, 3));
+
+        // Markers come from the board, so they survive a savestate, and a layout without them goes back to the config's starts.
+        let plain: Vec<String> = board
+            .iter()
+            .map(|row| row.replace(|c: char| c.is_ascii_digit(), "="))
+            .collect();
+        let two_layouts = Amidar {
+            board: with_player.clone(),
+            boards: vec![plain],
+            level_board_order: vec![0, 1],
+            ..config.clone()
+        };
+        let mut state = super::State::try_new(&two_layouts).unwrap();
+        state.state.player.position = TilePoint::new(0, 0).to_world();
+        let mut restored = super::State::from_bytes(&two_layouts, &state.to_bytes()).unwrap();
+        restored.reset();
+        assert_eq!(
+            restored.state.player.position.to_tile(),
+            TilePoint::new(4, 3)
+        );
+        restored.next_level();
+        assert_eq!(
+            restored.state.player.position.to_tile(),
+            config.player_start
+        );
+        assert!(restored
+            .state
+            .enemies
+            .iter()
+            .all(|e| e.position.to_tile() == TilePoint::new(8, 0)));
+
+        with_player[0] = "=====2=P=".to_owned();
+        assert!(Board::try_new(&with_player).is_err());
+        let too_few = Amidar {
+            enemies: vec![random],
+            ..config.clone()
+        };
+        assert!(super::State::try_new(&too_few).is_err());
+        assert!(too_few.validate().is_err());
+        let fixed_route = Amidar {
+            enemies: vec![
+                MovementAI::EnemyPerimeterAI {
+                    start: TilePoint::new(0, 0),
+                };
+                2
+            ],
+            ..config
+        };
+        assert!(super::State::try_new(&fixed_route).is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use toybox_core::State;
+    #[test]
+    fn test_collision_radius() {
+        let random = MovementAI::EnemyRandomMvmt {
+            start: TilePoint::new(0, 0),
+            start_dir: Direction::Right,
+            dir: Direction::Right,
+        };
+        let config = Amidar {
+            enemies: vec![random; 2],
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        state.state.chase_timer = 100;
+        let player = state.state.player.position.clone();
+        // Enemy 0 is on the player's tile, but well off to the side; enemy 1 is just over the edge of the tile before.
+        state.state.enemies[0].position = player.translate(world::TILE_SIZE.0 / 2, 0);
+        state.state.enemies[1].position = player.translate(-2, 0);
+        assert_eq!(state.state.enemies[0].position.to_tile(), player.to_tile());
+        assert_ne!(state.state.enemies[1].position.to_tile(), player.to_tile());
+
+        let caught = |state: &super::State| -> Vec<bool> {
+            (0..2)
+                .map(|i| {
+                    let enemy = &state.state.enemies[i];
+                    state.check_enemy_player_collision(enemy, i, None, None)
+                        != EnemyPlayerState::Miss
+                })
+                .collect()
+        };
+        assert_eq!(caught(&state), vec![true, false]);
+        state.config.collision_radius_world_units = Some(world::TILE_SIZE.0 / 4);
+        assert_eq!(caught(&state), vec![false, true]);
+
+        let negative = Amidar {
+            collision_radius_world_units: Some(-1),
+            ..config
+        };
+        assert!(super::State::try_new(&negative).is_err());
+    }
 
     #[test]
-    fn test_colors_unique_in_gray() {
-        let config = Amidar::default();
-        let num_colors = config.colors().len();
-        let uniq_grays: HashSet<u8> = config
-            .colors()
-            .into_iter()
-            .map(|c| c.grayscale_byte())
+    fn test_cornering() {
+        let board: Vec<String> = ["=========", "=   =   =", "=   =   =", "========="]
+            .iter()
+            .map(|&row| row.to_owned())
             .collect();
-        // Don't allow a grayscale agent to be confused where a human wouldn't be.
-        assert_eq!(uniq_grays.len(), num_colors);
+        let config = Amidar {
+            board,
+            player_start: TilePoint::new(0, 3),
+            default_board_bugs: false,
+            enemies: Vec::new(),
+            ..Amidar::default()
+        };
+        let right = AleAction::RIGHT.to_input();
+        let up = AleAction::UP.to_input();
+        // Tap right, then tap up long before the first turning.
+        let play = |cornering_tolerance: Option<i32>| -> Vec<TilePoint> {
+            let mut state = super::State::try_new(&Amidar {
+                cornering_tolerance,
+                ..config.clone()
+            })
+            .unwrap();
+            state.update_mut(right);
+            (0..60)
+                .map(|frame| {
+                    state.update_mut(if frame == 10 { up } else { Input::default() });
+                    state.state.player.position.to_tile()
+                })
+                .collect()
+        };
+        let arrival = |tiles: &[TilePoint]| tiles.iter().position(|t| *t == TilePoint::new(4, 2));
+
+        // Normally the player stops once it lets go.
+        let held = play(None);
+        assert!(held.iter().all(|t| t.ty == 3 && t.tx < 4));
+        // With cornering, it keeps going and turns up at the first chance.
+        let queued = play(Some(0));
+        assert!(arrival(&queued).is_some());
+        assert!(queued.iter().all(|t| t.tx <= 4));
+        // With some tolerance, it cuts that corner and gets there sooner.
+        let cut = play(Some(world::TILE_SIZE.0 / 2));
+        assert!(arrival(&cut).unwrap() < arrival(&queued).unwrap());
     }
 
     #[test]
-    fn board_included() {
-        let board_ch: Vec<Vec<char>> = AMIDAR_BOARD
-            .lines()
-            .map(|line| line.chars().collect::<Vec<char>>())
+    fn test_buffer_turns() {
+        let board: Vec<String> = ["=========", "=   =   =", "=   =   =", "========="]
+            .iter()
+            .map(|&row| row.to_owned())
             .collect();
-        for row in board_ch.iter() {
-            assert_eq!(Some('='), row.iter().cloned().find(|c| *c == '='));
-        }
+        let config = Amidar {
+            board,
+            player_start: TilePoint::new(0, 3),
+            default_board_bugs: false,
+            enemies: Vec::new(),
+            ..Amidar::default()
+        };
+        let play = |buffer_turns: bool, script: &str| -> TilePoint {
+            let mut state = super::State::try_new(&Amidar {
+                buffer_turns,
+                ..config.clone()
+            })
+            .unwrap();
+            for input in parse_script(script).unwrap() {
+                state.update_mut(input);
+            }
+            state.state.player.position.to_tile()
+        };
+        // Tap up a little before the junction at (4, 3), and let go.
+        let early = "R×28, U, NOOP×30";
+        assert_eq!(play(false, early), TilePoint::new(4, 3));
+        assert_eq!(play(true, early), TilePoint::new(4, 2));
+        // A turn that can't be taken at the next tile is forgotten there.
+        let too_early = "R×12, U, R×20";
+        assert_eq!(play(true, too_early), play(false, too_early));
+        assert_eq!(play(true, too_early).ty, 3);
     }
 
     #[test]
-    fn board_corners() {
-        let board = Board::fast_new();
-        assert!(board.is_corner(0, 0));
-        assert!(board.is_corner(0, 30));
-        assert!(board.is_corner(31, 0));
-        assert!(board.is_corner(31, 30));
+    fn test_adaptive_difficulty() {
+        let config = Amidar {
+            adaptive_difficulty: Some(AdaptiveDifficulty {
+                target_deaths: 1,
+                enemy_speed_step: 2,
+                chase_frames_step: 10,
+                min_enemy_speed: 4,
+            }),
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let clear = |state: &mut super::State| {
+            state.state.board.edit_tiles(|tiles| {
+                for tile in tiles.iter_mut() {
+                    if tile.walkable() {
+                        *tile = Tile::Painted;
+                    }
+                }
+            });
+            state.update_mut(Input::default());
+        };
+        let start = state.level_params_now();
+        assert_eq!(
+            serde_json::from_str::<LevelParams>(
+                &state
+                    .query_json("level_params", &serde_json::Value::Null)
+                    .unwrap()
+            )
+            .unwrap(),
+            start
+        );
+
+        // No deaths: harder.
+        clear(&mut state);
+        assert_eq!(state.level(), 2);
+        let harder = state.level_params_now();
+        assert_eq!(harder.enemy_speed, start.enemy_speed.map(|s| s + 2));
+        assert_eq!(harder.player_speed, start.player_speed);
+        let frames = |p: &LevelParams| p.chase_time.unwrap().to_frames(config.frames_per_second);
+        assert_eq!(frames(&harder), frames(&start) - 10);
+        assert!(state
+            .state
+            .enemies
+            .iter()
+            .all(|e| e.speed == harder.enemy_speed.unwrap()));
+
+        // Too many deaths: easier again, but never below the floor.
+        state.state.level_deaths = 3;
+        clear(&mut state);
+        let easier = state.level_params_now();
+        assert_eq!(
+            easier.enemy_speed,
+            harder.enemy_speed.map(|s| (s - 2).max(4))
+        );
+        assert_eq!(frames(&easier), frames(&harder) + 10);
+
+        // The adjusted level survives a binary savestate, in either unit.
+        let loaded = super::State::from_bytes(&config, &state.to_bytes()).unwrap();
+        assert_eq!(loaded.level_params_now(), easier);
+        state.state.level_override = Some(LevelParams {
+            chase_time: Some(GameTime::Seconds(2.5)),
+            ..easier
+        });
+        let loaded = super::State::from_bytes(&config, &state.to_bytes()).unwrap();
+        assert_eq!(loaded.state.level_override, state.state.level_override);
     }
+
     #[test]
-    fn player_start_position() {
-        let board = Board::fast_new();
-        assert_eq!(TilePoint::new(31, 15), board.lookup_position(511));
-        assert!(board.get_junction_id(&TilePoint::new(31, 18)).is_some());
+    fn test_ale_mspacman_scoring() {
+        let mut config = Amidar::default();
+        config.apply_ale_mspacman_scoring();
+        let mut state = super::State::try_new(&config).unwrap();
+        let mut bonuses = Vec::new();
+        for catches in 0..5 {
+            state.state.chase_catches = catches;
+            bonuses.push(state.chase_bonus());
+        }
+        assert_eq!(bonuses, vec![200, 400, 800, 1600, 1600]);
+        assert_eq!(state.lives(), 3);
+
+        let die = |state: &mut super::State| {
+            state.state.enemies[0].position = state.state.player.position.clone();
+            state.update_mut(Input::default());
+            while state.state.phase != GamePhase::Playing && !state.game_over() {
+                state.update_mut(Input::default());
+            }
+        };
+        let clear_level = |state: &mut super::State| {
+            state.state.board.edit_tiles(|tiles| {
+                for tile in tiles.iter_mut() {
+                    if tile.walkable() {
+                        *tile = Tile::Painted;
+                    }
+                }
+            });
+            let level = state.level();
+            while state.level() == level {
+                state.update_mut(Input::default());
+            }
+        };
+
+        // The default game gives a life back for clearing a level and none for points; ALE's, the other way around.
+        let mut default = super::State::try_new(&Amidar::default()).unwrap();
+        die(&mut default);
+        assert_eq!(default.lives(), 2);
+        clear_level(&mut default);
+        assert_eq!(default.lives(), 3);
+        default.state.score = 10_000;
+        default.update_mut(Input::default());
+        assert_eq!(default.lives(), 3);
+
+        die(&mut state);
+        assert_eq!(state.lives(), 2);
+        clear_level(&mut state);
+        assert_eq!(state.lives(), 2);
+        state.state.score = 10_000;
+        state.update_mut(Input::default());
+        assert_eq!(state.lives(), 3);
+        state.state.score = 20_000;
+        state.update_mut(Input::default());
+        assert_eq!(state.lives(), 3);
+
+        // ALE's last life reads 1, and losing it ends the game; the default game has one more.
+        for _ in 0..2 {
+            die(&mut state);
+        }
+        assert_eq!(state.lives(), 1);
+        assert!(!state.game_over());
+        die(&mut state);
+        assert!(state.game_over());
+        for _ in 0..3 {
+            die(&mut default);
+        }
+        assert!(!default.game_over());
     }
 
     #[test]
-    fn num_grid_boxes() {
-        let board = Board::fast_new();
-        let mut ordered = board.boxes.clone();
-        ordered.sort_by_key(|it| it.top_left.tx + it.top_left.ty * 32);
-        for gb in ordered {
-            println!("Box-found: {:?}", gb.top_left);
+    fn test_tiles_to_paint_count() {
+        let recount = |board: &Board| board.tiles.iter().filter(|t| t.needs_paint()).count() as u32;
+        let mut state = super::State::try_new(&Amidar::default()).unwrap();
+        let start = state.state.board.tiles_to_paint();
+        assert_eq!(start, recount(&state.state.board));
+
+        let go_up = Input {
+            up: true,
+            ..Input::default()
+        };
+        for _ in 0..200 {
+            state.update_mut(go_up);
+            assert_eq!(
+                state.state.board.tiles_to_paint(),
+                recount(&state.state.board)
+            );
         }
-        assert_eq!(board.boxes.len(), 29);
+        assert!(state.state.board.tiles_to_paint() < start);
+
+        let tile = TilePoint::new(31, 30);
+        state.state.board.set_tile(&tile, Tile::Painted);
+        state.state.board.set_tile(&tile, Tile::Painted);
+        state.state.board.set_tile(&tile, Tile::Unpainted);
+        assert_eq!(
+            state.state.board.tiles_to_paint(),
+            recount(&state.state.board)
+        );
+
+        let loaded = super::State::from_bytes(&state.config, &state.to_bytes()).unwrap();
+        assert_eq!(
+            loaded.state.board.tiles_to_paint(),
+            state.state.board.tiles_to_paint()
+        );
     }
 
     #[test]
-    fn test_load_png() {
-        let img = &images::PLAYER_L1;
-        assert!(img.width() > 0);
-        assert!(img.height() > 0);
-        assert!(img.find_visible_color().is_some());
+    fn test_target_player_vision() {
+        let board = super::State::try_new(&Amidar::default())
+            .unwrap()
+            .state
+            .board;
+        let mut rng = random::Gen::new_from_seed(1);
+        let mut choose =
+            |at: TilePoint, dir: Direction, player: TilePoint, line_of_sight, catchable| {
+                let mut ai = MovementAI::EnemyTargetPlayer {
+                    start: at.clone(),
+                    start_dir: dir,
+                    vision_distance: 8,
+                    line_of_sight,
+                    dir,
+                    player_seen: None,
+                };
+                let surroundings = Surroundings {
+                    player: Some(Mob::new(MovementAI::Player, player.to_world(), 1)),
+                    enemies: &[],
+                    mode: EnemyMode::Pursue,
+                    catchable,
+                    frightened_policy: FrightenedPolicy::Unchanged,
+                    elroy: false,
+                };
+                let mut why = super::Deliberation::new();
+                let next = ai.choose_next_tile(
+                    &at,
+                    Input::default(),
+                    &board,
+                    surroundings,
+                    &mut rng,
+                    &mut why,
+                );
+                (next.unwrap(), why.reason)
+            };
+
+        // Down the top row: head for the player, or turn around and run while it can catch us.
+        let (next, reason) = choose(
+            TilePoint::new(8, 0),
+            Direction::Left,
+            TilePoint::new(6, 0),
+            true,
+            false,
+        );
+        assert_eq!(
+            (next, reason),
+            (TilePoint::new(7, 0), AiReason::TargetSeeking)
+        );
+        let (next, reason) = choose(
+            TilePoint::new(8, 0),
+            Direction::Left,
+            TilePoint::new(6, 0),
+            true,
+            true,
+        );
+        assert_eq!((next, reason), (TilePoint::new(9, 0), AiReason::Fleeing));
+
+        // Around a corner, only an enemy that needn't see down the track notices the player.
+        let (_, reason) = choose(
+            TilePoint::new(10, 0),
+            Direction::Left,
+            TilePoint::new(6, 3),
+            true,
+            false,
+        );
+        assert_eq!(reason, AiReason::Junction);
+        let (next, reason) = choose(
+            TilePoint::new(10, 0),
+            Direction::Left,
+            TilePoint::new(6, 3),
+            false,
+            false,
+        );
+        assert_eq!(
+            (next, reason),
+            (TilePoint::new(9, 0), AiReason::TargetSeeking)
+        );
+        let (next, reason) = choose(
+            TilePoint::new(8, 0),
+            Direction::Left,
+            TilePoint::new(6, 3),
+            false,
+            true,
+        );
+        assert_eq!((next, reason), (TilePoint::new(9, 0), AiReason::Fleeing));
     }
 
     #[test]
-    fn what_json_do_we_want() {
-        let data = MovementAI::EnemyPerimeterAI {
-            start: TilePoint::new(0, 0),
+    fn test_observation_stack() {
+        let unstacked = super::State::try_new(&Amidar::default()).unwrap();
+        assert!(unstacked.stacked_board_tensor().is_empty());
+
+        let config = Amidar {
+            observation_stack: 3,
+            ..Amidar::default()
         };
-        println!("{}", serde_json::to_string_pretty(&data).unwrap());
-        let data = MovementAI::EnemyTargetPlayer {
-            vision_distance: 15,
-            start: TilePoint::new(0, 0),
-            start_dir: Direction::Up,
-            dir: Direction::Up,
-            player_seen: None,
+        let mut state = super::State::try_new(&config).unwrap();
+        let first = state.board_tensor();
+        assert_eq!(state.stacked_board_tensor(), vec![first.clone(); 3]);
+
+        let go_left = Input {
+            left: true,
+            ..Input::default()
         };
-        println!("{}", serde_json::to_string_pretty(&data).unwrap());
-    }
+        for _ in 0..20 {
+            state.update_mut(go_left);
+        }
+        let stack = state.stacked_board_tensor();
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack[2], state.board_tensor());
+        assert_ne!(stack[0], first);
 
-    fn player_tile(state: &dyn State) -> (i32, i32) {
-        serde_json::from_str(
-            &state
-                .query_json("player_tile", &serde_json::Value::Null)
-                .unwrap(),
-        )
-        .unwrap()
-    }
-    fn num_tiles_unpainted(state: &dyn State) -> usize {
-        serde_json::from_str(
-            &state
-                .query_json("num_tiles_unpainted", &serde_json::Value::Null)
-                .unwrap(),
-        )
-        .unwrap()
+        // A restored game hands back the same stack, and keeps stacking the same way.
+        let mut restored = super::State::from_bytes(&config, &state.to_bytes()).unwrap();
+        let from_json: StateCore = serde_json::from_str(&state.to_json()).unwrap();
+        assert_eq!(from_json.observations, state.state.observations);
+        for _ in 0..5 {
+            state.update_mut(go_left);
+            restored.update_mut(go_left);
+        }
+        assert_eq!(
+            restored.stacked_board_tensor(),
+            state.stacked_board_tensor()
+        );
     }
 
     #[test]
-    fn test_q_num_tiles_unpainted() {
-        let mut state = super::State::try_new(&Amidar::default()).unwrap();
-
-        let (px, py) = player_tile(&state);
-        let first = num_tiles_unpainted(&state);
+    fn test_frightened_policy() {
+        let board = super::State::try_new(&Amidar::default())
+            .unwrap()
+            .state
+            .board;
+        let mut rng = random::Gen::new_from_seed(1);
+        let mut enemy = Mob::new(
+            MovementAI::EnemyRandomMvmt {
+                start: TilePoint::new(10, 0),
+                start_dir: Direction::Left,
+                dir: Direction::Left,
+            },
+            TilePoint::new(10, 0).to_world(),
+            1,
+        );
+        enemy.heading = Some(Direction::Left);
+        let player = Some(TilePoint::new(6, 0));
+        let mut why = super::Deliberation::new();
 
-        let mut go_up = Input::default();
-        go_up.up = true;
+        // At a junction, fleeing takes the way out furthest from the player, but never back the way it came.
+        let at = TilePoint::new(10, 0);
+        let next = enemy.frightened_step(
+            &at,
+            FrightenedPolicy::Flee,
+            player.clone(),
+            &board,
+            &mut rng,
+            &mut why,
+        );
+        assert_eq!(
+            (next, why.reason),
+            (Some(TilePoint::new(10, 1)), AiReason::Fleeing)
+        );
+        assert_eq!(why.considered, vec![Direction::Left, Direction::Down]);
+        for _ in 0..20 {
+            let next = enemy.frightened_step(
+                &at,
+                FrightenedPolicy::Random,
+                player.clone(),
+                &board,
+                &mut rng,
+                &mut why,
+            );
+            assert_ne!(next, Some(TilePoint::new(11, 0)));
+        }
+        // Between junctions, keep going.
+        let at = TilePoint::new(8, 0);
+        let next = enemy.frightened_step(
+            &at,
+            FrightenedPolicy::Flee,
+            player,
+            &board,
+            &mut rng,
+            &mut why,
+        );
+        assert_eq!(next, Some(TilePoint::new(7, 0)));
 
-        // Move the user up (be a little robust to how long the animation takes.)
-        for _ in 0..5000 {
-            state.update_mut(go_up);
-            if state.score() > 0 {
-                // we must have painted something!
-                break;
+        // Every kind of enemy, table-driven ones included, stays on the track while frightened.
+        for &policy in &[FrightenedPolicy::Random, FrightenedPolicy::Flee] {
+            for enemies in &[Amidar::default().enemies, vec![enemy.ai.clone(); 3]] {
+                let config = Amidar {
+                    frightened_policy: policy,
+                    enemies: enemies.clone(),
+                    ..Amidar::default()
+                };
+                let mut state = super::State::try_new(&config).unwrap();
+                state.state.chase_timer = 10_000;
+                for _ in 0..300 {
+                    state.update_mut(Input::default());
+                    for e in &state.state.enemies {
+                        assert!(state.state.board.get_tile(&e.position.to_tile()).walkable());
+                    }
+                }
+                assert!(state.state.enemies.iter().all(|e| e.heading.is_some()));
             }
         }
+    }
 
-        let (nx, ny) = player_tile(&state);
-        if py == ny {
-            panic!("Player can't move upward!")
+    #[test]
+    fn test_exploration_bonus() {
+        let config = Amidar {
+            enemies: Vec::new(),
+            exploration_bonus: ExplorationBonus::Tiles(0.5),
+            ..Amidar::default()
+        };
+        let mut state = super::State::try_new(&config).unwrap();
+        let go_up = Input {
+            up: true,
+            ..Input::default()
+        };
+
+        let mut bonus = 0.0;
+        for _ in 0..50 {
+            state.update_mut(go_up);
+            // Painting the first segment is free on the default board, so all reward is exploration.
+            bonus += state.state.reward;
         }
-        println!("Moved player to ({},{}) from ({},{})", nx, ny, px, py);
+        let tile = state.state.player.position.to_tile();
+        assert!(tile.ty < config.player_start.ty);
+        let visited: u32 = state.state.visit_counts.iter().sum();
+        assert_eq!(bonus, 0.5 * visited as f32);
 
-        let painted_now = num_tiles_unpainted(&state);
-        println!("painted_now: {} ... before: {}", painted_now, first);
-        assert!(painted_now < first);
+        // Walk back down over the same tiles: no more bonus.
+        let go_down = Input {
+            down: true,
+            ..Input::default()
+        };
+        for _ in 0..10 {
+            state.update_mut(go_down);
+            assert_eq!(state.state.reward, 0.0);
+        }
     }
 }
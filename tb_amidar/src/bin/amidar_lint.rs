@@ -0,0 +1,92 @@
+//! Check a custom Amidar board before using it as ``Amidar::board``.
+//!
+//! Usage: amidar_lint BOARD_FILE [START_X START_Y]
+//!
+//! The board file has one row of tiles per line, in the same characters as the default board. The start defaults to the board's ``P`` marker, or else ``Amidar::default().player_start``. Exits non-zero if the board has problems.
+
+extern crate amidar;
+
+use amidar::{Amidar, Board, TilePoint};
+use std::fs;
+use std::process;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 2 && args.len() != 4 {
+        eprintln!("usage: {} BOARD_FILE [START_X START_Y]", args[0]);
+        process::exit(2);
+    }
+
+    let text = fs::read_to_string(&args[1]).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", args[1], e);
+        process::exit(2);
+    });
+    let lines: Vec<String> = text
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_owned())
+        .collect();
+    let board = Board::try_new(&lines).unwrap_or_else(|e| {
+        eprintln!("Could not parse {}: {}", args[1], e);
+        process::exit(1);
+    });
+
+    let start = if args.len() == 4 {
+        let coord = |s: &str| {
+            s.parse::<i32>().unwrap_or_else(|_| {
+                eprintln!("Start coordinates must be integers, not {:?}.", s);
+                process::exit(2);
+            })
+        };
+        TilePoint::new(coord(&args[2]), coord(&args[3]))
+    } else {
+        board
+            .player_spawn
+            .clone()
+            .unwrap_or_else(|| Amidar::default().player_start)
+    };
+
+    let report = board.lint(&start);
+    println!("size: {}x{}", report.width, report.height);
+    println!("walkable tiles: {}", report.walkable_tiles);
+    println!("tiles to paint: {}", report.paint_tiles);
+    println!(
+        "junctions: {} ({} trigger chases)",
+        report.junctions, report.chase_junctions
+    );
+    println!("boxes: {}", report.boxes);
+    println!("regions: {}", report.regions);
+    println!("dead ends: {}", report.dead_ends.len());
+    println!("start: ({}, {})", start.tx, start.ty);
+
+    // Preview: S marks the start, ! marks tiles that can't be reached from it.
+    println!();
+    for (ty, row) in board.rows().enumerate() {
+        let line: String = row
+            .iter()
+            .enumerate()
+            .map(|(tx, tile)| {
+                let tp = TilePoint::new(tx as i32, ty as i32);
+                if tp == start {
+                    'S'
+                } else if report.unreachable.contains(&tp) {
+                    '!'
+                } else {
+                    tile.to_char()
+                }
+            })
+            .collect();
+        println!("{}", line);
+    }
+    println!();
+
+    let problems = report.problems();
+    if problems.is_empty() {
+        println!("OK");
+    } else {
+        for problem in &problems {
+            println!("problem: {}", problem);
+        }
+        process::exit(1);
+    }
+}
@@ -0,0 +1,24 @@
+use crate::types::{AdaptiveDifficulty, DifficultyController, GameTime, LevelParams, LevelStats};
+
+impl DifficultyController for AdaptiveDifficulty {
+    fn next_level(&mut self, stats: &LevelStats, next: LevelParams) -> LevelParams {
+        // Positive makes the game harder, negative easier.
+        let direction = (self.target_deaths - stats.deaths).signum();
+        let speed = |speed: Option<i32>| {
+            speed.map(|s| (s + direction * self.enemy_speed_step).max(self.min_enemy_speed))
+        };
+        let chase_time = next.chase_time.map(|time| match time {
+            GameTime::Frames(frames) => {
+                GameTime::Frames((frames - direction * self.chase_frames_step).max(1))
+            }
+            // ``State::level_params_now`` always gives frames.
+            GameTime::Seconds(_) => time,
+        });
+        LevelParams {
+            enemy_speed: speed(next.enemy_speed),
+            chase_enemy_speed: speed(next.chase_enemy_speed),
+            chase_time,
+            ..next
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::types::{Amidar, AmidarEnv, State, StateCore, StepResult};
+use toybox_core::graphics::ImageBuffer;
+use toybox_core::State as _;
+use toybox_core::{AleAction, Simulation};
+
+impl AmidarEnv {
+    /// Start a game of ``config``, seeded from ``config`` itself.
+    pub fn new(config: Amidar) -> Result<AmidarEnv, String> {
+        Ok(AmidarEnv {
+            game: State::try_new(&config)?,
+        })
+    }
+
+    /// Throw away the current game and start a fresh one from ``seed``.
+    pub fn reset(&mut self, seed: u32) {
+        let mut config = self.game.config.clone();
+        config.reset_seed(seed);
+        self.game = State::try_new(&config).expect("the config was valid when the env was made");
+    }
+
+    /// Play one frame of ``action``.
+    pub fn step(&mut self, action: AleAction) -> StepResult {
+        self.game.update_mut(action.to_input());
+        StepResult {
+            reward: self.game.state.reward,
+            score: self.game.score(),
+            lives: self.game.lives(),
+            terminal: self.game.is_terminal(),
+            events: self.game.state.events.clone(),
+        }
+    }
+
+    /// Draw the current frame.
+    pub fn render(&self) -> ImageBuffer {
+        let (w, h) = self.game.config.screen_size();
+        let mut img = ImageBuffer::alloc(w, h);
+        img.render(&self.game.draw());
+        img
+    }
+
+    /// Draw the current frame into ``out`` as RGBA, four bytes per pixel of ``Amidar::screen_size``.
+    pub fn render_rgba(&self, out: &mut [u8]) -> Result<(), String> {
+        let (w, h) = self.game.config.screen_size();
+        self.game.render_into(w, h, false, out)
+    }
+
+    /// Draw the current frame into ``out`` as grayscale, one byte per pixel of ``Amidar::screen_size``.
+    pub fn render_grayscale(&self, out: &mut [u8]) -> Result<(), String> {
+        let (w, h) = self.game.config.screen_size();
+        self.game.render_into(w, h, true, out)
+    }
+
+    /// The current frame as JSON, for ``load``.
+    pub fn save(&self) -> String {
+        serde_json::to_string(&self.game.state).expect("Should be no JSON Serialization Errors.")
+    }
+
+    /// Pick up from a frame returned by ``save``; the config stays the same.
+    pub fn load(&mut self, json: &str) -> Result<(), serde_json::Error> {
+        let mut state: StateCore = serde_json::from_str(json)?;
+        self.game.config.upgrade_state(&mut state);
+        self.game.state = state;
+        Ok(())
+    }
+
+    /// The current frame as a binary savestate, for ``load_bytes``; see ``State::to_bytes``.
+    pub fn save_bytes(&self) -> Vec<u8> {
+        self.game.to_bytes()
+    }
+
+    /// Pick up from a frame returned by ``save_bytes``; the config stays the same.
+    pub fn load_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.game = State::from_bytes(&self.game.config, bytes)?;
+        Ok(())
+    }
+
+    /// The config this env plays.
+    pub fn config(&self) -> &Amidar {
+        &self.game.config
+    }
+
+    /// The game as it stands, for queries and anything else ``step`` doesn't report.
+    pub fn game(&self) -> &State {
+        &self.game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_save_load() {
+        let mut env = AmidarEnv::new(Amidar::default()).unwrap();
+        env.reset(3);
+        for _ in 0..20 {
+            env.step(AleAction::UP);
+        }
+        let saved = env.save();
+        let ahead: Vec<StepResult> = (0..20).map(|_| env.step(AleAction::LEFT)).collect();
+
+        env.load(&saved).unwrap();
+        let replayed: Vec<StepResult> = (0..20).map(|_| env.step(AleAction::LEFT)).collect();
+        assert_eq!(ahead, replayed);
+
+        let frame = env.render();
+        assert_eq!((frame.width, frame.height), env.config().screen_size());
+
+        // A frame saved before the per-enemy counters existed gets them on loading.
+        let mut old: serde_json::Value = serde_json::from_str(&saved).unwrap();
+        old.as_object_mut().unwrap().remove("kills_by_enemy");
+        env.load(&old.to_string()).unwrap();
+        assert_eq!(
+            env.game.state.kills_by_enemy.len(),
+            env.config().enemies.len()
+        );
+    }
+
+    #[test]
+    fn test_env_render_into() {
+        let env = AmidarEnv::new(Amidar::default()).unwrap();
+        let (w, h) = env.config().screen_size();
+        let pixels = (w * h) as usize;
+
+        let mut rgba = vec![0; pixels * 4];
+        env.render_rgba(&mut rgba).unwrap();
+        assert_eq!(rgba, env.render().data);
+
+        let mut gray = vec![0; pixels];
+        env.render_grayscale(&mut gray).unwrap();
+        assert!(gray.iter().any(|&p| p != gray[0]));
+
+        assert!(env.render_rgba(&mut gray).is_err());
+        assert!(env.render_grayscale(&mut rgba).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use crate::types::{Amidar, AmidarEnv, Episode, Evaluation, State};
+use toybox_core::AleAction;
+
+/// Play one episode of ``config`` per seed, choosing every action with ``policy``, and cut each off after ``max_frames``.
+/// Episodes end the way ``State::is_terminal`` says, so ``Amidar::episodic_life`` is honored.
+pub fn evaluate<F>(mut policy: F, seeds: &[u32], config: &Amidar, max_frames: usize) -> Evaluation
+where
+    F: FnMut(&State) -> AleAction,
+{
+    let mut env = AmidarEnv::new(config.clone()).expect("evaluate needs a valid config");
+    let episodes: Vec<Episode> = seeds
+        .iter()
+        .map(|&seed| {
+            env.reset(seed);
+            let mut frames = 0;
+            let mut finished = false;
+            while frames < max_frames {
+                let action = policy(env.game());
+                frames += 1;
+                if env.step(action).terminal {
+                    finished = true;
+                    break;
+                }
+            }
+            Episode {
+                seed,
+                score: env.game().state.score,
+                frames,
+                level: env.game().state.level,
+                finished,
+            }
+        })
+        .collect();
+
+    let n = episodes.len().max(1) as f64;
+    let mean_score = episodes.iter().map(|e| e.score as f64).sum::<f64>() / n;
+    let variance = episodes
+        .iter()
+        .map(|e| (e.score as f64 - mean_score).powi(2))
+        .sum::<f64>()
+        / n;
+    Evaluation {
+        mean_score,
+        std_score: variance.sqrt(),
+        min_score: episodes.iter().map(|e| e.score).min().unwrap_or(0),
+        max_score: episodes.iter().map(|e| e.score).max().unwrap_or(0),
+        mean_frames: episodes.iter().map(|e| e.frames as f64).sum::<f64>() / n,
+        episodes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let config = Amidar::default();
+        let seeds = [1, 2, 3];
+        let up = |_: &State| AleAction::UP;
+        let result = evaluate(up, &seeds, &config, 200);
+        assert_eq!(result.episodes.len(), 3);
+        assert!(result.episodes.iter().all(|e| e.frames <= 200));
+        assert!(result.min_score <= result.max_score);
+        assert!(result.mean_score >= result.min_score as f64);
+        assert!(result.mean_score <= result.max_score as f64);
+
+        // Same seeds, same policy, same answer.
+        assert_eq!(evaluate(up, &seeds, &config, 200), result);
+    }
+}
@@ -1,3 +1,8 @@
+//! The amidar crate contains the data structures and logic for a clone of the Atari 2600 game Amidar, but defined to be more flexible.
+//!
+//! The `Amidar` struct is the `toybox_core::Simulation` and the `State` struct is the `toybox_core::State` used generically by other crates.
+//! Everything a config or state is made of is re-exported here, so downstream code never needs to name the `types` module directly.
+
 extern crate serde;
 extern crate serde_json;
 extern crate toybox_core;
@@ -5,11 +10,101 @@ extern crate toybox_core;
 extern crate lazy_static;
 #[macro_use]
 extern crate schemars;
+extern crate bincode;
 extern crate rand;
 
+/// This module contains the core logic of the game, and its screen and board constants.
 pub mod amidar;
+/// This module contains ``AdaptiveDifficulty``'s ``DifficultyController``, for changing levels as the game goes.
+pub mod difficulty;
+/// This module contains the font used for rendering scores.
 mod digit_sprites;
-mod types;
+/// This module contains the step/reset methods of ``AmidarEnv``, a wrapper around a config and its game.
+pub mod env;
+/// This module contains ``evaluate``, for scoring a policy over many seeds.
+pub mod eval;
+/// This module contains a determinism check for downstream builds and configs.
+pub mod selftest;
+/// This module contains a long-running random-play harness that checks game invariants every frame.
+pub mod soak;
+/// This module contains ``ai_harness``, for testing an enemy AI tile by tile on a small board.
+pub mod testing;
+/// This module contains the core data structures used in the game.
+pub mod types;
 
+// Configuration:
+pub use crate::types::AdaptiveDifficulty;
 pub use crate::types::Amidar;
+pub use crate::types::BoardGenerator;
+pub use crate::types::BonusGate;
+pub use crate::types::CollisionModel;
+pub use crate::types::EnemyRelease;
+pub use crate::types::ExplorationBonus;
+pub use crate::types::GameTime;
+pub use crate::types::LevelParams;
+pub use crate::types::ScoreDisplay;
+pub use crate::types::Symmetry;
+pub use crate::types::Theme;
+pub use crate::types::Unpainter;
+pub use crate::types::UpdateOrder;
+
+// Changing levels as the game goes:
+pub use crate::types::DifficultyController;
+pub use crate::types::LevelStats;
+
+// Stepping a game directly:
+pub use crate::types::AmidarEnv;
+pub use crate::types::StepResult;
+
+// Scoring a policy:
+pub use crate::types::Episode;
+pub use crate::types::Evaluation;
+
+// Checking builds and configs:
+pub use crate::types::DeterminismReport;
+pub use crate::types::Divergence;
+pub use crate::types::SoakReport;
+pub use crate::types::Violation;
+
+// Frame state:
+pub use crate::types::BoardUpdate;
+pub use crate::types::CatchFreeze;
+pub use crate::types::GameEvent;
+pub use crate::types::GamePhase;
+pub use crate::types::Mob;
+pub use crate::types::RewardBreakdown;
+pub use crate::types::RngDraw;
+pub use crate::types::RngPurpose;
+pub use crate::types::ScorePopup;
 pub use crate::types::State;
+pub use crate::types::StateCore;
+
+// Board and coordinates:
+pub use crate::types::Board;
+pub use crate::types::BoardProblem;
+pub use crate::types::BoardReport;
+pub use crate::types::Geometry;
+pub use crate::types::GridBox;
+pub use crate::types::ScreenPoint;
+pub use crate::types::Tile;
+pub use crate::types::TilePoint;
+pub use crate::types::WorldPoint;
+
+// Enemy AI:
+pub use crate::types::AiDecision;
+pub use crate::types::AiReason;
+pub use crate::types::EnemyMode;
+pub use crate::types::FrightenedPolicy;
+pub use crate::types::MovementAI;
+pub use crate::types::Surroundings;
+
+/// Everything needed to configure and play a game: ``use amidar::prelude::*;``.
+pub mod prelude {
+    pub use crate::types::{
+        Amidar, AmidarEnv, Board, GameEvent, MovementAI, State, StepResult, TilePoint,
+    };
+    pub use toybox_core::script::parse_script;
+    pub use toybox_core::Simulation as _;
+    pub use toybox_core::State as _;
+    pub use toybox_core::{AleAction, Direction, Input};
+}
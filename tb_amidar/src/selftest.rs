@@ -0,0 +1,102 @@
+use crate::types::{Amidar, DeterminismReport, Divergence, State};
+use rand::seq::SliceRandom;
+use toybox_core::random;
+use toybox_core::{AleAction, Input, Simulation};
+
+impl DeterminismReport {
+    /// Did every run match the reference run frame for frame?
+    pub fn is_deterministic(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// Check that the default config plays out identically from the same seed; see ``determinism_for``.
+pub fn determinism(seed: u32, n_frames: usize) -> DeterminismReport {
+    determinism_for(&Amidar::default(), seed, n_frames)
+}
+
+/// Step ``config`` through a seeded, random input script three times -- twice from scratch, and once saved to JSON and restored halfway through -- and report where the runs disagree.
+pub fn determinism_for(config: &Amidar, seed: u32, n_frames: usize) -> DeterminismReport {
+    let script = input_script(config, seed, n_frames);
+    let reference = run(config, seed, &script, None);
+
+    let mut divergences = Vec::new();
+    let runs = vec![
+        ("replay", run(config, seed, &script, None)),
+        ("restore", run(config, seed, &script, Some(n_frames / 2))),
+    ];
+    for (name, frames) in runs {
+        if let Some(frame) = first_difference(&reference, &frames) {
+            divergences.push(Divergence {
+                run: name.to_string(),
+                frame: frame + 1,
+            });
+        }
+    }
+
+    DeterminismReport {
+        seed,
+        n_frames,
+        divergences,
+    }
+}
+
+/// A random sequence of the legal actions, drawn from its own generator so the game's stays untouched.
+fn input_script(config: &Amidar, seed: u32, n_frames: usize) -> Vec<Input> {
+    let actions = config.legal_action_set();
+    let mut rng = random::Gen::new_from_seed(seed);
+    (0..n_frames)
+        .map(|_| {
+            actions
+                .choose(&mut rng)
+                .cloned()
+                .unwrap_or(AleAction::NOOP)
+                .to_input()
+        })
+        .collect()
+}
+
+/// Play the script from a freshly seeded game, optionally round-tripping the state through JSON after ``restore_at`` frames; returns the serialized state after every frame.
+fn run(config: &Amidar, seed: u32, script: &[Input], restore_at: Option<usize>) -> Vec<String> {
+    let mut config = config.clone();
+    config.reset_seed(seed);
+    let mut state: Box<dyn toybox_core::State> =
+        Box::new(State::try_new(&config).expect("determinism check needs a valid config"));
+
+    let mut frames = Vec::with_capacity(script.len());
+    for (i, input) in script.iter().enumerate() {
+        if Some(i) == restore_at {
+            state = config
+                .new_state_from_json(&state.to_json())
+                .expect("a saved state should load");
+        }
+        state.update_mut(*input);
+        frames.push(state.to_json());
+    }
+    frames
+}
+
+fn first_difference(expected: &[String], actual: &[String]) -> Option<usize> {
+    expected
+        .iter()
+        .zip(actual)
+        .position(|(e, a)| e != a)
+        .or_else(|| {
+            if expected.len() == actual.len() {
+                None
+            } else {
+                Some(expected.len().min(actual.len()))
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_deterministic() {
+        let report = determinism(17, 300);
+        assert!(report.is_deterministic(), "{:?}", report);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::types::{Amidar, SoakReport, State, Tile, Violation};
+use rand::Rng;
+use toybox_core::random;
+use toybox_core::{Simulation, State as _};
+
+/// How many frames pass between the (slower) serialization round-trip checks?
+const ROUND_TRIP_EVERY: usize = 97;
+
+/// Play ``frames`` frames of the default config with uniformly random legal actions, drawn from ``seed``, and check after every frame that:
+///
+/// - the player and every enemy stand on walkable tiles;
+/// - the score never goes down, except when penalty tiles are painted;
+/// - lives never exceed ``start_lives``, plus the ``extra_life_score`` life once it's won, and no timer is negative;
+/// - every so often, the state survives a JSON and a binary round trip unchanged.
+pub fn run(seed: u32, frames: usize) -> Result<SoakReport, Violation> {
+    run_config(&Amidar::default(), seed, frames)
+}
+
+/// ``run``, but playing ``config`` instead of the default game; handy for custom boards and enemy AIs.
+pub fn run_config(config: &Amidar, seed: u32, frames: usize) -> Result<SoakReport, Violation> {
+    let mut config = config.clone();
+    config.reset_seed(seed);
+    let actions = config.legal_action_set();
+    let mut rng = random::Gen::new_from_seed(seed);
+    let mut game = State::try_new(&config).map_err(|message| Violation {
+        frame: 0,
+        before: String::new(),
+        message,
+    })?;
+    let mut report = SoakReport {
+        frames: 0,
+        games: 1,
+        max_level: game.state.level,
+        max_score: game.state.score,
+    };
+
+    for frame in 1..=frames {
+        if game.state.game_over {
+            game = State::try_new(&config).expect("the config was valid a moment ago");
+            report.games += 1;
+        }
+        let before = game.clone();
+        let action = actions[rng.gen_range(0, actions.len())];
+        game.update_mut(action.to_input());
+
+        let checked = check_frame(&before, &game).and_then(|_| {
+            if frame % ROUND_TRIP_EVERY == 0 {
+                check_round_trips(&game)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(message) = checked {
+            return Err(Violation {
+                frame,
+                before: before.to_json(),
+                message,
+            });
+        }
+        report.frames = frame;
+        report.max_level = report.max_level.max(game.state.level);
+        report.max_score = report.max_score.max(game.state.score);
+    }
+    Ok(report)
+}
+
+fn penalty_tiles(game: &State) -> usize {
+    game.state
+        .board
+        .tiles
+        .iter()
+        .filter(|&&t| t == Tile::Penalty)
+        .count()
+}
+
+/// The invariants that must hold after every frame.
+fn check_frame(before: &State, after: &State) -> Result<(), String> {
+    let state = &after.state;
+    let player = state.player.position.to_tile();
+    if !state.board.get_tile(&player).walkable() {
+        return Err(format!("The player is off the track at {:?}.", player));
+    }
+    for (i, enemy) in state.enemies.iter().enumerate() {
+        let tile = enemy.position.to_tile();
+        if !state.board.get_tile(&tile).walkable() {
+            return Err(format!("Enemy {} is off the track at {:?}.", i, tile));
+        }
+    }
+
+    let same_level = state.level == before.state.level;
+    if state.score < before.state.score
+        && !(same_level && penalty_tiles(after) < penalty_tiles(before))
+    {
+        return Err(format!(
+            "The score went down from {} to {} without painting a penalty tile.",
+            before.state.score, state.score
+        ));
+    }
+
+    let max_lives = after.config.start_lives + if state.extra_life_awarded { 1 } else { 0 };
+    if state.lives > max_lives {
+        return Err(format!(
+            "{} lives is more than start_lives ({}) and any extra life.",
+            state.lives, after.config.start_lives
+        ));
+    }
+    let timers = [
+        ("chase_timer", state.chase_timer),
+        ("jump_timer", state.jump_timer),
+        ("grace_timer", state.grace_timer),
+        ("phase_timer", state.phase_timer),
+        ("mode_timer", state.mode_timer),
+        ("penalty_slow_timer", state.penalty_slow_timer),
+        (
+            "catch_freeze",
+            state.catch_freeze.as_ref().map_or(0, |f| f.frames_left),
+        ),
+    ];
+    for (name, value) in timers.iter() {
+        if *value < 0 {
+            return Err(format!("{} is negative: {}.", name, value));
+        }
+    }
+    Ok(())
+}
+
+/// Saving and loading must give back exactly the same state.
+fn check_round_trips(game: &State) -> Result<(), String> {
+    let json = game.to_json();
+    let from_json = game
+        .config
+        .new_state_from_json(&json)
+        .map_err(|e| format!("The JSON savestate didn't load: {}", e))?;
+    if from_json.to_json() != json {
+        return Err("The JSON savestate loaded as a different state.".to_owned());
+    }
+    let from_bytes = State::from_bytes(&game.config, &game.to_bytes())
+        .map_err(|e| format!("The binary savestate didn't load: {}", e))?;
+    if from_bytes.to_json() != json {
+        return Err("The binary savestate loaded as a different state.".to_owned());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::TilePoint;
+
+    #[test]
+    fn test_soak() {
+        let report = run(7, 3000).unwrap();
+        assert_eq!(report.frames, 3000);
+        assert!(report.games >= 1);
+        assert_eq!(run(7, 3000), Ok(report));
+    }
+
+    #[test]
+    fn test_soak_reports_violations() {
+        // A start off the track is caught on the very first frame.
+        let config = Amidar {
+            player_start: TilePoint::new(1, 1),
+            default_board_bugs: false,
+            ..Amidar::default()
+        };
+        let violation = run_config(&config, 1, 100).unwrap_err();
+        assert_eq!(violation.frame, 1);
+        assert!(violation.message.contains("off the track"));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::amidar::world;
+use crate::types::{Board, EnemyMode, FrightenedPolicy, Mob, MovementAI, Surroundings, TilePoint};
+use toybox_core::random;
+use toybox_core::Input;
+
+/// Fast enough that a mob reaches the next tile every frame.
+const ONE_TILE_PER_FRAME: i32 = world::TILE_SIZE.0 + world::TILE_SIZE.1;
+
+/// Play a single enemy with ``ai`` on ``board`` (rows of tiles, as in ``Amidar::board``), while the player walks ``player_path``; returns the tile the enemy is on after each step.
+///
+/// The enemy moves exactly one tile per step, and sets off from its start tile before the first one. The player stands on ``player_path[i]`` during step ``i``, facing the next tile of the path.
+/// With ``frightened``, the player can catch the enemy throughout, and the enemy moves as that policy says. Nobody is ever caught, so the enemy never restarts.
+pub fn ai_harness(
+    board: &[&str],
+    ai: MovementAI,
+    player_path: &[TilePoint],
+    frightened: Option<FrightenedPolicy>,
+) -> Result<Vec<TilePoint>, String> {
+    let lines: Vec<String> = board.iter().map(|&row| row.to_owned()).collect();
+    let mut board = Board::try_new(&lines)?;
+    let mut enemy = board.make_enemy(ai, ONE_TILE_PER_FRAME);
+    let mut rng = random::Gen::new_from_seed(17);
+
+    let mut tiles = Vec::new();
+    if player_path.is_empty() {
+        return Ok(tiles);
+    }
+    for i in 0..=player_path.len() {
+        // Before the first step (i = 0) the enemy only sets off, with the player already where the path starts.
+        let here = &player_path[i.max(1) - 1];
+        let mut player = Mob::new_player(here.to_world(), ONE_TILE_PER_FRAME);
+        player.step = player_path.get(i).cloned();
+        let enemy_tile = enemy.position.to_tile();
+        enemy.update(
+            Input::default(),
+            &mut board,
+            Surroundings {
+                player: Some(player),
+                enemies: &[enemy_tile],
+                mode: EnemyMode::Pursue,
+                catchable: frightened.is_some(),
+                frightened_policy: frightened.unwrap_or(FrightenedPolicy::Unchanged),
+                elroy: false,
+            },
+            0,
+            &mut rng,
+            None,
+        );
+        if i > 0 {
+            tiles.push(enemy.position.to_tile());
+        }
+    }
+    Ok(tiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toybox_core::Direction;
+
+    const LADDER: &[&str] = &["=========", "=   =   =", "=   =   =", "========="];
+
+    fn path(points: &[(i32, i32)]) -> Vec<TilePoint> {
+        points.iter().map(|&(x, y)| TilePoint::new(x, y)).collect()
+    }
+
+    #[test]
+    fn test_chase_takes_the_short_way() {
+        let chase = MovementAI::EnemyChase {
+            start: TilePoint::new(0, 0),
+            start_dir: Direction::Right,
+            dir: Direction::Right,
+        };
+        let tiles = ai_harness(LADDER, chase, &vec![TilePoint::new(8, 3); 11], None).unwrap();
+        assert_eq!(
+            tiles,
+            path(&[
+                (1, 0),
+                (2, 0),
+                (3, 0),
+                (4, 0),
+                (5, 0),
+                (6, 0),
+                (7, 0),
+                (8, 0),
+                (8, 1),
+                (8, 2),
+                (8, 3)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_target_player_runs_when_catchable() {
+        let target = MovementAI::EnemyTargetPlayer {
+            start: TilePoint::new(6, 0),
+            start_dir: Direction::Left,
+            vision_distance: 10,
+            line_of_sight: true,
+            dir: Direction::Left,
+            player_seen: None,
+        };
+        let player = vec![TilePoint::new(2, 0); 3];
+        let hunting = ai_harness(LADDER, target.clone(), &player, None).unwrap();
+        assert_eq!(hunting, path(&[(5, 0), (4, 0), (3, 0)]));
+        let running =
+            ai_harness(LADDER, target, &player, Some(FrightenedPolicy::Unchanged)).unwrap();
+        assert_eq!(running, path(&[(7, 0), (8, 0), (8, 1)]));
+    }
+}
@@ -3,18 +3,31 @@ use serde::{Deserialize, Serialize};
 use toybox_core::graphics::Color;
 use toybox_core::random;
 use toybox_core::Direction;
+use toybox_core::Input;
 
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Arc;
 
 /// This struct represents the configuration of an Amidar game, and affects any new games generated from it.
+/// Fields a config leaves out take their ``Amidar::default()`` values, so configs written before a field existed still load.
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct Amidar {
     /// The random number generator that seeds new games.
     pub rand: random::Gen,
     /// A representation of the board as a list of strings.
+    /// Digits ``1`` to ``9`` are unpainted track where ``enemies[0]`` to ``enemies[8]`` start, overriding the ``start`` of their AI; likewise, a ``P`` is where the player starts, overriding ``player_start``.
     pub board: Vec<String>,
+    /// More layouts for ``level_board_order`` to pick from: ``board`` is layout 0, and these are 1, 2, ...
+    pub boards: Vec<Vec<String>>,
+    /// Which layout does each level use, starting from level 1? Levels past the end start over from the beginning; empty means always ``board``.
+    pub level_board_order: Vec<usize>,
+    /// Should each level get a freshly generated board instead? Overrides ``board``, ``boards``, ``player_start`` and ``default_board_bugs``.
+    pub board_generator: Option<BoardGenerator>,
     /// Where does the player start on a new life?
     pub player_start: TilePoint,
+    /// Which named palette was applied to the colors below? ``from_json`` fills in every color field the JSON leaves out from this theme.
+    pub theme: Option<Theme>,
     /// What is the background color?
     pub bg_color: Color,
     /// What is the player rectangle color?
@@ -27,30 +40,414 @@ pub struct Amidar {
     pub enemy_color: Color,
     /// What color do we fill the rectangles with when the player paints their circumference.
     pub inner_painted_color: Color,
+    /// What color are penalty tiles (``x`` on the board) before they are painted?
+    pub penalty_color: Color,
     /// How many lives do new games start with?
     pub start_lives: i32,
+    /// Does clearing a level give back a lost life (up to ``start_lives``)?
+    pub refill_lives_on_level_clear: bool,
+    /// The score at which the player wins one extra life, beyond ``start_lives``; None never gives one.
+    pub extra_life_score: Option<i32>,
+    /// Should ``lives()`` count the life being played, as ALE does, rather than just the ones left after it? The game ends at the same point either way.
+    pub count_life_in_play: bool,
     /// How many jumps do new games start with?
     pub start_jumps: i32,
-    /// Should we show images/sprites (true) or just colored rectangles (false).
+    /// Should we show images/sprites (true) or just colored rectangles (false). Sprites need the ``graphics`` feature.
     pub render_images: bool,
-    /// How long does chase mode last for, in frames?
-    pub chase_time: i32,
+    /// How long does chase mode last for?
+    pub chase_time: GameTime,
     /// How much is eating an enemy in chase/chickens mode worth?
     pub chase_score_bonus: i32,
+    /// Each further enemy caught in the same chase mode is worth this many times the one before; 1 keeps every catch at ``chase_score_bonus``.
+    pub chase_bonus_factor: i32,
+    /// What is the most a single catch can be worth, however long the chain?
+    pub chase_bonus_max: Option<i32>,
+    /// If more than 0, a caught enemy goes back to its ``respawn_point`` and sits there, harmless, for this many frames; otherwise it carries on where it was caught.
+    /// Should chase mode outlast the wait, the enemy can't be caught again until the next one.
+    pub immobilized_time: i32,
+    /// Where does ``immobilized_time`` send a caught enemy? None, or a tile that isn't track on the current board, means its own start tile. Levels and lives still start enemies at their starts.
+    pub respawn_point: Option<TilePoint>,
+    /// Per enemy (by index), somewhere else for a caught enemy to go than ``respawn_point``; enemies past the end, or with None, use ``respawn_point``.
+    pub enemy_respawn_points: Vec<Option<TilePoint>>,
+    /// With ``immobilized_time``: instead of jumping there, a caught enemy goes back to its ``respawn_point`` as harmless eyes, the shortest way along the track at this speed, and only then starts waiting.
+    pub eyes_speed: Option<i32>,
+    /// At the start of each level and after each lost life, enemy ``i`` waits on its start tile for ``start_immobilized_base * i`` frames, so they set off one by one.
+    pub start_immobilized_base: i32,
+    /// For how many frames before chase mode ends do the enemies flash back to their usual look, as a warning? 0 never flashes.
+    pub vulnerable_warning_frames: i32,
+    /// How many frames does each half of that flash last?
+    pub flash_period_frames: i32,
     /// How long does the invulnerable jump_time last?
-    pub jump_time: i32,
+    pub jump_time: GameTime,
     /// How many points do you get for filling a box?
     pub box_bonus: i32,
+    /// How many points does painting each penalty tile take away?
+    pub penalty_tile_cost: i32,
+    /// For how many frames after painting a penalty tile does the player move at half speed?
+    pub penalty_slow_frames: i32,
     /// This should be false if you ever use a non-default board.
     pub default_board_bugs: bool,
     /// What AIs should we use to spawn enemies on a new game?
     pub enemies: Vec<MovementAI>,
+    /// When does each enemy (by position in ``enemies``) start moving? Enemies without an entry move at once.
+    pub enemy_releases: Vec<EnemyRelease>,
+    /// How many of ``enemies``, counting from the first, are in play on each level, starting from level 1? Levels past the end use the last entry; empty means all of them.
+    pub enemies_per_level: Vec<usize>,
+    /// What changes from level to level, starting from level 1? Levels past the end use the last entry; empty means every level plays the same, apart from the built-in enemy speed-ups.
+    pub difficulty_table: Vec<LevelParams>,
+    /// If set, each new level is made harder or easier depending on how many lives the last one cost; its adjustments replace ``difficulty_table`` from then on.
+    pub adaptive_difficulty: Option<AdaptiveDifficulty>,
     /// How many previous junctions should the player and enemies remember?
     pub history_limit: u32,
     /// How fast do enemies move?
     pub enemy_starting_speed: i32,
+    /// How fast do enemies move during chase mode? Levels speed this up just like ``enemy_starting_speed``.
+    pub chase_enemy_speed: i32,
+    /// How much faster (or, if negative, slower) than the shared speed is each enemy, by position in ``enemies``? Enemies without an entry move at the shared speed.
+    pub enemy_speed_offsets: Vec<i32>,
+    /// Once only this many tiles are left to paint, the first enemy goes "Cruise Elroy" like the arcade's red ghost: ``elroy_part1_speed`` faster, and straight for the player.
+    pub elroy_part1_tiles: Option<u32>,
+    /// How much faster is the first enemy in the first Elroy stage?
+    pub elroy_part1_speed: i32,
+    /// Once only this many tiles are left to paint, the first enemy moves on to the second Elroy stage; should be below ``elroy_part1_tiles``.
+    pub elroy_part2_tiles: Option<u32>,
+    /// How much faster is the first enemy in the second Elroy stage?
+    pub elroy_part2_speed: i32,
+    /// How do enemies the player can catch move? Enemies following a lookup table stay on it either way.
+    pub frightened_policy: FrightenedPolicy,
     /// How fast does the player move?
     pub player_speed: i32,
+    /// Should enemies record why they chose each move? See the ``ai_debug_trace`` query.
+    pub debug_ai: bool,
+    /// The per-frame reward is the score change multiplied by this factor.
+    pub reward_scale: f32,
+    /// Should the per-frame reward be clipped to [-1, 1] after scaling? (Common Atari preprocessing.)
+    pub reward_clip: bool,
+    /// How many points do you get for clearing (painting) a whole board?
+    pub level_clear_bonus: i32,
+    /// Should a frame the player dies on take back the points scored on it? Off by default, so score differences between frames always add up to the score.
+    pub rollback_score_on_death: bool,
+    /// How thoroughly are enemy/player collisions checked each frame?
+    pub collision_model: CollisionModel,
+    /// If set, the player and an enemy meet when their positions are at most this far apart (in world units; a tile is ``world::TILE_SIZE``), rather than whenever they share a tile. Tile swaps still count as meeting.
+    pub collision_radius_world_units: Option<i32>,
+    /// Who moves first each frame: the player or the enemies?
+    pub update_order: UpdateOrder,
+    /// If set, a Stalled event fires once the player has stayed on one tile for this many frames.
+    pub stall_limit: Option<i32>,
+    /// Should a stall also end the game (by taking all remaining lives)?
+    pub stall_ends_game: bool,
+    /// Should we count how many times the player enters each tile? See the ``visit_counts`` query.
+    pub track_visits: bool,
+    /// An intrinsic reward, added to the per-frame reward (never the score), for exploring.
+    pub exploration_bonus: ExplorationBonus,
+    /// How many recent events should we keep for clients that step several frames between polls? Older ones are dropped first.
+    pub event_log_limit: usize,
+    /// What should the on-screen score show? Scoring itself is unaffected.
+    pub score_display: ScoreDisplay,
+    /// How many frames make a second? Converts ``GameTime::Seconds``; change it alongside any frame skip.
+    pub frames_per_second: f32,
+    /// For how many frames should the game pause, showing the bonus, when an enemy is caught? 0 disables the pause.
+    pub catch_freeze_frames: i32,
+    /// For how many frames should points scored be shown where they were scored? 0 (the default) shows none, keeping pixel observations unchanged.
+    pub score_popup_frames: i32,
+    /// Should we log every place the game's random number generator is drawn from? See the ``rng_audit`` query.
+    pub audit_rng: bool,
+    /// Should the ``terminal`` query report the end of an episode on every lost life, not just game over? The game itself carries on either way.
+    pub episodic_life: bool,
+    /// For how many frames after losing a life can enemies not hurt the player?
+    pub respawn_grace_frames: i32,
+    /// For how many frames does the game stand still, the player blinking where it was caught, before everyone goes back to the start? 0 resets at once.
+    pub death_pause_frames: i32,
+    /// For how many frames after that reset does the game stand still before play resumes?
+    pub respawn_pause_frames: i32,
+    /// For how many frames does a new game or level stand still before play starts?
+    pub ready_frames: i32,
+    /// For how many frames does a finished board stay on screen before the next level is set up?
+    pub level_complete_frames: i32,
+    /// Should the player blink while that grace period lasts?
+    pub blink_during_grace: bool,
+    /// Once the game is over, should the screen show the board dimmed with the final score in the middle, instead of going blank? Videos then end on something legible.
+    pub game_over_screen: bool,
+    /// How many frames does each ``update_mut`` play with the same input? Rewards and events add up over them.
+    pub frame_skip: u32,
+    /// How likely is each frame to repeat the last frame's buttons instead of the new ones, as in ALE's sticky actions?
+    pub action_repeat_probability: f32,
+    /// Should a direction keep being pressed until another one is, like an arcade joystick, instead of only while it's held? Much easier to play from a keyboard.
+    pub latch_directions: bool,
+    /// Can the player turn around between tiles by pressing the opposite direction, as in the arcade? Otherwise it can only turn on reaching a tile.
+    pub reverse_mid_step: bool,
+    /// Should a direction pressed between tiles still be taken on reaching the next tile, even if it has been let go by then? ``cornering_tolerance`` always does this.
+    pub buffer_turns: bool,
+    /// If set, the player remembers the last direction pressed and turns that way as soon as it can, keeping on the way it's heading until then, as in the arcade. A turn is taken early, cutting the corner, from at most this many world units short of the tile it turns at. None moves the player exactly as the buttons held say.
+    pub cornering_tolerance: Option<i32>,
+    /// How many of the latest board tensors does ``stacked_board_tensor`` hand back together, one per ``update_mut``? 0 keeps none.
+    pub observation_stack: usize,
+    /// Which modes do the targeting enemies (chase, ambush, flank, shy) go through, and for how many frames each? The last one lasts until the next life or level. Empty means always ``Pursue``.
+    pub mode_schedule: Vec<(EnemyMode, i32)>,
+    /// Should enemies turn around on the spot whenever chase mode starts or ``mode_schedule`` moves on, as the arcade ghosts do? Enemies on lookup tables or the perimeter never do.
+    pub reverse_on_mode_change: bool,
+    /// Which tiles pay a bonus for reaching them quickly after the level starts?
+    pub bonus_gates: Vec<BonusGate>,
+    /// What color are bonus gates that haven't been reached yet this level?
+    pub gate_color: Color,
+    /// Does an enemy undo the player's painting as it goes?
+    pub unpainter: Option<Unpainter>,
+}
+
+/// Settings for generating Amidar-style boards: full-width lines a few rows apart, joined by vertical lines at random columns.
+/// The outer edges are always track, so every generated board is connected, and its four corners are chase markers.
+/// The player starts halfway down the right edge. Enemies need AIs that don't follow the default board's routes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BoardGenerator {
+    /// How many tiles wide? At least 5.
+    pub width: u32,
+    /// How many tiles tall? At least 5.
+    pub height: u32,
+    /// Should the two halves mirror each other?
+    pub symmetry: Symmetry,
+    /// Seeds the layout; each level mixes in its number so levels differ.
+    pub seed: u32,
+}
+
+/// A named palette covering every color field of ``Amidar``; see ``Amidar::apply_theme``.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Theme {
+    /// The default colors.
+    Classic,
+    /// Shades of gray only.
+    Mono,
+    /// Saturated colors on a dark background.
+    Neon,
+    /// Dark ink on a light background.
+    Paper,
+}
+
+/// Which mirror symmetry should a generated board have?
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Symmetry {
+    /// No symmetry.
+    None,
+    /// The left half mirrors the right, across a vertical line through the middle.
+    Vertical,
+}
+
+/// An enemy that unpaints track behind it, so painting the board is no longer one-way.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Unpainter {
+    /// Which enemy (index in ``enemies``)?
+    pub enemy: usize,
+    /// It unpaints every this-many tiles it enters; 1 unpaints everything it walks over.
+    pub every_tiles: u32,
+}
+
+/// A tile worth a bonus for reaching it early in a level: ``max_bonus`` on the first frame, falling linearly to nothing after ``decay_frames``.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BonusGate {
+    /// Where is the gate?
+    pub tile: TilePoint,
+    /// What is it worth at the very start of the level?
+    pub max_bonus: i32,
+    /// After how many frames of the level is it worth nothing?
+    pub decay_frames: i32,
+}
+
+/// What's different about one level; see ``Amidar::difficulty_table``. Anything left out comes from the rest of the config.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LevelParams {
+    /// How fast do enemies move? Replaces ``enemy_starting_speed`` and its built-in speed-ups.
+    pub enemy_speed: Option<i32>,
+    /// How fast do enemies move during chase mode? Replaces ``chase_enemy_speed`` and its built-in speed-ups.
+    pub chase_enemy_speed: Option<i32>,
+    /// How fast does the player move?
+    pub player_speed: Option<i32>,
+    /// How long does chase mode last?
+    pub chase_time: Option<GameTime>,
+    /// How many points do you get for filling a box?
+    pub box_bonus: Option<i32>,
+}
+
+/// How a level went, as a ``DifficultyController`` sees it once the level is cleared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LevelStats {
+    /// Which level was just cleared? 1-based.
+    pub level: i32,
+    /// How many frames did it take to clear?
+    pub frames: i32,
+    /// How many lives were lost on it?
+    pub deaths: i32,
+}
+
+/// Decides what the next level plays like, from how the last one went; see ``State::adjust_difficulty``.
+pub trait DifficultyController {
+    /// ``next`` is what the next level would play with, every field filled in; return what it should play with instead.
+    fn next_level(&mut self, stats: &LevelStats, next: LevelParams) -> LevelParams;
+}
+
+/// A ``DifficultyController`` that aims for a steady number of deaths per level: after a level that cost fewer lives, the next is a step harder; after one that cost more, a step easier.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AdaptiveDifficulty {
+    /// How many lives should a level cost?
+    pub target_deaths: i32,
+    /// How much faster (harder) or slower (easier) do enemies get each step, in and out of chase mode?
+    pub enemy_speed_step: i32,
+    /// How many frames shorter (harder) or longer (easier) does chase mode get each step? It always lasts at least a frame.
+    pub chase_frames_step: i32,
+    /// Enemies never get slower than this.
+    pub min_enemy_speed: i32,
+}
+
+/// Which part of the game is playing? Everything but ``Playing`` stands still and ignores inputs.
+/// States saved before phases existed were always ``Playing``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum GamePhase {
+    /// Waiting to start a new game or level (``ready_frames``), or to carry on after losing a life (``respawn_pause_frames``).
+    Ready,
+    /// The player is in control.
+    #[default]
+    Playing,
+    /// The player was just caught and blinks where it was (``death_pause_frames``).
+    Dying,
+    /// The board was just finished; the next level waits (``level_complete_frames``).
+    LevelComplete,
+    /// No lives are left; see ``StateCore::game_over``.
+    GameOver,
+}
+
+/// What are the targeting enemies trying to do? (Not to be confused with chase mode, where the player hunts the enemies.)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum EnemyMode {
+    /// Head back toward their own start tiles, giving the player a breather.
+    Scatter,
+    /// Go after the player, each in its own way.
+    Pursue,
+}
+
+/// How do enemies move while the player can catch them?
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum FrightenedPolicy {
+    /// Just as they always do, only at ``chase_enemy_speed``.
+    Unchanged,
+    /// Like the arcade's frightened ghosts: pick a random way out at every junction.
+    Random,
+    /// Take the way out that ends up furthest (straight-line) from the player at every junction.
+    Flee,
+}
+
+/// A length of game time, given in whichever unit is clearer; see ``frames_per_second``.
+/// A bare integer, as configs used to give these, still reads as ``Frames`` from JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub enum GameTime {
+    /// An exact number of frames.
+    Frames(i32),
+    /// Wall-clock seconds at the configured frame rate.
+    Seconds(f32),
+}
+
+/// Every way a ``GameTime`` can be written in JSON.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GameTimeJson {
+    Frames(i32),
+    Tagged(TaggedGameTime),
+}
+
+/// ``GameTime`` as it serializes, and all binary savestates can hold.
+#[derive(Deserialize)]
+enum TaggedGameTime {
+    Frames(i32),
+    Seconds(f32),
+}
+
+impl From<GameTimeJson> for GameTime {
+    fn from(json: GameTimeJson) -> GameTime {
+        match json {
+            GameTimeJson::Frames(frames) | GameTimeJson::Tagged(TaggedGameTime::Frames(frames)) => {
+                GameTime::Frames(frames)
+            }
+            GameTimeJson::Tagged(TaggedGameTime::Seconds(seconds)) => GameTime::Seconds(seconds),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for GameTime {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<GameTime, D::Error> {
+        // Guessing which form we were given needs a self-describing format; bincode isn't one.
+        let json = if deserializer.is_human_readable() {
+            GameTimeJson::deserialize(deserializer)?
+        } else {
+            GameTimeJson::Tagged(TaggedGameTime::deserialize(deserializer)?)
+        };
+        Ok(json.into())
+    }
+}
+
+/// What do the score digits at the top of the screen show?
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ScoreDisplay {
+    /// The real score.
+    Shown,
+    /// Nothing; the lives display stays.
+    Hidden,
+    /// A random number with as many digits as the real score, redrawn every frame.
+    Randomized,
+}
+
+/// How thoroughly are enemy/player collisions checked each frame?
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum CollisionModel {
+    /// Only check after the player moves; a player and an enemy may pass through each other.
+    PassThrough,
+    /// Check again after the enemies move, and count a player and enemy that swapped tiles as meeting, so the player can't run through enemies.
+    Recheck,
+    /// Like Recheck, but an enemy also collides with the player on the tile it just left.
+    Strict,
+}
+
+/// Who moves first each frame? This decides who wins races at frame boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum UpdateOrder {
+    /// The player moves, then the enemies react to the player's new position.
+    PlayerFirst,
+    /// The enemies move, then the player.
+    EnemiesFirst,
+    /// Everyone moves based on the previous frame; a player and enemy that swap tiles collide.
+    Simultaneous,
+}
+
+/// An intrinsic reward for reaching places the player hasn't been yet this game.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum ExplorationBonus {
+    /// No bonus.
+    Off,
+    /// Reward the first visit to every tile by this amount.
+    Tiles(f32),
+    /// Reward the first visit to every junction by this amount.
+    Junctions(f32),
+}
+
+/// Everything an external renderer needs to convert between pixel, world, and tile coordinates.
+/// Rectangles are (x, y, width, height) in pixels.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Geometry {
+    /// Size of a whole frame, in pixels.
+    pub game_size: (i32, i32),
+    /// Pixel position of the top-left corner of tile (0, 0).
+    pub board_offset: (i32, i32),
+    /// Size of the board, in tiles.
+    pub board_tiles: (i32, i32),
+    /// Size of one tile, in pixels.
+    pub tile_size: (i32, i32),
+    /// How many world units there are per pixel.
+    pub world_scale: i32,
+    /// Size of the player sprite, in pixels.
+    pub player_size: (i32, i32),
+    /// Size of an enemy sprite, in pixels.
+    pub enemy_size: (i32, i32),
+    /// Where the current score is drawn.
+    pub score_region: (i32, i32, i32, i32),
+    /// Where the lives markers are drawn, given the most lives a game can have.
+    pub lives_region: (i32, i32, i32, i32),
 }
 
 /// When things are drawn, they are drawn in screen coordinates, i.e., pixels.
@@ -97,6 +494,17 @@ pub enum Tile {
     ChaseMarker,
     /// During play, the same as Empty; Walkable tiles you haven't painted yet. Used to form GridBox objects with triggers_chase=true.
     Painted,
+    /// Walkable tiles that cost points (see ``Amidar::penalty_tile_cost``) to paint; the level can be cleared without them, but not the boxes they border.
+    Penalty,
+}
+
+/// When an enemy starts moving: it waits, harmless, on its start tile until either condition is met each level.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct EnemyRelease {
+    /// Release after this many frames of the level.
+    pub after_frames: Option<i32>,
+    /// Release once this many tiles have been painted this level.
+    pub after_tiles_painted: Option<u32>,
 }
 
 /// MovementAI represents Mob (enemy/player) logic for movement.
@@ -138,7 +546,7 @@ pub enum MovementAI {
         /// Which direction am I currently moving?
         dir: Direction,
     },
-    /// Move randomly unless the player is within some fixed Manhattan distance of this enemy -- in that case, move toward the player.
+    /// Move randomly unless the player is within some fixed Manhattan distance of this enemy -- in that case, move toward the player, or away from it while it can catch me.
     EnemyTargetPlayer {
         /// Where do I start?
         start: TilePoint,
@@ -146,11 +554,158 @@ pub enum MovementAI {
         start_dir: Direction,
         /// How far (Manhattan distance) can I see?
         vision_distance: i32,
+        /// Must the player also be down a straight, unbroken stretch of track from me? If not, I can see around corners.
+        #[serde(default)]
+        line_of_sight: bool,
         /// Which direction am I currently moving?
         dir: Direction,
         /// We lock onto a player's position when we see it, so that we can actually be evaded.
         player_seen: Option<TilePoint>,
     },
+    /// Like the arcade's red ghost: at every junction, turn toward the player's tile.
+    EnemyChase {
+        /// Where do I start?
+        start: TilePoint,
+        /// Which direction to move first?
+        start_dir: Direction,
+        /// Which direction am I currently moving?
+        dir: Direction,
+    },
+    /// Like the arcade's pink ghost: at every junction, turn toward a tile some way ahead of the player.
+    EnemyAmbush {
+        /// Where do I start?
+        start: TilePoint,
+        /// Which direction to move first?
+        start_dir: Direction,
+        /// Which direction am I currently moving?
+        dir: Direction,
+        /// How many tiles ahead of the player do I aim? (The arcade uses 4.)
+        lookahead: i32,
+    },
+    /// Like the arcade's cyan ghost: aim at the point opposite a partner enemy, mirrored through a tile ahead of the player, so the two close in from both sides.
+    EnemyFlank {
+        /// Where do I start?
+        start: TilePoint,
+        /// Which direction to move first?
+        start_dir: Direction,
+        /// Which direction am I currently moving?
+        dir: Direction,
+        /// How many tiles ahead of the player is the mirror point? (The arcade uses 2.)
+        lookahead: i32,
+        /// Which enemy (index in the enemy list) do I flank with? Without one, I just chase.
+        partner: usize,
+    },
+    /// Like the arcade's orange ghost: chase the player from afar, but head for a retreat tile when close.
+    EnemyShy {
+        /// Where do I start?
+        start: TilePoint,
+        /// Which direction to move first?
+        start_dir: Direction,
+        /// Which direction am I currently moving?
+        dir: Direction,
+        /// Within how many tiles (straight-line) of the player do I give up? (The arcade uses 8.)
+        shy_distance: i32,
+        /// Where do I go when I give up?
+        retreat: TilePoint,
+    },
+    /// Cycle through other AIs, using each for a fixed number of frames, e.g., random for 300 frames and then targeting the player for 600.
+    EnemyScheduled {
+        /// Which AIs to use, and for how many frames each; wraps around after the last one. The first one decides where we start.
+        schedule: Vec<(MovementAI, i32)>,
+        /// Which entry of the schedule is active?
+        cursor: usize,
+        /// How many frames has the active entry been in use?
+        frames: i32,
+    },
+}
+
+/// Why did an enemy AI pick the tile it is now moving toward?
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Debug, JsonSchema)]
+pub enum AiReason {
+    /// Followed a fixed route or rule that never branches (lookup tables, perimeter loops).
+    Scripted,
+    /// Kept going in the current direction.
+    Continue,
+    /// Picked a new direction because it stood on a junction.
+    Junction,
+    /// Picked a new direction because the current one was blocked.
+    Blocked,
+    /// Moved toward the player, or toward where the player was last seen.
+    TargetSeeking,
+    /// Moved away from a player that could catch it.
+    Fleeing,
+}
+
+/// A record of a single enemy movement decision, kept when ``debug_ai`` is set.
+#[derive(Clone, Serialize, Deserialize, Debug, JsonSchema)]
+pub struct AiDecision {
+    /// Which tile was the enemy standing on when it decided?
+    pub position: TilePoint,
+    /// Which directions did its AI weigh up? Just the one it took when it had no choice to make.
+    pub considered: Vec<Direction>,
+    /// Which direction did it take? None if it chose to stand still or jumped to a non-adjacent tile.
+    pub chosen: Option<Direction>,
+    /// Which rule made the choice?
+    pub reason: AiReason,
+}
+
+/// Something notable that happened during the most recent frame.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug, JsonSchema)]
+pub enum GameEvent {
+    /// The board was completed and the game moved on to a new level.
+    LevelCleared {
+        /// Which level was just cleared? 1-based.
+        level: i32,
+        /// How many frames did it take to clear?
+        frames: i32,
+        /// How many frames had the game run, all levels together, when it was cleared?
+        total_frames: i32,
+        /// How many lives were lost on this level?
+        deaths: i32,
+    },
+    /// The player has not changed tiles for ``stall_limit`` frames.
+    Stalled {
+        /// Where is the player stuck?
+        tile: TilePoint,
+    },
+    /// The player reached a bonus gate.
+    GateReached {
+        /// Which gate (index in ``Amidar::bonus_gates``)?
+        gate: usize,
+        /// How many points was it worth by then?
+        bonus: i32,
+    },
+    /// The ``unpainter`` enemy unpainted a tile, along with any boxes it bordered.
+    TileUnpainted {
+        /// Which enemy did it?
+        enemy: usize,
+        /// Which tile needs painting again?
+        tile: TilePoint,
+    },
+    /// An enemy caught the player, costing a life.
+    PlayerKilled {
+        /// Which enemy (index in state.enemies) did it?
+        enemy: usize,
+    },
+    /// The player caught an enemy during chase mode.
+    EnemyCaught {
+        /// Which enemy (index in state.enemies) was caught?
+        enemy: usize,
+    },
+    /// The player painted the segment between two junctions.
+    SegmentPainted {
+        /// The junction the segment starts at.
+        from: TilePoint,
+        /// The junction it ends at.
+        to: TilePoint,
+    },
+    /// Painting a segment filled in boxes.
+    BoxesFilled {
+        /// How many?
+        count: i32,
+    },
+    /// Filling a corner box started chase mode.
+    ChaseStarted,
 }
 
 /// Mob is a videogame slang for "mobile" unit. Players and Enemies are the same struct.
@@ -160,34 +715,228 @@ pub struct Mob {
     pub ai: MovementAI,
     /// Where is this unit placed (WorldPoint represents sub-pixels!)
     pub position: WorldPoint,
-    /// Have I been caught/eaten in chase mode?
+    /// Have I been caught/eaten in this chase mode? Cleared when chase mode starts or ends.
     pub caught: bool,
+    /// Was I caught during the most recent frame?
+    #[serde(default)]
+    pub caught_this_frame: bool,
+    /// Did I come back (after a lost life) or start moving during the current chase mode? Then I can't be caught until the next one.
+    #[serde(default)]
+    pub chase_immune: bool,
+    /// For how many more frames do I sit still where I (re)started, harmless? See ``Amidar::immobilized_time``.
+    #[serde(default)]
+    pub immobilized_timer: i32,
+    /// Was I caught, and am I on my way back to my respawn point as eyes? See ``Amidar::eyes_speed``.
+    #[serde(default)]
+    pub eaten: bool,
     /// How fast do I get to move?
     pub speed: i32,
     /// Am I currently moving toward a point?
     pub step: Option<TilePoint>,
     /// Which junctions have I visited most recently?
     pub history: VecDeque<u32>,
+    /// Am I moving yet? Held enemies wait on their start tile and can't hurt the player; see ``Amidar::enemy_releases``.
+    #[serde(default = "released_by_default")]
+    pub released: bool,
+    /// Which way did I last set off? None until my first move after a (re)start.
+    #[serde(default)]
+    pub heading: Option<Direction>,
+    /// Which way will I turn as soon as I can? Only the player queues turns; see ``buffer_turns``.
+    #[serde(default)]
+    pub queued_direction: Option<Direction>,
+}
+
+/// Enemies in states saved before ``released`` existed were always moving.
+fn released_by_default() -> bool {
+    true
 }
 
 /// Board represents the Amidar level/board and all associated information.
-#[derive(Clone, Serialize, Deserialize, JsonSchema)]
+/// Its parts are shared between copies of a board (cheap ``State::copy`` for tree search) until one copy changes them.
+/// In JSON the tiles are still a list of rows; see ``BoardRows``.
+#[derive(Clone)]
 pub struct Board {
-    /// What are the state of the tiles on the board: rows first, then columns.
-    pub tiles: Vec<Vec<Tile>>,
+    /// What are the state of the tiles on the board, one row after another: tile (x, y) is at ``y * width + x``. Read them through ``get_tile`` or ``rows``, and change them through ``set_tile`` or ``edit_tiles``, which keep ``tiles_to_paint`` in step.
+    pub(crate) tiles: Arc<Vec<Tile>>,
     /// How wide is the board?
     pub width: u32,
     /// How tall is the board?
     pub height: u32,
     /// Which positions (y*width + x) are junctions? Helps MovementAI and painting game logic!
-    pub junctions: HashSet<u32>,
+    pub junctions: Arc<BTreeSet<u32>>,
+    /// Which junctions trigger chases?
+    pub chase_junctions: Arc<BTreeSet<u32>>,
+    /// The list of boxes (inside-portions) of the board.
+    pub boxes: Arc<Vec<GridBox>>,
+    /// Which rows weren't ``width`` (the first row's length) tiles long when the board was made? They were cut or padded with empty tiles to fit; see ``Board::lint``.
+    pub ragged_rows: Vec<u32>,
+    /// How many tiles still need painting; kept up to date as tiles change so ``board_complete`` needn't look at every tile.
+    pub(crate) tiles_to_paint: u32,
+    /// Where do the board's spawn markers start each enemy (by index; marker ``1`` is enemy 0)?
+    pub enemy_spawns: Vec<Option<TilePoint>>,
+    /// Where does the board's ``P`` marker start the player, if it has one?
+    pub player_spawn: Option<TilePoint>,
+}
+
+/// How a ``Board`` is written in JSON: the same as a board, but with its tiles as a list of rows.
+#[derive(Serialize, Deserialize, JsonSchema)]
+struct BoardRows {
+    /// What are the state of the tiles on the board: rows first, then columns.
+    tiles: Vec<Vec<Tile>>,
+    /// How wide is the board?
+    width: u32,
+    /// How tall is the board?
+    height: u32,
+    /// Which positions (y*width + x) are junctions? Helps MovementAI and painting game logic!
+    junctions: Arc<BTreeSet<u32>>,
     /// Which junctions trigger chases?
-    pub chase_junctions: HashSet<u32>,
+    chase_junctions: Arc<BTreeSet<u32>>,
     /// The list of boxes (inside-portions) of the board.
-    pub boxes: Vec<GridBox>,
+    boxes: Arc<Vec<GridBox>>,
+    /// Where do the board's spawn markers start each enemy (by index; marker ``1`` is enemy 0)?
+    #[serde(default)]
+    enemy_spawns: Vec<Option<TilePoint>>,
+    /// Where does the board's ``P`` marker start the player, if it has one?
+    #[serde(default)]
+    player_spawn: Option<TilePoint>,
+}
+
+impl Serialize for Board {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BoardRows {
+            tiles: self.rows().map(|row| row.to_vec()).collect(),
+            width: self.width,
+            height: self.height,
+            junctions: self.junctions.clone(),
+            chase_junctions: self.chase_junctions.clone(),
+            boxes: self.boxes.clone(),
+            enemy_spawns: self.enemy_spawns.clone(),
+            player_spawn: self.player_spawn.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Board, D::Error> {
+        let rows = BoardRows::deserialize(deserializer)?;
+        let (tiles, ragged_rows) = flatten_rows(rows.tiles, rows.width, rows.height);
+        Ok(Board {
+            tiles_to_paint: Board::count_tiles_to_paint(&tiles),
+            tiles: Arc::new(tiles),
+            width: rows.width,
+            height: rows.height,
+            junctions: rows.junctions,
+            chase_junctions: rows.chase_junctions,
+            boxes: rows.boxes,
+            ragged_rows,
+            enemy_spawns: rows.enemy_spawns,
+            player_spawn: rows.player_spawn,
+        })
+    }
+}
+
+impl JsonSchema for Board {
+    fn schema_name() -> String {
+        "Board".to_owned()
+    }
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        BoardRows::json_schema(gen)
+    }
+}
+
+/// Lay ``rows`` out one after another, each exactly ``width`` tiles long, and with exactly ``height`` of them; returns the tiles and which rows had to be cut or padded.
+pub(crate) fn flatten_rows(rows: Vec<Vec<Tile>>, width: u32, height: u32) -> (Vec<Tile>, Vec<u32>) {
+    let mut tiles = Vec::with_capacity((width * height) as usize);
+    let mut ragged_rows = Vec::new();
+    for (y, mut row) in rows.into_iter().take(height as usize).enumerate() {
+        if row.len() != width as usize {
+            ragged_rows.push(y as u32);
+            row.resize(width as usize, Tile::Empty);
+        }
+        tiles.extend(row);
+    }
+    tiles.resize((width * height) as usize, Tile::Empty);
+    (tiles, ragged_rows)
+}
+
+/// What ``Board::lint`` found out about a board; useful when authoring custom boards.
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct BoardReport {
+    /// How wide is the board (its first row)?
+    pub width: u32,
+    /// How tall is the board?
+    pub height: u32,
+    /// Which rows (0-based) are a different length than the first?
+    pub ragged_rows: Vec<u32>,
+    /// How many tiles can be walked on?
+    pub walkable_tiles: u32,
+    /// How many tiles need painting to clear the board?
+    pub paint_tiles: u32,
+    /// How many junctions are there?
+    pub junctions: u32,
+    /// How many of the junctions trigger chases?
+    pub chase_junctions: u32,
+    /// How many boxes can be filled in?
+    pub boxes: u32,
+    /// How many separate walkable regions are there? A playable board has exactly one.
+    pub regions: u32,
+    /// Which tiles have exactly one walkable neighbor, so mobs must turn around on them?
+    pub dead_ends: Vec<TilePoint>,
+    /// Where was the player asked to start?
+    pub start: TilePoint,
+    /// Can the player start where we asked?
+    pub start_walkable: bool,
+    /// Which tiles need painting but can't be reached from the start? If any, the level can never be cleared.
+    pub unreachable: Vec<TilePoint>,
+}
+
+/// Something that makes a board unplayable; see ``Board::validate``.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum BoardProblem {
+    /// Some rows are a different length than the first.
+    RaggedRows {
+        /// Which rows (0-based)?
+        rows: Vec<u32>,
+        /// How wide is the first row?
+        width: u32,
+    },
+    /// The player start is not on the track.
+    StartNotWalkable {
+        /// Where the player was asked to start.
+        start: TilePoint,
+    },
+    /// The track is in more than one piece.
+    SplitRegions {
+        /// How many pieces?
+        regions: u32,
+    },
+    /// Some tiles that need painting can't be reached from the start, so the level can never be cleared.
+    Unreachable {
+        /// Which tiles?
+        tiles: Vec<TilePoint>,
+    },
+    /// No tile needs painting, so the level is over before it starts.
+    NothingToPaint,
 }
 
-/// This struct is temporarily used inside of the game logic, to ensure purely-functional behavior in certain points. Encodes any changes to the board that could happen in a single update.
+/// What a mob gets to see of everyone else when it picks where to go next.
+pub struct Surroundings<'a> {
+    /// The player, when the mob is an enemy.
+    pub player: Option<Mob>,
+    /// Where every enemy (by index in the enemy list) is this frame.
+    pub enemies: &'a [TilePoint],
+    /// What the targeting enemies are trying to do.
+    pub mode: EnemyMode,
+    /// Can the player catch this mob right now (chase mode)?
+    pub catchable: bool,
+    /// How does this mob move while it's catchable?
+    pub frightened_policy: FrightenedPolicy,
+    /// Should this mob forget its AI and head straight for the player? See ``elroy_part1_tiles``.
+    pub elroy: bool,
+}
+
+/// This struct is temporarily used inside of the game logic, to ensure purely-functional behavior in certain points. Encodes any changes to the board that could happen in a single update; the latest is kept in ``StateCore::last_board_update``.
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug, JsonSchema)]
 pub struct BoardUpdate {
     /// Number of vertical segments filled in.
@@ -198,10 +947,45 @@ pub struct BoardUpdate {
     pub num_boxes: i32,
     /// Whether we just triggered chase mode or not.
     pub triggers_chase: bool,
+    /// The number of penalty tiles painted.
+    pub penalties: i32,
     /// If we just painted something, the start junction and the end junction as a tuple!
     pub junctions: Option<(u32, u32)>,
 }
 
+/// Where the most recent frame's points came from; the parts add up to its score change. See the ``last_reward_breakdown`` query.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RewardBreakdown {
+    /// Points for painting segments.
+    pub painting: i32,
+    /// Points for filling boxes, including the corner boxes that start chase mode.
+    pub boxes: i32,
+    /// Points for catching enemies in chase mode.
+    pub enemies: i32,
+    /// Points for bonus gates and clearing the level.
+    pub bonuses: i32,
+    /// Points lost to painting penalty tiles (zero or less).
+    pub penalties: i32,
+    /// Points taken back by a death, with ``rollback_score_on_death`` (zero or less).
+    pub death: i32,
+}
+
+impl RewardBreakdown {
+    /// The score change these parts add up to.
+    pub fn total(&self) -> i32 {
+        self.painting + self.boxes + self.enemies + self.bonuses + self.penalties + self.death
+    }
+    /// Add ``other``'s points to these, part by part.
+    pub fn add(&mut self, other: &RewardBreakdown) {
+        self.painting += other.painting;
+        self.boxes += other.boxes;
+        self.enemies += other.enemies;
+        self.bonuses += other.bonuses;
+        self.penalties += other.penalties;
+        self.death += other.death;
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, JsonSchema)]
 pub struct StateCore {
     /// Where are random numbers drawn from?
@@ -221,9 +1005,166 @@ pub struct StateCore {
     /// The position and state of the player.
     pub player: Mob,
     /// The position and other state for the enemies.
+    /// Their index is their id everywhere (events, per-enemy counters, ``ai_debug``); enemies move in this order, and collisions are resolved in it after everyone has moved, so the lowest-numbered enemy touching the player gets the kill.
     pub enemies: Vec<Mob>,
     /// A representation of the current game board.
     pub board: Board,
+    /// When ``debug_ai`` is set: the decision each enemy (by index) made during the most recent frame, if it made one.
+    #[serde(skip)]
+    pub ai_debug: Vec<Option<AiDecision>>,
+    /// The reward for the most recent frame: its score change after ``reward_scale`` and ``reward_clip``.
+    #[serde(default)]
+    pub reward: f32,
+    /// Events that happened during the most recent frame.
+    #[serde(default)]
+    pub events: Vec<GameEvent>,
+    /// Where the most recent frame's points came from.
+    #[serde(default)]
+    pub reward_breakdown: RewardBreakdown,
+    /// What the player's most recent frame that changed the board changed, if any.
+    #[serde(default)]
+    pub last_board_update: Option<BoardUpdate>,
+    /// How many frames have been played on the current level?
+    #[serde(default)]
+    pub level_frames: i32,
+    /// How many frames have been played since the game started, counting catch freezes?
+    #[serde(default)]
+    pub total_frames: i32,
+    /// How many lives have been lost on the current level?
+    #[serde(default)]
+    pub level_deaths: i32,
+    /// How many tiles needed painting when the current level started? See ``enemy_releases``.
+    #[serde(default)]
+    pub level_tiles_to_paint: u32,
+    /// How many frames in a row has the player stayed on the same tile?
+    #[serde(default)]
+    pub stalled_frames: i32,
+    /// When ``track_visits`` or an ``exploration_bonus`` is set: how many times has the player entered each tile (y*width + x) this game?
+    #[serde(default)]
+    pub visit_counts: Vec<u32>,
+    /// Recent events, oldest first, kept across frames until drained; at most ``event_log_limit`` long.
+    #[serde(default)]
+    pub event_log: VecDeque<GameEvent>,
+    /// How many events have ever been logged? Lets pollers tell which entries of ``event_log`` they have already seen.
+    #[serde(default)]
+    pub events_logged: u64,
+    /// How many times has each enemy (by index in ``enemies``) killed the player this game?
+    #[serde(default)]
+    pub kills_by_enemy: Vec<u32>,
+    /// How many times has each enemy (by index in ``enemies``) been caught this game?
+    #[serde(default)]
+    pub catches_by_enemy: Vec<u32>,
+    /// While the game is paused after catching an enemy: what to show, and for how long.
+    #[serde(default)]
+    pub catch_freeze: Option<CatchFreeze>,
+    /// When ``audit_rng`` is set: every draw from ``rand`` this game, oldest first.
+    #[serde(default)]
+    pub rng_audit: Vec<RngDraw>,
+    /// How many enemies has the player caught during the current chase mode?
+    #[serde(default)]
+    pub chase_catches: i32,
+    /// How many lives has the player won back (by clearing levels) this game?
+    #[serde(default)]
+    pub lives_gained: i32,
+    /// Has the ``extra_life_score`` life been won yet?
+    #[serde(default)]
+    pub extra_life_awarded: bool,
+    /// Did the player lose a life during the most recent frame?
+    #[serde(default)]
+    pub life_lost: bool,
+    /// Has the game ended? Set when the lives run out (or a stall ends it); from then on, frames change nothing.
+    #[serde(default)]
+    pub game_over: bool,
+    /// Which ``mode_schedule`` entry are the enemies following?
+    #[serde(default)]
+    pub mode_index: usize,
+    /// How many frames has the current ``mode_schedule`` entry been running?
+    #[serde(default)]
+    pub mode_timer: i32,
+    /// How many more frames is the player slowed for painting a penalty tile?
+    #[serde(default)]
+    pub penalty_slow_timer: i32,
+    /// Which of ``bonus_gates`` has the player reached this level?
+    #[serde(default)]
+    pub gates_reached: Vec<bool>,
+    /// How many tiles has the ``unpainter`` entered since it last unpainted one?
+    #[serde(default)]
+    pub unpainter_tiles: u32,
+    /// How many more frames are left of the grace period after the last lost life? See ``respawn_grace_frames``.
+    #[serde(default)]
+    pub grace_timer: i32,
+    /// What part of the game is playing: the level, or one of the pauses around it?
+    #[serde(default)]
+    pub phase: GamePhase,
+    /// How many more frames until ``phase`` moves on? Unused while ``Playing`` or at ``GameOver``.
+    #[serde(default)]
+    pub phase_timer: i32,
+    /// What buttons were pressed last frame? Repeated by ``action_repeat_probability``.
+    #[serde(default)]
+    pub last_buttons: Input,
+    /// Which direction is the joystick resting in? See ``latch_directions``; cleared when a life or level starts.
+    #[serde(default)]
+    pub latched_direction: Option<Direction>,
+    /// Which points are still being shown where they were scored?
+    #[serde(default)]
+    pub score_popups: Vec<ScorePopup>,
+    /// The last ``observation_stack`` board tensors, oldest first; saved with the game so a restored state stacks exactly like the original.
+    #[serde(default)]
+    pub observations: VecDeque<Vec<Vec<Vec<u8>>>>,
+    /// Which "Cruise Elroy" stage is the first enemy in: 0 (none), 1 or 2? See ``elroy_part1_tiles``.
+    #[serde(default)]
+    pub elroy_stage: u8,
+    /// What a ``DifficultyController`` last set the level parameters to; replaces ``difficulty_table`` until the next adjustment.
+    #[serde(default)]
+    pub level_override: Option<LevelParams>,
+}
+
+/// Who drew from the game's random number generator, and why; see ``audit_rng``.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum RngPurpose {
+    /// The game's generator was split off the config's to start a new game.
+    NewGame,
+    /// The player's movement.
+    PlayerMove,
+    /// An enemy AI choosing where to go.
+    EnemyMove,
+    /// Deciding whether to repeat the last buttons; see ``action_repeat_probability``.
+    StickyAction,
+}
+
+/// One entry of the ``rng_audit`` log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RngDraw {
+    /// Which level was being played?
+    pub level: i32,
+    /// Which frame of that level (see ``level_frames``)?
+    pub frame: i32,
+    /// Which enemy (index in ``enemies``) drew, if it was an enemy?
+    pub mob: Option<usize>,
+    /// Why was it drawn?
+    pub purpose: RngPurpose,
+}
+
+/// A short pause after catching an enemy, showing the bonus where the catch happened; see ``catch_freeze_frames``.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CatchFreeze {
+    /// How many more frames will the game stay paused?
+    pub frames_left: i32,
+    /// Where was the enemy caught?
+    pub position: WorldPoint,
+    /// How many points was it worth?
+    pub bonus: i32,
+}
+
+/// Points shown for a while where they were scored; see ``score_popup_frames``.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScorePopup {
+    /// Where were the points scored?
+    pub position: WorldPoint,
+    /// How many points?
+    pub points: i32,
+    /// How many more frames will it be shown?
+    pub frames_left: i32,
 }
 
 /// Wrapping the current game config into one struct with the current frame state.
@@ -235,13 +1176,110 @@ pub struct State {
     pub state: StateCore,
 }
 
+/// What happened in one ``AmidarEnv::step``.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepResult {
+    /// The shaped reward for this frame; see ``Amidar::shape_reward``.
+    pub reward: f32,
+    /// The score after this frame.
+    pub score: i32,
+    /// Lives left after this frame.
+    pub lives: i32,
+    /// Is the episode over? Honors ``Amidar::episodic_life``.
+    pub terminal: bool,
+    /// Everything notable that happened this frame.
+    pub events: Vec<GameEvent>,
+}
+
+/// A config and the game it is playing, for Rust users who want to step a game without going through the ``Simulation`` and ``State`` trait objects.
+#[derive(Clone)]
+pub struct AmidarEnv {
+    pub(crate) game: State,
+}
+
+/// How one seeded episode of ``evaluate`` went.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Episode {
+    /// The seed the game was reset with.
+    pub seed: u32,
+    /// The score when the episode ended.
+    pub score: i32,
+    /// How many frames the episode lasted.
+    pub frames: usize,
+    /// Which level the player reached.
+    pub level: i32,
+    /// Did the game end, rather than running out of frames?
+    pub finished: bool,
+}
+
+/// The result of ``evaluate``: every episode, plus summary statistics over their scores and lengths.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Evaluation {
+    /// One entry per seed, in the order given.
+    pub episodes: Vec<Episode>,
+    /// Mean score over all episodes.
+    pub mean_score: f64,
+    /// Population standard deviation of the scores.
+    pub std_score: f64,
+    /// Lowest score of any episode.
+    pub min_score: i32,
+    /// Highest score of any episode.
+    pub max_score: i32,
+    /// Mean episode length in frames.
+    pub mean_frames: f64,
+}
+
+/// The first frame where a run disagreed with the reference run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Divergence {
+    /// Which run disagreed: "replay" (a second run from scratch) or "restore" (saved and restored from JSON halfway through).
+    pub run: String,
+    /// The first frame (counting from 1) after which the two states differed.
+    pub frame: usize,
+}
+
+/// The result of ``determinism``.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DeterminismReport {
+    /// The seed that drove both the game and the input script.
+    pub seed: u32,
+    /// How many frames each run was stepped.
+    pub n_frames: usize,
+    /// Every run that did not match the reference; empty when everything is reproducible.
+    pub divergences: Vec<Divergence>,
+}
+
+/// What a ``run`` that found nothing wrong did.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SoakReport {
+    /// How many frames were played.
+    pub frames: usize,
+    /// How many games were started; a new one starts whenever the last runs out of lives.
+    pub games: usize,
+    /// The highest level any game reached.
+    pub max_level: i32,
+    /// The highest score any game reached.
+    pub max_score: i32,
+}
+
+/// The first invariant a ``run`` saw broken.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Violation {
+    /// How many frames into the run (counting from 1)?
+    pub frame: usize,
+    /// The game as JSON just before the frame that broke it, so the frame can be replayed.
+    pub before: String,
+    /// What was wrong.
+    pub message: String,
+}
+
 /// When we compared the player position to all the enemies, what happened?
 #[derive(PartialEq, Eq, Clone, Copy, JsonSchema)]
 pub enum EnemyPlayerState {
     /// Most of the time: nobody's colliding.
     Miss,
-    /// The player just died.
-    PlayerDeath,
+    /// The player was just killed by the given enemy (id by index in state.enemies list!)
+    PlayerDeath(usize),
     /// In chase mode, the player just caught the given enemy (id by index in state.enemies list!)
     EnemyCatch(usize),
 }
@@ -9,21 +9,44 @@ pub use toybox_core::State;
 
 /// This method returns a Box<Simulation> if possible for a given game name.
 pub fn get_simulation_by_name(name: &str) -> Result<Box<dyn Simulation>, String> {
-    match name.to_lowercase().as_str() {
-        #[cfg(feature = "amidar")]
-        "amidar" => Ok(Box::new(amidar::Amidar::default())),
-        #[cfg(feature = "breakout")]
-        "breakout" => Ok(Box::new(breakout::Breakout::default())),
-        #[cfg(feature = "gridworld")]
-        "gridworld" => Ok(Box::new(gridworld::GridWorld::default())),
-        #[cfg(feature = "space_invaders")]
-        "space_invaders" => Ok(Box::new(space_invaders::SpaceInvaders::default())),
-        #[cfg(feature = "pong")]
-        "pong" => Ok(Box::new(pong::PongConfig::default())),
-        _ => Err(format!(
-            "Cannot construct game: `{}`. Try any of {:?}.",
-            name, GAME_LIST
-        )),
+    let lower = name.to_lowercase();
+    games::registry()
+        .iter()
+        .find(|(known, _)| *known == lower)
+        .map(|(_, make)| make())
+        .ok_or_else(|| {
+            format!(
+                "Cannot construct game: `{}`. Try any of {:?}.",
+                name, GAME_LIST
+            )
+        })
+}
+
+/// The games compiled into this build, by name, so frontends and the FFI layer can start one from a string.
+pub mod games {
+    use toybox_core::Simulation;
+
+    /// Makes the default config of one game.
+    pub type Constructor = fn() -> Box<dyn Simulation>;
+
+    fn boxed<S: Simulation + Default + 'static>() -> Box<dyn Simulation> {
+        Box::new(S::default())
+    }
+
+    /// Every game enabled by a feature, with its default-config constructor; in the same order as ``GAME_LIST``.
+    pub fn registry() -> &'static [(&'static str, Constructor)] {
+        &[
+            #[cfg(feature = "amidar")]
+            ("amidar", boxed::<amidar::Amidar>),
+            #[cfg(feature = "breakout")]
+            ("breakout", boxed::<breakout::Breakout>),
+            #[cfg(feature = "space_invaders")]
+            ("space_invaders", boxed::<space_invaders::SpaceInvaders>),
+            #[cfg(feature = "pong")]
+            ("pong", boxed::<pong::PongConfig>),
+            #[cfg(feature = "gridworld")]
+            ("gridworld", boxed::<gridworld::GridWorld>),
+        ]
     }
 }
 
@@ -56,3 +79,19 @@ extern crate pong;
 /// Space Invaders logic defined in this module.
 #[cfg(feature = "space_invaders")]
 extern crate space_invaders;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_matches_game_list() {
+        let names: Vec<&str> = games::registry().iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, GAME_LIST);
+        for name in GAME_LIST {
+            let sim = get_simulation_by_name(&name.to_uppercase()).unwrap();
+            assert!(!sim.legal_action_set().is_empty());
+        }
+        assert!(get_simulation_by_name("pacman").is_err());
+    }
+}